@@ -0,0 +1,215 @@
+use x11_client::*;
+
+use std::io::prelude::*;
+use std::io::{self, Result};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+
+const USAGE: &str =
+    "usage: clip [-display DISPLAY] [-selection primary|clipboard|both] [-target TARGET]
+            [-foreground] [-in | -out | -targets]
+
+Copies standard input to a selection (-in, the default), pastes a selection to standard output
+(-out), or lists the targets a selection can be pasted as (-targets). The selection defaults to
+CLIPBOARD.
+
+Without -target, input must be UTF-8 text and is offered as text; output is the selection's
+text. When copying, clip stays in the background to serve the selection until another client
+takes it over, or in the foreground with -foreground.";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    In,
+    Out,
+    Targets,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Selection {
+    Primary,
+    Clipboard,
+    Both,
+}
+
+struct Options {
+    display: Option<String>,
+    selection: Selection,
+    target: Option<String>,
+    foreground: bool,
+    mode: Mode,
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        display: None,
+        selection: Selection::Clipboard,
+        target: None,
+        foreground: false,
+        mode: Mode::In,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "-display" => options.display = Some(value()),
+            "-selection" => {
+                options.selection = match value().as_str() {
+                    "primary" => Selection::Primary,
+                    "clipboard" => Selection::Clipboard,
+                    "both" => Selection::Both,
+                    _ => usage(),
+                }
+            }
+            "-target" => options.target = Some(value()),
+            "-foreground" => options.foreground = true,
+            "-in" => options.mode = Mode::In,
+            "-out" => options.mode = Mode::Out,
+            "-targets" => options.mode = Mode::Targets,
+            "-h" | "-help" | "--help" => usage(),
+            _ => usage(),
+        }
+    }
+
+    if options.selection == Selection::Both && options.mode != Mode::In {
+        usage();
+    }
+    options
+}
+
+extern "C" {
+    fn fork() -> i32;
+    fn dup2(old: i32, new: i32) -> i32;
+}
+
+/// Forks, leaving the child to serve the selection while the parent exits so that the shell
+/// carries on.
+fn daemonize() -> Result<()> {
+    // The child must not hold a pipe open that the shell is reading from.
+    let null = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")?;
+
+    match unsafe { fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => {
+            for fd in 0..=2 {
+                if unsafe { dup2(null.as_raw_fd(), fd) } == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        }
+        _ => std::process::exit(0),
+    }
+}
+
+fn copy(
+    mut conn: Connection<UnixStream>,
+    window: u32,
+    atoms: SelectionAtoms,
+    options: &Options,
+) -> Result<()> {
+    let mut input = Vec::new();
+    io::stdin().read_to_end(&mut input)?;
+
+    let mut owner = SelectionOwner::new(window, atoms);
+    match options.target {
+        Some(ref target) => {
+            let target = intern_atoms(&mut conn, &[target])?[0];
+            owner.set_data(target, input);
+        }
+        None => {
+            let text = String::from_utf8(input).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "input is not UTF-8 text; give a -target for other data",
+                )
+            })?;
+            owner.set_text(&text);
+        }
+    }
+
+    let selections: &[u32] = match options.selection {
+        Selection::Primary => &[atom::PRIMARY],
+        Selection::Clipboard => &[atoms.clipboard],
+        Selection::Both => &[atom::PRIMARY, atoms.clipboard],
+    };
+    for &selection in selections {
+        if !owner.acquire(&mut conn, selection)? {
+            return Err(io::Error::other("another client took the selection"));
+        }
+    }
+
+    if !options.foreground {
+        conn.flush()?;
+        daemonize()?;
+    }
+
+    while owner.is_owner() || owner.is_transferring() {
+        match conn.next_event_timeout(SELECTION_TIMEOUT)? {
+            // Errors about requestors that vanished in the middle of a transfer are ignored, and
+            // a request that cannot be answered must not cost everyone else the selection.
+            Some(event) => {
+                if let Err(e) = owner.handle_event(&mut conn, &event) {
+                    eprintln!("clip: {}", e);
+                }
+            }
+            None => owner.expire_transfers(&mut conn)?,
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let options = parse_args();
+    let (mut conn, screen) = Connection::open(options.display.as_deref())?;
+    let atoms = SelectionAtoms::intern(&mut conn)?;
+    let window = selection_window(&mut conn, screen)?;
+
+    let selection = match options.selection {
+        Selection::Primary => atom::PRIMARY,
+        Selection::Clipboard | Selection::Both => atoms.clipboard,
+    };
+
+    match options.mode {
+        Mode::In => copy(conn, window, atoms, &options),
+        Mode::Out => {
+            let value = match options.target {
+                Some(ref target) => {
+                    let target = intern_atoms(&mut conn, &[target])?[0];
+                    convert_selection(&mut conn, window, &atoms, selection, target, CURRENT_TIME)?
+                        .map(|reply| reply.value)
+                }
+                None => {
+                    selection_text(&mut conn, window, &atoms, selection)?.map(String::into_bytes)
+                }
+            };
+            match value {
+                Some(value) => io::stdout().write_all(&value),
+                None => {
+                    eprintln!("clip: the selection is empty or cannot be converted");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Mode::Targets => {
+            let targets = selection_targets(&mut conn, window, &atoms, selection)?;
+            let targets = targets
+                .into_iter()
+                .filter(|&target| target != atom::NONE)
+                .collect::<Vec<_>>();
+            let mut out = io::stdout().lock();
+            for name in atom_names(&mut conn, &targets)? {
+                writeln!(out, "{}", name)?;
+            }
+            Ok(())
+        }
+    }
+}
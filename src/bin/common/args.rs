@@ -0,0 +1,46 @@
+//! The `-root`, `-id` and `-name` options the binaries use to pick a window.
+
+// Each binary includes this file and uses a different part of it.
+#![allow(dead_code)]
+
+use x11_client::*;
+
+use std::io::{self, Read, Result, Write};
+
+/// The window given on the command line.
+pub enum Target {
+    Root,
+    Id(u32),
+    Name(String),
+}
+
+impl Target {
+    /// Finds the window, taking the root window of `screen` for `Root`.
+    pub fn window<S: Read + Write>(&self, conn: &mut Connection<S>, screen: usize) -> Result<u32> {
+        match *self {
+            Target::Root => Ok(conn
+                .server_init()
+                .roots
+                .get(screen)
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, format!("no screen {}", screen))
+                })?
+                .root),
+            Target::Id(id) => Ok(id),
+            Target::Name(ref name) => find_window_by_name(conn, name)?.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no window named {:?}", name),
+                )
+            }),
+        }
+    }
+}
+
+/// Parses a window ID in decimal or, with a `0x` prefix, hexadecimal.
+pub fn parse_id(id: &str) -> Option<u32> {
+    match id.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => id.parse().ok(),
+    }
+}
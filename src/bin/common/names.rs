@@ -0,0 +1,59 @@
+//! Names for the atoms and enumerated values the binaries print.
+
+// Each binary includes this file and uses a different part of it.
+#![allow(dead_code)]
+
+use x11_client::*;
+
+use std::collections::HashMap;
+use std::io::{Read, Result, Write};
+
+/// Atom names looked up so far, so that each is only asked of the server once.
+pub struct AtomNames {
+    names: HashMap<u32, String>,
+}
+
+impl AtomNames {
+    pub fn new() -> Self {
+        AtomNames {
+            names: HashMap::new(),
+        }
+    }
+
+    pub fn get<S: Read + Write>(&mut self, conn: &mut Connection<S>, atom: u32) -> Result<String> {
+        Ok(self.get_all(conn, &[atom])?.remove(0))
+    }
+
+    /// Looks up the names of `atoms` not seen before in a single round trip.
+    pub fn get_all<S: Read + Write>(
+        &mut self,
+        conn: &mut Connection<S>,
+        atoms: &[u32],
+    ) -> Result<Vec<String>> {
+        let mut missing = atoms
+            .iter()
+            .copied()
+            .filter(|&atom| atom != atom::NONE && !self.names.contains_key(&atom))
+            .collect::<Vec<_>>();
+        missing.sort_unstable();
+        missing.dedup();
+        let names = atom_names(conn, &missing)?;
+        self.names.extend(missing.into_iter().zip(names));
+
+        Ok(atoms
+            .iter()
+            .map(|atom| match self.names.get(atom) {
+                Some(name) => name.clone(),
+                None => "None".to_string(),
+            })
+            .collect())
+    }
+}
+
+/// Names `value` from a list of names indexed by value, or gives the number if it is out of range.
+pub fn name_or_number(names: &[&'static str], value: u8) -> String {
+    match names.get(value as usize) {
+        Some(name) => name.to_string(),
+        None => value.to_string(),
+    }
+}
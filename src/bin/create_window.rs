@@ -1,76 +1,123 @@
 use x11_client::*;
 
-use std::io::prelude::*;
 use std::os::unix::net::UnixStream;
 
 fn main() {
-    let mut socket = UnixStream::connect("/tmp/.X11-unix/X0").unwrap();
+    let socket = UnixStream::connect("/tmp/.X11-unix/X0").unwrap();
+    let mut conn = Connection::new(socket).unwrap();
 
-    let client_init: Vec<_> = ClientInit::new().into();
-    socket.write_all(&client_init).unwrap();
+    let screen = &conn.server_init().roots[0];
+    let root = screen.root;
+    let depth = screen.root_depth;
+    let visual = screen.root_visual;
+    let colormap = screen.default_colormap;
 
-    let server_init = ServerInit::from_stream(&mut socket).unwrap();
+    let window = conn.generate_id().unwrap();
+    let pixmap = conn.generate_id().unwrap();
+    let background_gc = conn.generate_id().unwrap();
+    let gc = conn.generate_id().unwrap();
+
+    let background = alloc_pixel(
+        &mut conn,
+        colormap,
+        visual,
+        Rgb {
+            red: 0xcccc,
+            green: 0xffff,
+            blue: 0xcccc,
+        },
+    )
+    .unwrap();
+    let blue = alloc_pixel(
+        &mut conn,
+        colormap,
+        visual,
+        Rgb {
+            red: 0,
+            green: 0,
+            blue: 0xffff,
+        },
+    )
+    .unwrap();
+    let cursor = load_cursor(&mut conn, 0, "crosshair").unwrap();
 
     let create_window = CreateWindow::new(
-        24,
-        server_init.resource_id_base + 1,
-        server_init.roots[0].root,
-        100,
-        100,
-        1024,
-        1024,
-        0,
-        1, // InputOutput
+        depth, window, root, 100, 100, 1024, 1024, 0, 1, // InputOutput
         0, // CopyFromParent
-    );
-    socket.write_all(&create_window.as_bytes()).unwrap();
+    )
+    .with_attributes(WindowAttributes {
+        background_pixel: Some(background),
+        event_mask: Some(event_mask::KEY_PRESS | event_mask::EXPOSURE),
+        cursor: Some(cursor),
+        ..Default::default()
+    });
+    conn.send(&create_window.as_bytes()).unwrap();
+
+    conn.send(&MapWindow::new(window).as_bytes()).unwrap();
 
-    socket
-        .write_all(&MapWindow::new(server_init.resource_id_base + 1).as_bytes())
+    conn.send(&ChangeWmName::new(window, "holy crap that worked".into()).as_bytes())
         .unwrap();
 
-    socket
-        .write_all(
-            &CreateGc::new(
-                server_init.resource_id_base + 2,
-                server_init.resource_id_base + 1,
-                0x00_00_FF,
-            )
-            .as_bytes(),
-        )
+    // Render once into an offscreen pixmap, and copy from it on every Expose.
+    let create_pixmap = CreatePixmap::new(depth, pixmap, window, 1024, 1024);
+    create_pixmap
+        .validate(conn.server_init(), &conn.server_init().roots[0])
         .unwrap();
+    conn.send(&create_pixmap.as_bytes()).unwrap();
 
-    socket
-        .write_all(
-            &ChangeWmName::new(
-                server_init.resource_id_base + 1,
-                "holy crap that worked".into(),
-            )
-            .as_bytes(),
-        )
+    conn.send(&CreateGc::new(background_gc, pixmap, background).as_bytes())
+        .unwrap();
+    conn.send(&CreateGc::new(gc, pixmap, blue).as_bytes())
         .unwrap();
 
-    loop {
-        let mut buf = [0 as u8; 32];
-        socket.read_exact(&mut buf).unwrap();
+    conn.send(
+        &PolyFillRectangle::new(
+            pixmap,
+            background_gc,
+            &[Rectangle {
+                x: 0,
+                y: 0,
+                width: 1024,
+                height: 1024,
+            }],
+        )
+        .as_bytes(),
+    )
+    .unwrap();
+    conn.send(
+        &PolyFillRectangle::new(
+            pixmap,
+            gc,
+            &[Rectangle {
+                x: 256,
+                y: 256,
+                width: 512,
+                height: 512,
+            }],
+        )
+        .as_bytes(),
+    )
+    .unwrap();
 
-        let event = Event::from_bytes(&buf);
+    loop {
+        let event = conn.next_event().unwrap();
         println!("event: {:?}", event);
 
-        if let Event::Expose { .. } = event {
-            socket
-                .write_all(
-                    &PolyFillRectangle::new(
-                        server_init.resource_id_base + 1,
-                        server_init.resource_id_base + 2,
-                        256,
-                        256,
-                        512,
-                        512,
-                    )
-                    .as_bytes(),
+        if let Event::Expose {
+            x,
+            y,
+            width,
+            height,
+            ..
+        } = event
+        {
+            conn.send(
+                &CopyArea::new(
+                    pixmap, window, gc, x as i16, y as i16, x as i16, y as i16, width, height,
                 )
-                .unwrap();
+                .as_bytes(),
+            )
+            .unwrap();
         }
     }
 }
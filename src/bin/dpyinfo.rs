@@ -0,0 +1,364 @@
+use x11_client::*;
+
+use std::io::prelude::*;
+use std::io::{self, Result};
+
+#[path = "common/json.rs"]
+mod json;
+
+use json::Json;
+
+const USAGE: &str = "usage: dpyinfo [-display DISPLAY] [--json]
+
+Describes the display: the server and its connection parameters, the extensions it supports,
+and each screen with its depths and visuals. --json writes the same as a JSON object.";
+
+struct Options {
+    display: Option<String>,
+    json: bool,
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        display: None,
+        json: false,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "-display" => options.display = Some(value()),
+            "-json" | "--json" => options.json = true,
+            "-h" | "-help" | "--help" => usage(),
+            _ => usage(),
+        }
+    }
+
+    options
+}
+
+fn byte_order_name(order: u8) -> &'static str {
+    match order {
+        0 => "LSBFirst",
+        _ => "MSBFirst",
+    }
+}
+
+fn backing_store_name(backing_store: u8) -> &'static str {
+    match backing_store {
+        0 => "NO",
+        1 => "WHEN MAPPED",
+        _ => "YES",
+    }
+}
+
+/// Dots per inch along one dimension, rounded.
+fn resolution(pixels: u16, millimeters: u16) -> u32 {
+    if millimeters == 0 {
+        return 0;
+    }
+    (pixels as f64 * 25.4 / millimeters as f64).round() as u32
+}
+
+/// The server's description, gathered before it is printed in either format.
+struct Info {
+    display: String,
+    default_screen: usize,
+    maximum_request_bytes: usize,
+    extensions: Vec<String>,
+}
+
+fn write_text<W: Write>(out: &mut W, init: &ServerInit, info: &Info) -> Result<()> {
+    writeln!(out, "name of display:    {}", info.display)?;
+    writeln!(out, "version number:    {}.{}", init.major, init.minor)?;
+    writeln!(out, "vendor string:    {}", init.vendor)?;
+    writeln!(out, "vendor release number:    {}", init.release_number)?;
+    writeln!(
+        out,
+        "maximum request size:  {} bytes",
+        info.maximum_request_bytes
+    )?;
+    writeln!(out, "motion buffer size:  {}", init.motion_buffer_size)?;
+    writeln!(
+        out,
+        "bitmap unit, bit order, padding:    {}, {}, {}",
+        init.bitmap_format_scanline_unit,
+        byte_order_name(init.bitmap_format_bit_order),
+        init.bitmap_format_scanline_pad
+    )?;
+    writeln!(
+        out,
+        "image byte order:    {}",
+        byte_order_name(init.image_byte_order)
+    )?;
+    writeln!(
+        out,
+        "number of supported pixmap formats:    {}",
+        init.pixmap_formats.len()
+    )?;
+    writeln!(out, "supported pixmap formats:")?;
+    for format in &init.pixmap_formats {
+        writeln!(
+            out,
+            "    depth {}, bits_per_pixel {}, scanline_pad {}",
+            format.depth, format.bits_per_pixel, format.scanline_pad
+        )?;
+    }
+    writeln!(
+        out,
+        "keycode range:    minimum {}, maximum {}",
+        init.min_keycode, init.max_keycode
+    )?;
+    writeln!(out, "number of extensions:    {}", info.extensions.len())?;
+    for extension in &info.extensions {
+        writeln!(out, "    {}", extension)?;
+    }
+    writeln!(out, "default screen number:    {}", info.default_screen)?;
+    writeln!(out, "number of screens:    {}", init.roots.len())?;
+
+    for (number, screen) in init.roots.iter().enumerate() {
+        writeln!(out)?;
+        write_screen(out, number, screen)?;
+    }
+    Ok(())
+}
+
+fn write_screen<W: Write>(out: &mut W, number: usize, screen: &Screen) -> Result<()> {
+    let depths = screen
+        .allowed_depths
+        .iter()
+        .map(|depth| depth.depth.to_string())
+        .collect::<Vec<_>>();
+    let visuals = screen
+        .allowed_depths
+        .iter()
+        .flat_map(|depth| {
+            depth
+                .visuals
+                .iter()
+                .map(move |visual| (depth.depth, visual))
+        })
+        .collect::<Vec<_>>();
+    let root_visual = visuals
+        .iter()
+        .find(|(_, visual)| visual.id == screen.root_visual);
+
+    writeln!(out, "screen #{}:", number)?;
+    writeln!(
+        out,
+        "  dimensions:    {}x{} pixels ({}x{} millimeters)",
+        screen.width_pixels,
+        screen.height_pixels,
+        screen.width_millimeters,
+        screen.height_millimeters
+    )?;
+    writeln!(
+        out,
+        "  resolution:    {}x{} dots per inch",
+        resolution(screen.width_pixels, screen.width_millimeters),
+        resolution(screen.height_pixels, screen.height_millimeters)
+    )?;
+    writeln!(out, "  depths ({}):    {}", depths.len(), depths.join(", "))?;
+    writeln!(out, "  root window id:    {:#x}", screen.root)?;
+    writeln!(
+        out,
+        "  depth of root window:    {} planes",
+        screen.root_depth
+    )?;
+    writeln!(
+        out,
+        "  number colormaps:    minimum {}, maximum {}",
+        screen.min_installed_maps, screen.max_installed_maps
+    )?;
+    writeln!(out, "  default colormap:    {:#x}", screen.default_colormap)?;
+    if let Some((_, visual)) = root_visual {
+        writeln!(
+            out,
+            "  default number of colormap cells:    {}",
+            visual.colormap_entries
+        )?;
+    }
+    writeln!(
+        out,
+        "  preallocated pixels:    black {}, white {}",
+        screen.black_pixel, screen.white_pixel
+    )?;
+    writeln!(
+        out,
+        "  options:    backing-store {}, save-unders {}",
+        backing_store_name(screen.backing_stores),
+        if screen.save_unders { "YES" } else { "NO" }
+    )?;
+    writeln!(
+        out,
+        "  current input event mask:    {:#x}",
+        screen.current_input_masks
+    )?;
+    writeln!(out, "  number of visuals:    {}", visuals.len())?;
+    writeln!(out, "  default visual id:  {:#x}", screen.root_visual)?;
+    for (depth, visual) in visuals {
+        let per_subfield = match visual.class {
+            VisualClass::TrueColor | VisualClass::DirectColor => " per subfield",
+            _ => "",
+        };
+        writeln!(out, "  visual:")?;
+        writeln!(out, "    visual id:    {:#x}", visual.id)?;
+        writeln!(out, "    class:    {:?}", visual.class)?;
+        writeln!(out, "    depth:    {} planes", depth)?;
+        writeln!(
+            out,
+            "    available colormap entries:    {}{}",
+            visual.colormap_entries, per_subfield
+        )?;
+        writeln!(
+            out,
+            "    red, green, blue masks:    {:#x}, {:#x}, {:#x}",
+            visual.red_mask, visual.green_mask, visual.blue_mask
+        )?;
+        writeln!(
+            out,
+            "    significant bits in color specification:    {} bits",
+            visual.bits_per_rgb_value
+        )?;
+    }
+    Ok(())
+}
+
+fn screen_json(screen: &Screen) -> Json {
+    let depths = screen
+        .allowed_depths
+        .iter()
+        .map(|depth| {
+            let visuals = depth
+                .visuals
+                .iter()
+                .map(|visual| {
+                    Json::Object(vec![
+                        ("id", visual.id.into()),
+                        ("class", format!("{:?}", visual.class).into()),
+                        ("bits_per_rgb_value", visual.bits_per_rgb_value.into()),
+                        ("colormap_entries", visual.colormap_entries.into()),
+                        ("red_mask", visual.red_mask.into()),
+                        ("green_mask", visual.green_mask.into()),
+                        ("blue_mask", visual.blue_mask.into()),
+                    ])
+                })
+                .collect::<Vec<_>>();
+            Json::Object(vec![
+                ("depth", depth.depth.into()),
+                ("visuals", visuals.into()),
+            ])
+        })
+        .collect::<Vec<_>>();
+
+    Json::Object(vec![
+        ("root", screen.root.into()),
+        ("width", screen.width_pixels.into()),
+        ("height", screen.height_pixels.into()),
+        ("width_millimeters", screen.width_millimeters.into()),
+        ("height_millimeters", screen.height_millimeters.into()),
+        ("root_depth", screen.root_depth.into()),
+        ("root_visual", screen.root_visual.into()),
+        ("default_colormap", screen.default_colormap.into()),
+        ("min_installed_maps", screen.min_installed_maps.into()),
+        ("max_installed_maps", screen.max_installed_maps.into()),
+        ("white_pixel", screen.white_pixel.into()),
+        ("black_pixel", screen.black_pixel.into()),
+        (
+            "backing_stores",
+            backing_store_name(screen.backing_stores).into(),
+        ),
+        ("save_unders", screen.save_unders.into()),
+        ("current_input_masks", screen.current_input_masks.into()),
+        ("depths", depths.into()),
+    ])
+}
+
+fn to_json(init: &ServerInit, info: &Info) -> Json {
+    let pixmap_formats = init
+        .pixmap_formats
+        .iter()
+        .map(|format| {
+            Json::Object(vec![
+                ("depth", format.depth.into()),
+                ("bits_per_pixel", format.bits_per_pixel.into()),
+                ("scanline_pad", format.scanline_pad.into()),
+            ])
+        })
+        .collect::<Vec<_>>();
+
+    Json::Object(vec![
+        ("display", info.display.as_str().into()),
+        ("protocol_major", init.major.into()),
+        ("protocol_minor", init.minor.into()),
+        ("vendor", init.vendor.as_str().into()),
+        ("release_number", init.release_number.into()),
+        ("resource_id_base", init.resource_id_base.into()),
+        ("resource_id_mask", init.resource_id_mask.into()),
+        ("maximum_request_bytes", info.maximum_request_bytes.into()),
+        ("motion_buffer_size", init.motion_buffer_size.into()),
+        (
+            "image_byte_order",
+            byte_order_name(init.image_byte_order).into(),
+        ),
+        (
+            "bitmap_bit_order",
+            byte_order_name(init.bitmap_format_bit_order).into(),
+        ),
+        (
+            "bitmap_scanline_unit",
+            init.bitmap_format_scanline_unit.into(),
+        ),
+        (
+            "bitmap_scanline_pad",
+            init.bitmap_format_scanline_pad.into(),
+        ),
+        ("min_keycode", init.min_keycode.into()),
+        ("max_keycode", init.max_keycode.into()),
+        ("pixmap_formats", pixmap_formats.into()),
+        ("extensions", info.extensions.clone().into()),
+        ("default_screen", info.default_screen.into()),
+        (
+            "screens",
+            init.roots
+                .iter()
+                .map(screen_json)
+                .collect::<Vec<_>>()
+                .into(),
+        ),
+    ])
+}
+
+fn main() -> Result<()> {
+    let options = parse_args();
+    let (mut conn, default_screen) = Connection::open(options.display.as_deref())?;
+
+    let maximum_request_bytes = conn.maximum_request_length()? * 4;
+    let mut extensions =
+        ListExtensionsReply::from_bytes(&conn.request(&ListExtensions::new().as_bytes())?)?.names;
+    extensions.sort();
+
+    let info = Info {
+        display: options
+            .display
+            .or_else(|| std::env::var("DISPLAY").ok())
+            .unwrap_or_default(),
+        default_screen,
+        maximum_request_bytes,
+        extensions,
+    };
+
+    let mut out = io::stdout().lock();
+    if options.json {
+        writeln!(out, "{}", to_json(conn.server_init(), &info))?;
+    } else {
+        write_text(&mut out, conn.server_init(), &info)?;
+    }
+    out.flush()
+}
@@ -0,0 +1,870 @@
+use x11_client::*;
+
+use std::io::prelude::*;
+use std::io::{self, Result};
+use std::os::unix::net::UnixStream;
+
+#[path = "common/args.rs"]
+mod args;
+#[path = "common/names.rs"]
+mod names;
+
+use args::{parse_id, Target};
+use names::{name_or_number, AtomNames};
+
+const USAGE: &str = "usage: events [-display DISPLAY] [-root | -id WINDOW]
+
+Creates a window and prints each event it receives, decoded, with the keysym and text of key
+events. With -root or -id, watches an existing window instead; button presses are then left
+out, since only one client may select them and the window's owner usually has.";
+
+const CONTROL_MASK: u16 = 1 << 2;
+
+struct Options {
+    display: Option<String>,
+    /// The window to watch, or `None` to create one.
+    target: Option<Target>,
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        display: None,
+        target: None,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "-display" => options.display = Some(value()),
+            "-root" => options.target = Some(Target::Root),
+            "-id" => {
+                options.target = Some(Target::Id(parse_id(&value()).unwrap_or_else(|| usage())))
+            }
+            "-h" | "-help" | "--help" => usage(),
+            _ => usage(),
+        }
+    }
+
+    options
+}
+
+fn notify_detail(detail: u8) -> String {
+    let names = [
+        "NotifyAncestor",
+        "NotifyVirtual",
+        "NotifyInferior",
+        "NotifyNonlinear",
+        "NotifyNonlinearVirtual",
+        "NotifyPointer",
+        "NotifyPointerRoot",
+        "NotifyDetailNone",
+    ];
+    name_or_number(&names, detail)
+}
+
+fn notify_mode(mode: u8) -> String {
+    let names = [
+        "NotifyNormal",
+        "NotifyGrab",
+        "NotifyUngrab",
+        "NotifyWhileGrabbed",
+    ];
+    name_or_number(&names, mode)
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "YES"
+    } else {
+        "NO"
+    }
+}
+
+/// The text a key types: the keysym's character, turned into a control character when Control
+/// is held, as `XLookupString` does.
+fn key_text(keysym: u32, state: u16) -> Option<char> {
+    let c = keysym::to_char(keysym)?;
+    if state & CONTROL_MASK == 0 {
+        return Some(c);
+    }
+    match c {
+        '@'..='~' => Some((c as u8 & 0x1f) as char),
+        ' ' | '2' => Some('\0'),
+        '3'..='7' => Some((c as u8 - b'3' + 0x1b) as char),
+        '8' => Some('\x7f'),
+        '/' => Some('\x1f'),
+        c => Some(c),
+    }
+}
+
+fn describe_key(keymap: &Keymap, keycode: u8, state: u16) -> String {
+    let keysym = keymap.keysym(keycode, state);
+    let name = keysym::name(keysym).unwrap_or_else(|| "NoSymbol".to_string());
+    let mut ret = format!("keycode {} (keysym {:#x}, {})", keycode, keysym, name);
+    match key_text(keysym, state) {
+        Some(c) => {
+            let mut utf8 = [0; 4];
+            let bytes = c.encode_utf8(&mut utf8).as_bytes();
+            let hex = bytes
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<Vec<_>>()
+                .join(" ");
+            ret.push_str(&format!(
+                ",\n    text gives {} bytes: ({}) {:?}",
+                bytes.len(),
+                hex,
+                c
+            ));
+        }
+        None => ret.push_str(",\n    text gives 0 bytes"),
+    }
+    ret
+}
+
+/// The fields shared by key, button and motion events.
+#[allow(clippy::too_many_arguments)]
+fn describe_input(
+    name: &str,
+    sequence: u16,
+    event: u32,
+    root: u32,
+    child: u32,
+    time: u32,
+    position: (i16, i16),
+    root_position: (i16, i16),
+    state: u16,
+) -> String {
+    format!(
+        "{} event, serial {}, window {:#x},\n    root {:#x}, subw {:#x}, time {}, ({},{}), \
+         root:({},{}),\n    state {:#x}",
+        name,
+        sequence,
+        event,
+        root,
+        child,
+        time,
+        position.0,
+        position.1,
+        root_position.0,
+        root_position.1,
+        state
+    )
+}
+
+fn describe_client_message(format: u8, data: &[u8; 20]) -> String {
+    let items = match format {
+        8 => data.iter().map(|byte| format!("{:#x}", byte)).collect(),
+        16 => data
+            .chunks_exact(2)
+            .map(|item| format!("{:#x}", u16::from_be_bytes([item[0], item[1]])))
+            .collect(),
+        _ => data
+            .chunks_exact(4)
+            .map(|item| {
+                format!(
+                    "{:#x}",
+                    u32::from_be_bytes([item[0], item[1], item[2], item[3]])
+                )
+            })
+            .collect::<Vec<_>>(),
+    };
+    items.join(" ")
+}
+
+/// Formats an event over one or more lines.
+fn describe(
+    conn: &mut Connection<UnixStream>,
+    keymap: &Keymap,
+    names: &mut AtomNames,
+    event: &Event,
+) -> Result<String> {
+    Ok(match *event {
+        Event::KeyPress {
+            detail,
+            sequence,
+            time,
+            root,
+            event,
+            child,
+            root_x,
+            root_y,
+            event_x,
+            event_y,
+            state,
+            same_screen,
+        } => format!(
+            "{},\n    {}, same_screen {}",
+            describe_input(
+                "KeyPress",
+                sequence,
+                event,
+                root,
+                child,
+                time,
+                (event_x, event_y),
+                (root_x, root_y),
+                state
+            ),
+            describe_key(keymap, detail, state),
+            yes_no(same_screen)
+        ),
+        Event::KeyRelease {
+            detail,
+            sequence,
+            time,
+            root,
+            event,
+            child,
+            root_x,
+            root_y,
+            event_x,
+            event_y,
+            state,
+            same_screen,
+        } => format!(
+            "{},\n    {}, same_screen {}",
+            describe_input(
+                "KeyRelease",
+                sequence,
+                event,
+                root,
+                child,
+                time,
+                (event_x, event_y),
+                (root_x, root_y),
+                state
+            ),
+            describe_key(keymap, detail, state),
+            yes_no(same_screen)
+        ),
+        Event::ButtonPress {
+            detail,
+            sequence,
+            time,
+            root,
+            event,
+            child,
+            root_x,
+            root_y,
+            event_x,
+            event_y,
+            state,
+            same_screen,
+        } => format!(
+            "{},\n    button {}, same_screen {}",
+            describe_input(
+                "ButtonPress",
+                sequence,
+                event,
+                root,
+                child,
+                time,
+                (event_x, event_y),
+                (root_x, root_y),
+                state
+            ),
+            detail,
+            yes_no(same_screen)
+        ),
+        Event::ButtonRelease {
+            detail,
+            sequence,
+            time,
+            root,
+            event,
+            child,
+            root_x,
+            root_y,
+            event_x,
+            event_y,
+            state,
+            same_screen,
+        } => format!(
+            "{},\n    button {}, same_screen {}",
+            describe_input(
+                "ButtonRelease",
+                sequence,
+                event,
+                root,
+                child,
+                time,
+                (event_x, event_y),
+                (root_x, root_y),
+                state
+            ),
+            detail,
+            yes_no(same_screen)
+        ),
+        Event::MotionNotify {
+            detail,
+            sequence,
+            time,
+            root,
+            event,
+            child,
+            root_x,
+            root_y,
+            event_x,
+            event_y,
+            state,
+            same_screen,
+        } => format!(
+            "{},\n    is_hint {}, same_screen {}",
+            describe_input(
+                "MotionNotify",
+                sequence,
+                event,
+                root,
+                child,
+                time,
+                (event_x, event_y),
+                (root_x, root_y),
+                state
+            ),
+            name_or_number(&["NotifyNormal", "NotifyHint"], detail),
+            yes_no(same_screen)
+        ),
+        Event::EnterNotify {
+            detail,
+            sequence,
+            time,
+            root,
+            event,
+            child,
+            root_x,
+            root_y,
+            event_x,
+            event_y,
+            state,
+            mode,
+            same_screen,
+            focus,
+        } => format!(
+            "{},\n    mode {}, detail {}, same_screen {}, focus {}",
+            describe_input(
+                "EnterNotify",
+                sequence,
+                event,
+                root,
+                child,
+                time,
+                (event_x, event_y),
+                (root_x, root_y),
+                state
+            ),
+            notify_mode(mode),
+            notify_detail(detail),
+            yes_no(same_screen),
+            yes_no(focus)
+        ),
+        Event::LeaveNotify {
+            detail,
+            sequence,
+            time,
+            root,
+            event,
+            child,
+            root_x,
+            root_y,
+            event_x,
+            event_y,
+            state,
+            mode,
+            same_screen,
+            focus,
+        } => format!(
+            "{},\n    mode {}, detail {}, same_screen {}, focus {}",
+            describe_input(
+                "LeaveNotify",
+                sequence,
+                event,
+                root,
+                child,
+                time,
+                (event_x, event_y),
+                (root_x, root_y),
+                state
+            ),
+            notify_mode(mode),
+            notify_detail(detail),
+            yes_no(same_screen),
+            yes_no(focus)
+        ),
+        Event::FocusIn {
+            detail,
+            sequence,
+            event,
+            mode,
+        } => format!(
+            "FocusIn event, serial {}, window {:#x},\n    mode {}, detail {}",
+            sequence,
+            event,
+            notify_mode(mode),
+            notify_detail(detail)
+        ),
+        Event::FocusOut {
+            detail,
+            sequence,
+            event,
+            mode,
+        } => format!(
+            "FocusOut event, serial {}, window {:#x},\n    mode {}, detail {}",
+            sequence,
+            event,
+            notify_mode(mode),
+            notify_detail(detail)
+        ),
+        Event::KeymapNotify { keys } => {
+            let pressed = (0..keys.len() * 8)
+                .filter(|&bit| keys[bit / 8] & 1 << (bit % 8) != 0)
+                .map(|bit| (bit + 8).to_string())
+                .collect::<Vec<_>>();
+            format!("KeymapNotify event,\n    keys down: {}", pressed.join(" "))
+        }
+        Event::Expose {
+            sequence,
+            window,
+            x,
+            y,
+            width,
+            height,
+            count,
+        } => format!(
+            "Expose event, serial {}, window {:#x},\n    ({},{}), width {}, height {}, count {}",
+            sequence, window, x, y, width, height, count
+        ),
+        Event::GraphicsExposure {
+            sequence,
+            drawable,
+            x,
+            y,
+            width,
+            height,
+            minor_opcode,
+            count,
+            major_opcode,
+        } => format!(
+            "GraphicsExposure event, serial {}, drawable {:#x},\n    ({},{}), width {}, \
+             height {}, count {}, major {}, minor {}",
+            sequence, drawable, x, y, width, height, count, major_opcode, minor_opcode
+        ),
+        Event::NoExposure {
+            sequence,
+            drawable,
+            minor_opcode,
+            major_opcode,
+        } => format!(
+            "NoExposure event, serial {}, drawable {:#x},\n    major {}, minor {}",
+            sequence, drawable, major_opcode, minor_opcode
+        ),
+        Event::VisibilityNotify {
+            sequence,
+            window,
+            state,
+        } => {
+            let names = [
+                "VisibilityUnobscured",
+                "VisibilityPartiallyObscured",
+                "VisibilityFullyObscured",
+            ];
+            format!(
+                "VisibilityNotify event, serial {}, window {:#x},\n    state {}",
+                sequence,
+                window,
+                name_or_number(&names, state)
+            )
+        }
+        Event::CreateNotify {
+            sequence,
+            parent,
+            window,
+            x,
+            y,
+            width,
+            height,
+            border_width,
+            override_redirect,
+        } => format!(
+            "CreateNotify event, serial {}, parent {:#x}, window {:#x},\n    ({},{}), width {}, \
+             height {}, border_width {}, override {}",
+            sequence,
+            parent,
+            window,
+            x,
+            y,
+            width,
+            height,
+            border_width,
+            yes_no(override_redirect)
+        ),
+        Event::DestroyNotify {
+            sequence,
+            event,
+            window,
+        } => format!(
+            "DestroyNotify event, serial {}, event {:#x}, window {:#x}",
+            sequence, event, window
+        ),
+        Event::UnmapNotify {
+            sequence,
+            event,
+            window,
+            from_configure,
+        } => format!(
+            "UnmapNotify event, serial {}, event {:#x}, window {:#x},\n    from_configure {}",
+            sequence,
+            event,
+            window,
+            yes_no(from_configure)
+        ),
+        Event::MapNotify {
+            sequence,
+            event,
+            window,
+            override_redirect,
+        } => format!(
+            "MapNotify event, serial {}, event {:#x}, window {:#x},\n    override {}",
+            sequence,
+            event,
+            window,
+            yes_no(override_redirect)
+        ),
+        Event::MapRequest {
+            sequence,
+            parent,
+            window,
+        } => format!(
+            "MapRequest event, serial {}, parent {:#x}, window {:#x}",
+            sequence, parent, window
+        ),
+        Event::ReparentNotify {
+            sequence,
+            event,
+            window,
+            parent,
+            x,
+            y,
+            override_redirect,
+        } => format!(
+            "ReparentNotify event, serial {}, event {:#x}, window {:#x},\n    parent {:#x}, \
+             ({},{}), override {}",
+            sequence,
+            event,
+            window,
+            parent,
+            x,
+            y,
+            yes_no(override_redirect)
+        ),
+        Event::ConfigureNotify {
+            sequence,
+            event,
+            window,
+            above_sibling,
+            x,
+            y,
+            width,
+            height,
+            border_width,
+            override_redirect,
+        } => format!(
+            "ConfigureNotify event, serial {}, event {:#x}, window {:#x},\n    ({},{}), width \
+             {}, height {}, border_width {},\n    above {:#x}, override {}",
+            sequence,
+            event,
+            window,
+            x,
+            y,
+            width,
+            height,
+            border_width,
+            above_sibling,
+            yes_no(override_redirect)
+        ),
+        Event::ConfigureRequest {
+            stack_mode,
+            sequence,
+            parent,
+            window,
+            sibling,
+            x,
+            y,
+            width,
+            height,
+            border_width,
+            value_mask,
+        } => {
+            let names = ["Above", "Below", "TopIf", "BottomIf", "Opposite"];
+            format!(
+                "ConfigureRequest event, serial {}, parent {:#x}, window {:#x},\n    ({},{}), \
+                 width {}, height {}, border_width {},\n    above {:#x}, detail {}, value \
+                 {:#x}",
+                sequence,
+                parent,
+                window,
+                x,
+                y,
+                width,
+                height,
+                border_width,
+                sibling,
+                name_or_number(&names, stack_mode),
+                value_mask
+            )
+        }
+        Event::GravityNotify {
+            sequence,
+            event,
+            window,
+            x,
+            y,
+        } => format!(
+            "GravityNotify event, serial {}, event {:#x}, window {:#x},\n    ({},{})",
+            sequence, event, window, x, y
+        ),
+        Event::ResizeRequest {
+            sequence,
+            window,
+            width,
+            height,
+        } => format!(
+            "ResizeRequest event, serial {}, window {:#x},\n    width {}, height {}",
+            sequence, window, width, height
+        ),
+        Event::CirculateNotify {
+            sequence,
+            event,
+            window,
+            place,
+        } => format!(
+            "CirculateNotify event, serial {}, event {:#x}, window {:#x},\n    place {}",
+            sequence,
+            event,
+            window,
+            name_or_number(&["PlaceOnTop", "PlaceOnBottom"], place)
+        ),
+        Event::CirculateRequest {
+            sequence,
+            parent,
+            window,
+            place,
+        } => format!(
+            "CirculateRequest event, serial {}, parent {:#x}, window {:#x},\n    place {}",
+            sequence,
+            parent,
+            window,
+            name_or_number(&["PlaceOnTop", "PlaceOnBottom"], place)
+        ),
+        Event::PropertyNotify {
+            sequence,
+            window,
+            atom,
+            time,
+            state,
+        } => format!(
+            "PropertyNotify event, serial {}, window {:#x},\n    atom {:#x} ({}), time {}, \
+             state {}",
+            sequence,
+            window,
+            atom,
+            names.get(conn, atom)?,
+            time,
+            name_or_number(&["PropertyNewValue", "PropertyDelete"], state)
+        ),
+        Event::SelectionClear {
+            sequence,
+            time,
+            owner,
+            selection,
+        } => format!(
+            "SelectionClear event, serial {}, window {:#x},\n    selection {:#x} ({}), time {}",
+            sequence,
+            owner,
+            selection,
+            names.get(conn, selection)?,
+            time
+        ),
+        Event::SelectionRequest {
+            sequence,
+            time,
+            owner,
+            requestor,
+            selection,
+            target,
+            property,
+        } => format!(
+            "SelectionRequest event, serial {}, owner {:#x}, requestor {:#x},\n    selection \
+             {:#x} ({}), target {:#x} ({}),\n    property {:#x} ({}), time {}",
+            sequence,
+            owner,
+            requestor,
+            selection,
+            names.get(conn, selection)?,
+            target,
+            names.get(conn, target)?,
+            property,
+            names.get(conn, property)?,
+            time
+        ),
+        Event::SelectionNotify {
+            sequence,
+            time,
+            requestor,
+            selection,
+            target,
+            property,
+        } => format!(
+            "SelectionNotify event, serial {}, requestor {:#x},\n    selection {:#x} ({}), \
+             target {:#x} ({}),\n    property {:#x} ({}), time {}",
+            sequence,
+            requestor,
+            selection,
+            names.get(conn, selection)?,
+            target,
+            names.get(conn, target)?,
+            property,
+            names.get(conn, property)?,
+            time
+        ),
+        Event::ColormapNotify {
+            sequence,
+            window,
+            colormap,
+            new,
+            state,
+        } => format!(
+            "ColormapNotify event, serial {}, window {:#x},\n    colormap {:#x}, new {}, state {}",
+            sequence,
+            window,
+            colormap,
+            yes_no(new),
+            name_or_number(&["ColormapUninstalled", "ColormapInstalled"], state)
+        ),
+        Event::ClientMessage {
+            format,
+            sequence,
+            window,
+            message_type,
+            ref data,
+        } => format!(
+            "ClientMessage event, serial {}, window {:#x},\n    message_type {:#x} ({}), \
+             format {},\n    data {}",
+            sequence,
+            window,
+            message_type,
+            names.get(conn, message_type)?,
+            format,
+            describe_client_message(format, data)
+        ),
+        Event::MappingNotify {
+            sequence,
+            request,
+            first_keycode,
+            count,
+        } => {
+            let names = ["MappingModifier", "MappingKeyboard", "MappingPointer"];
+            format!(
+                "MappingNotify event, serial {},\n    request {}, first_keycode {}, count {}",
+                sequence,
+                name_or_number(&names, request),
+                first_keycode,
+                count
+            )
+        }
+        Event::Error(ref error) => format!("Error: {}", error),
+        Event::Unknown(t, _) => format!("Unknown event type {}", t),
+    })
+}
+
+fn main() -> Result<()> {
+    let options = parse_args();
+    let (mut conn, screen_number) = Connection::open(options.display.as_deref())?;
+    let screen = conn.server_init().roots.get(screen_number).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no screen {}", screen_number),
+        )
+    })?;
+    let (root, depth, visual) = (screen.root, screen.root_depth, screen.root_visual);
+    let (white, black) = (screen.white_pixel, screen.black_pixel);
+
+    let protocols = intern_atoms(&mut conn, &["WM_PROTOCOLS", "WM_DELETE_WINDOW"])?;
+    let (wm_protocols, wm_delete_window) = (protocols[0], protocols[1]);
+
+    let window = match options.target {
+        None => {
+            let window = conn.generate_id()?;
+            let create_window = CreateWindow::new(
+                depth, window, root, 10, 10, 400, 400, 4, 1, // InputOutput
+                visual,
+            )
+            .with_attributes(WindowAttributes {
+                background_pixel: Some(white),
+                border_pixel: Some(black),
+                event_mask: Some(event_mask::ALL_EVENTS),
+                ..Default::default()
+            });
+            conn.send(&create_window.as_bytes())?;
+            conn.send(&ChangeWmName::new(window, "Event Tester".into()).as_bytes())?;
+            // Ask the window manager to send WM_DELETE_WINDOW rather than kill the connection
+            // when the window is closed.
+            let delete_window = wm_delete_window.to_be_bytes();
+            let change_protocols = ChangeProperty::new(
+                PropMode::Replace,
+                window,
+                wm_protocols,
+                atom::ATOM,
+                32,
+                &delete_window,
+            );
+            conn.send(&change_protocols.as_bytes())?;
+            conn.send(&MapWindow::new(window).as_bytes())?;
+            window
+        }
+        // Button presses are left to the client that already selects them.
+        Some(ref target) => {
+            let window = target.window(&mut conn, screen_number)?;
+            let attributes = WindowAttributes {
+                event_mask: Some(event_mask::ALL_EVENTS & !event_mask::BUTTON_PRESS),
+                ..Default::default()
+            };
+            conn.send(&ChangeWindowAttributes::new(window, attributes).as_bytes())?;
+            window
+        }
+    };
+
+    let mut keymap = Keymap::load(&mut conn)?;
+    let mut names = AtomNames::new();
+    let mut out = io::stdout().lock();
+    loop {
+        let event = conn.next_event()?;
+        keymap.update(&mut conn, &event)?;
+        writeln!(
+            out,
+            "{}\n",
+            describe(&mut conn, &keymap, &mut names, &event)?
+        )?;
+        out.flush()?;
+
+        if let Event::ClientMessage {
+            window: w,
+            message_type,
+            format: 32,
+            data,
+            ..
+        } = event
+        {
+            let protocol = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+            if w == window && message_type == wm_protocols && protocol == wm_delete_window {
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,334 @@
+use x11_client::*;
+
+use std::io::prelude::*;
+use std::io::{self, Result};
+use std::os::unix::net::UnixStream;
+
+#[path = "common/args.rs"]
+mod args;
+#[path = "common/names.rs"]
+mod names;
+
+use args::{parse_id, Target};
+use names::AtomNames;
+
+const USAGE: &str =
+    "usage: prop [-display DISPLAY] [-root | -id WINDOW | -name NAME] [-spy] [PROPERTY...]
+
+Lists the properties of a window (the root window by default), or just those named, decoding
+the common types. With -spy, goes on to print each property as it changes.";
+
+struct Options {
+    display: Option<String>,
+    target: Target,
+    spy: bool,
+    properties: Vec<String>,
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        display: None,
+        target: Target::Root,
+        spy: false,
+        properties: Vec::new(),
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "-display" => options.display = Some(value()),
+            "-root" => options.target = Target::Root,
+            "-id" => options.target = Target::Id(parse_id(&value()).unwrap_or_else(|| usage())),
+            "-name" => options.target = Target::Name(value()),
+            "-spy" => options.spy = true,
+            "-h" | "-help" | "--help" => usage(),
+            _ if !arg.starts_with('-') => options.properties.push(arg),
+            _ => usage(),
+        }
+    }
+
+    options
+}
+
+/// Quotes a string the way C string literals are written.
+fn quote(s: &str) -> String {
+    let mut ret = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\t' => ret.push_str("\\t"),
+            c if c.is_control() => ret.push_str(&format!("\\{:03o}", c as u32)),
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+/// The null-separated strings of a text property, ignoring one trailing null.
+fn strings(value: &[u8], utf8: bool) -> Vec<String> {
+    let value = value.strip_suffix(&[0]).unwrap_or(value);
+    value
+        .split(|&byte| byte == 0)
+        .map(|string| {
+            if utf8 {
+                String::from_utf8_lossy(string).into_owned()
+            } else {
+                // STRING is Latin-1.
+                string.iter().map(|&byte| byte as char).collect()
+            }
+        })
+        .collect()
+}
+
+/// The items of a property as integers, sign-extended if `signed`.
+fn items(property: &GetPropertyReply, signed: bool) -> Vec<i64> {
+    let size = property.format as usize / 8;
+    property
+        .value
+        .chunks_exact(size)
+        .map(|item| {
+            let value = item.iter().fold(0, |value, &byte| value << 8 | byte as u64);
+            let shift = 64 - 8 * size;
+            if signed {
+                (value << shift) as i64 >> shift
+            } else {
+                value as i64
+            }
+        })
+        .collect()
+}
+
+fn join<T: ToString>(items: impl IntoIterator<Item = T>) -> String {
+    items
+        .into_iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn gravity_name(gravity: u32) -> String {
+    let names = [
+        "Forget",
+        "NorthWest",
+        "North",
+        "NorthEast",
+        "West",
+        "Center",
+        "East",
+        "SouthWest",
+        "South",
+        "SouthEast",
+        "Static",
+    ];
+    match names.get(gravity as usize) {
+        Some(name) => name.to_string(),
+        None => gravity.to_string(),
+    }
+}
+
+fn format_wm_hints(hints: &WmHints) -> String {
+    let mut lines = Vec::new();
+    if let Some(input) = hints.input {
+        let input = if input { "True" } else { "False" };
+        lines.push(format!("Client accepts input or input focus: {}", input));
+    }
+    if let Some(state) = hints.initial_state {
+        let state = match state {
+            0 => "Withdrawn State".to_string(),
+            1 => "Normal State".to_string(),
+            3 => "Iconic State".to_string(),
+            state => format!("state {}", state),
+        };
+        lines.push(format!("Initial state is {}.", state));
+    }
+    if let Some(pixmap) = hints.icon_pixmap {
+        lines.push(format!("bitmap id # to use for icon: {:#x}", pixmap));
+    }
+    if let Some(window) = hints.icon_window {
+        lines.push(format!("window id # to use for icon: {:#x}", window));
+    }
+    if let Some((x, y)) = hints.icon_position {
+        lines.push(format!("starting position for icon: {}, {}", x, y));
+    }
+    if let Some(mask) = hints.icon_mask {
+        lines.push(format!("bitmap id # of mask for icon: {:#x}", mask));
+    }
+    if let Some(group) = hints.window_group {
+        lines.push(format!("window id # of group leader: {:#x}", group));
+    }
+    if hints.urgent {
+        lines.push("The urgency hint bit is set".to_string());
+    }
+    lines.iter().map(|line| format!("\n\t\t{}", line)).collect()
+}
+
+fn format_wm_size_hints(hints: &WmSizeHints) -> String {
+    let chooser = |user| if user { "user" } else { "program" };
+    let mut lines = Vec::new();
+    if let Some((x, y, user)) = hints.position {
+        lines.push(format!(
+            "{} specified location: {}, {}",
+            chooser(user),
+            x,
+            y
+        ));
+    }
+    if let Some((width, height, user)) = hints.size {
+        let chooser = chooser(user);
+        lines.push(format!(
+            "{} specified size: {} by {}",
+            chooser, width, height
+        ));
+    }
+    let sizes = [
+        ("minimum size", hints.min_size),
+        ("maximum size", hints.max_size),
+        ("resize increment", hints.resize_inc),
+    ];
+    for (name, size) in sizes {
+        if let Some((width, height)) = size {
+            lines.push(format!(
+                "program specified {}: {} by {}",
+                name, width, height
+            ));
+        }
+    }
+    if let Some(((min_num, min_den), (max_num, max_den))) = hints.aspect {
+        lines.push(format!(
+            "program specified minimum aspect ratio: {}/{}",
+            min_num, min_den
+        ));
+        lines.push(format!(
+            "program specified maximum aspect ratio: {}/{}",
+            max_num, max_den
+        ));
+    }
+    if let Some((width, height)) = hints.base_size {
+        lines.push(format!(
+            "program specified base size: {} by {}",
+            width, height
+        ));
+    }
+    if let Some(gravity) = hints.win_gravity {
+        lines.push(format!("window gravity: {}", gravity_name(gravity)));
+    }
+    lines.iter().map(|line| format!("\n\t\t{}", line)).collect()
+}
+
+/// Formats a property as `NAME(TYPE) = value`, or with `:` for the types that span lines.
+fn format_property(
+    conn: &mut Connection<UnixStream>,
+    names: &mut AtomNames,
+    name: &str,
+    property: &GetPropertyReply,
+) -> Result<String> {
+    if property.format == 0 {
+        return Ok(format!("{}:  not found.", name));
+    }
+    let type_name = names.get(conn, property.property_type)?;
+    let header = format!("{}({})", name, type_name);
+
+    let hints = match type_name.as_str() {
+        "WM_HINTS" => WmHints::from_property(property).map(|hints| format_wm_hints(&hints)),
+        "WM_SIZE_HINTS" => {
+            WmSizeHints::from_property(property).map(|hints| format_wm_size_hints(&hints))
+        }
+        _ => None,
+    };
+    if let Some(hints) = hints {
+        return Ok(format!("{}:{}", header, hints));
+    }
+
+    let value = match (type_name.as_str(), property.format) {
+        ("STRING", 8) | ("UTF8_STRING", 8) => {
+            let utf8 = type_name == "UTF8_STRING";
+            join(strings(&property.value, utf8).iter().map(|s| quote(s)))
+        }
+        ("ATOM", 32) => join(names.get_all(conn, &property.u32_values())?),
+        ("CARDINAL", _) => join(items(property, false)),
+        ("INTEGER", _) => join(items(property, true)),
+        ("WINDOW", 32) => {
+            let windows = property.u32_values();
+            let windows = windows.iter().map(|window| format!("{:#x}", window));
+            return Ok(format!("{}: window id # {}", header, join(windows)));
+        }
+        (_, 8) => join(items(property, false)),
+        _ => join(
+            items(property, false)
+                .into_iter()
+                .map(|item| format!("{:#x}", item)),
+        ),
+    };
+    Ok(format!("{} = {}", header, value))
+}
+
+fn print_property<W: Write>(
+    conn: &mut Connection<UnixStream>,
+    names: &mut AtomNames,
+    out: &mut W,
+    window: u32,
+    atom: u32,
+) -> Result<()> {
+    let property = read_property(conn, false, window, atom, atom::NONE)?;
+    let name = names.get(conn, atom)?;
+    writeln!(out, "{}", format_property(conn, names, &name, &property)?)
+}
+
+fn main() -> Result<()> {
+    let options = parse_args();
+    let (mut conn, screen) = Connection::open(options.display.as_deref())?;
+
+    let window = options.target.window(&mut conn, screen)?;
+
+    let mut names = AtomNames::new();
+    let atoms = if options.properties.is_empty() {
+        ListPropertiesReply::from_bytes(&conn.request(&ListProperties::new(window).as_bytes())?)?
+            .atoms
+    } else {
+        let properties = options
+            .properties
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        intern_atoms(&mut conn, &properties)?
+    };
+
+    if options.spy {
+        let attributes = WindowAttributes {
+            event_mask: Some(event_mask::PROPERTY_CHANGE),
+            ..Default::default()
+        };
+        conn.send(&ChangeWindowAttributes::new(window, attributes).as_bytes())?;
+    }
+
+    let mut out = io::stdout().lock();
+    for &atom in &atoms {
+        print_property(&mut conn, &mut names, &mut out, window, atom)?;
+    }
+    out.flush()?;
+
+    if !options.spy {
+        return Ok(());
+    }
+    loop {
+        match conn.next_event()? {
+            Event::PropertyNotify {
+                window: w, atom, ..
+            } if w == window && (options.properties.is_empty() || atoms.contains(&atom)) => {
+                print_property(&mut conn, &mut names, &mut out, window, atom)?;
+                out.flush()?;
+            }
+            Event::Error(error) => return Err(io::Error::other(error)),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use x11_client::image::{Image, ImageLayout};
+use x11_client::*;
+
+use std::io::prelude::*;
+use std::io::{self, Result};
+use std::os::unix::net::UnixStream;
+
+#[path = "common/args.rs"]
+mod args;
+
+use args::{parse_id, Target};
+
+const USAGE: &str =
+    "usage: screenshot [-display DISPLAY] [-screen N] [-root | -id WINDOW | -name NAME]
+                  [-crop WIDTHxHEIGHT[{+-}X{+-}Y]] [-format ppm|png] OUTPUT
+
+Captures a window (the root window by default) and writes it as a PPM or PNG image. The format
+defaults to the extension of OUTPUT, or PNG; an OUTPUT of - writes to standard output. As in
+other X geometries, a -X or -Y offset of the crop area is from the right or bottom edge.";
+
+#[derive(Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Ppm,
+    Png,
+}
+
+struct Options {
+    display: Option<String>,
+    screen: Option<usize>,
+    target: Target,
+    crop: Option<Geometry>,
+    format: Option<OutputFormat>,
+    output: String,
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        display: None,
+        screen: None,
+        target: Target::Root,
+        crop: None,
+        format: None,
+        output: String::new(),
+    };
+
+    let mut args = std::env::args().skip(1);
+    let mut output = None;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "-display" => options.display = Some(value()),
+            "-screen" => options.screen = Some(value().parse().unwrap_or_else(|_| usage())),
+            "-root" => options.target = Target::Root,
+            "-id" => options.target = Target::Id(parse_id(&value()).unwrap_or_else(|| usage())),
+            "-name" => options.target = Target::Name(value()),
+            "-crop" => options.crop = Some(parse_geometry(&value()).unwrap_or_else(|| usage())),
+            "-format" => {
+                options.format = Some(match value().as_str() {
+                    "ppm" => OutputFormat::Ppm,
+                    "png" => OutputFormat::Png,
+                    _ => usage(),
+                })
+            }
+            "-h" | "-help" | "--help" => usage(),
+            _ if output.is_none() && (arg == "-" || !arg.starts_with('-')) => output = Some(arg),
+            _ => usage(),
+        }
+    }
+
+    options.output = output.unwrap_or_else(|| usage());
+    options
+}
+
+/// An area given as an X geometry specification, whose offsets may be from the right or bottom
+/// edge of the window.
+#[derive(Clone, Copy)]
+struct Geometry {
+    width: u16,
+    height: u16,
+    x: i16,
+    y: i16,
+    from_right: bool,
+    from_bottom: bool,
+}
+
+/// Parses an X geometry specification of the form `WIDTHxHEIGHT`, `WIDTHxHEIGHT+X+Y` or with
+/// `-X` or `-Y` for offsets from the right or bottom edge.
+fn parse_geometry(geometry: &str) -> Option<Geometry> {
+    let (size, position) = geometry.split_at(geometry.find(['+', '-']).unwrap_or(geometry.len()));
+    let (width, height) = size.split_once('x')?;
+    let (x, y) = if position.is_empty() {
+        ("+0", "+0")
+    } else {
+        let y_start = position[1..].find(['+', '-'])? + 1;
+        position.split_at(y_start)
+    };
+    // Returns the offset and whether it is from the far edge.
+    let offset = |offset: &str| match offset.split_at(1) {
+        ("+", value) => Some((value.parse().ok()?, false)),
+        ("-", value) => Some((value.parse().ok()?, true)),
+        _ => None,
+    };
+    let (x, from_right) = offset(x)?;
+    let (y, from_bottom) = offset(y)?;
+
+    Some(Geometry {
+        width: width.parse().ok()?,
+        height: height.parse().ok()?,
+        x,
+        y,
+        from_right,
+        from_bottom,
+    })
+}
+
+/// Places `area` in a window of the given size and clips it to the window.
+fn clip(area: Geometry, width: u16, height: u16) -> Option<Rectangle> {
+    let place = |offset: i16, from_end: bool, size: u16, window_size: u16| {
+        if from_end {
+            window_size as i32 - size as i32 - offset as i32
+        } else {
+            offset as i32
+        }
+    };
+    let x = place(area.x, area.from_right, area.width, width);
+    let y = place(area.y, area.from_bottom, area.height, height);
+
+    let x1 = std::cmp::max(x, 0);
+    let y1 = std::cmp::max(y, 0);
+    let x2 = std::cmp::min(x + area.width as i32, width as i32);
+    let y2 = std::cmp::min(y + area.height as i32, height as i32);
+    if x2 <= x1 || y2 <= y1 {
+        return None;
+    }
+
+    Some(Rectangle {
+        x: x1 as i16,
+        y: y1 as i16,
+        width: (x2 - x1) as u16,
+        height: (y2 - y1) as u16,
+    })
+}
+
+/// Looks up the colors of pixels from a visual whose colors are defined by a colormap.
+fn colormap_to_rgba(
+    conn: &mut Connection<UnixStream>,
+    colormap: u32,
+    pixels: &[u32],
+) -> Result<Vec<u8>> {
+    let mut unique = pixels.to_vec();
+    unique.sort_unstable();
+    unique.dedup();
+
+    let mut colors = std::collections::HashMap::new();
+    for chunk in unique.chunks(16384) {
+        let query = QueryColors::new(colormap, chunk);
+        let reply = QueryColorsReply::from_bytes(&conn.request(&query.as_bytes())?)?;
+        colors.extend(chunk.iter().copied().zip(reply.colors));
+    }
+
+    Ok(pixels
+        .iter()
+        .flat_map(|pixel| {
+            let color = colors[pixel];
+            [
+                (color.red >> 8) as u8,
+                (color.green >> 8) as u8,
+                (color.blue >> 8) as u8,
+                255,
+            ]
+        })
+        .collect())
+}
+
+fn write_ppm<W: Write>(out: &mut W, rgba: &[u8], width: u16, height: u16) -> Result<()> {
+    write!(out, "P6\n{} {}\n255\n", width, height)?;
+    let rgb = rgba
+        .chunks_exact(4)
+        .flat_map(|pixel| pixel[..3].iter().copied())
+        .collect::<Vec<_>>();
+    out.write_all(&rgb)
+}
+
+fn crc32(data: &[&[u8]]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data.iter().flat_map(|d| d.iter()) {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+fn write_png_chunk<W: Write>(out: &mut W, kind: &[u8], data: &[u8]) -> Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    out.write_all(&crc32(&[kind, data]).to_be_bytes())
+}
+
+/// Writes an 8-bit RGB PNG, using uncompressed deflate blocks so we need no compressor.
+fn write_png<W: Write>(out: &mut W, rgba: &[u8], width: u16, height: u16) -> Result<()> {
+    let mut raw = Vec::with_capacity((width as usize * 3 + 1) * height as usize);
+    for row in rgba.chunks_exact(width as usize * 4) {
+        raw.push(0); // filter type None
+        raw.extend(
+            row.chunks_exact(4)
+                .flat_map(|pixel| pixel[..3].iter().copied()),
+        );
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let blocks = raw.chunks(65535).collect::<Vec<_>>();
+    for (i, block) in blocks.iter().enumerate() {
+        zlib.push((i == blocks.len() - 1) as u8);
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]); // 8-bit RGB, no interlacing
+
+    out.write_all(b"\x89PNG\r\n\x1a\n")?;
+    write_png_chunk(out, b"IHDR", &header)?;
+    write_png_chunk(out, b"IDAT", &zlib)?;
+    write_png_chunk(out, b"IEND", &[])
+}
+
+fn main() -> Result<()> {
+    let options = parse_args();
+    let (mut conn, default_screen) = Connection::open(options.display.as_deref())?;
+
+    let window = options
+        .target
+        .window(&mut conn, options.screen.unwrap_or(default_screen))?;
+
+    let geometry =
+        GetGeometryReply::from_bytes(&conn.request(&GetGeometry::new(window).as_bytes())?)?;
+    let screen = conn
+        .server_init()
+        .roots
+        .iter()
+        .position(|screen| screen.root == geometry.root)
+        .unwrap();
+    let attributes = GetWindowAttributesReply::from_bytes(
+        &conn.request(&GetWindowAttributes::new(window).as_bytes())?,
+    )?;
+
+    let area = options.crop.unwrap_or(Geometry {
+        width: geometry.width,
+        height: geometry.height,
+        x: 0,
+        y: 0,
+        from_right: false,
+        from_bottom: false,
+    });
+    let area = clip(area, geometry.width, geometry.height).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "crop area lies outside the window",
+        )
+    })?;
+
+    let server_init = conn.server_init();
+    let (depth, visual) = server_init.roots[screen]
+        .find_visual(attributes.visual)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "window visual not found"))?;
+    let true_color = visual.class == VisualClass::TrueColor;
+    let layout = ImageLayout::new(server_init, depth, Some(visual))?;
+
+    let image = Image::get(&mut conn, &layout, ImageFormat::ZPixmap, window, area)?;
+    let rgba = if true_color {
+        image.to_rgba(&layout)?
+    } else {
+        colormap_to_rgba(&mut conn, attributes.colormap, &image.to_pixels(&layout)?)?
+    };
+
+    let format = options
+        .format
+        .unwrap_or(if options.output.ends_with(".ppm") {
+            OutputFormat::Ppm
+        } else {
+            OutputFormat::Png
+        });
+    let mut out: Box<dyn Write> = if options.output == "-" {
+        Box::new(io::stdout())
+    } else {
+        Box::new(std::fs::File::create(&options.output)?)
+    };
+    let mut out = io::BufWriter::new(&mut out);
+    match format {
+        OutputFormat::Ppm => write_ppm(&mut out, &rgba, area.width, area.height)?,
+        OutputFormat::Png => write_png(&mut out, &rgba, area.width, area.height)?,
+    }
+    out.flush()
+}
@@ -0,0 +1,430 @@
+use x11_client::*;
+
+use std::io::prelude::*;
+use std::io::{self, Result};
+use std::os::unix::net::UnixStream;
+
+#[path = "common/args.rs"]
+mod args;
+#[path = "common/json.rs"]
+mod json;
+#[path = "common/names.rs"]
+mod names;
+
+use args::{parse_id, Target};
+use json::Json;
+use names::name_or_number;
+
+const USAGE: &str =
+    "usage: winfo [-display DISPLAY] [-root | -id WINDOW | -name NAME] [-tree] [--json]
+
+Describes a window (the root window by default): its geometry and absolute position, its
+attributes, and its place in the window tree with a line for each child. With -tree, lists
+all descendants rather than just the children. --json writes the same as a JSON object.";
+
+struct Options {
+    display: Option<String>,
+    target: Target,
+    tree: bool,
+    json: bool,
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        display: None,
+        target: Target::Root,
+        tree: false,
+        json: false,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "-display" => options.display = Some(value()),
+            "-root" => options.target = Target::Root,
+            "-id" => options.target = Target::Id(parse_id(&value()).unwrap_or_else(|| usage())),
+            "-name" => options.target = Target::Name(value()),
+            "-tree" => options.tree = true,
+            "-json" | "--json" => options.json = true,
+            "-h" | "-help" | "--help" => usage(),
+            _ => usage(),
+        }
+    }
+
+    options
+}
+
+fn bit_gravity_name(gravity: u8) -> String {
+    let names = [
+        "ForgetGravity",
+        "NorthWestGravity",
+        "NorthGravity",
+        "NorthEastGravity",
+        "WestGravity",
+        "CenterGravity",
+        "EastGravity",
+        "SouthWestGravity",
+        "SouthGravity",
+        "SouthEastGravity",
+        "StaticGravity",
+    ];
+    name_or_number(&names, gravity)
+}
+
+fn win_gravity_name(gravity: u8) -> String {
+    // Window gravity has UnmapGravity where bit gravity has ForgetGravity.
+    match gravity {
+        0 => "UnmapGravity".to_string(),
+        gravity => bit_gravity_name(gravity),
+    }
+}
+
+fn class_name(class: u16) -> &'static str {
+    match class {
+        1 => "InputOutput",
+        2 => "InputOnly",
+        _ => "CopyFromParent",
+    }
+}
+
+fn backing_store_name(backing_store: u8) -> String {
+    name_or_number(&["NotUseful", "WhenMapped", "Always"], backing_store)
+}
+
+fn map_state_name(map_state: u8) -> String {
+    name_or_number(&["IsUnmapped", "IsUnviewable", "IsViewable"], map_state)
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+fn visual_class(init: &ServerInit, id: u32) -> Option<VisualClass> {
+    init.roots
+        .iter()
+        .flat_map(|screen| &screen.allowed_depths)
+        .flat_map(|depth| &depth.visuals)
+        .find(|visual| visual.id == id)
+        .map(|visual| visual.class)
+}
+
+/// The window's title: _NET_WM_NAME if it has one, or else WM_NAME.
+fn window_name(
+    conn: &mut Connection<UnixStream>,
+    net_wm_name: u32,
+    window: u32,
+) -> Result<Option<String>> {
+    for property in [net_wm_name, atom::WM_NAME] {
+        if property == atom::NONE {
+            continue;
+        }
+        let reply = read_property(conn, false, window, property, atom::NONE)?;
+        if reply.format != 8 {
+            continue;
+        }
+        return Ok(Some(if reply.property_type == atom::STRING {
+            // STRING is Latin-1.
+            reply.value.iter().map(|&byte| byte as char).collect()
+        } else {
+            String::from_utf8_lossy(&reply.value).into_owned()
+        }));
+    }
+    Ok(None)
+}
+
+/// A window's name, geometry and position on the screen, as listed for it and its children.
+struct Summary {
+    id: u32,
+    name: Option<String>,
+    geometry: GetGeometryReply,
+    absolute: (i16, i16),
+    /// The window's parent and children, unless the depth ran out before it.
+    tree: Option<QueryTreeReply>,
+    children: Vec<Summary>,
+}
+
+fn summarize(
+    conn: &mut Connection<UnixStream>,
+    net_wm_name: u32,
+    window: u32,
+    depth: Option<usize>,
+) -> Result<Summary> {
+    let name = window_name(conn, net_wm_name, window)?;
+    let geometry =
+        GetGeometryReply::from_bytes(&conn.request(&GetGeometry::new(window).as_bytes())?)?;
+    // The origin is inside the border, but the position shown is that of the border's corner.
+    let border = -(geometry.border_width as i16);
+    let translate = TranslateCoordinates::new(window, geometry.root, border, border);
+    let translated = TranslateCoordinatesReply::from_bytes(&conn.request(&translate.as_bytes())?)?;
+
+    let mut tree = None;
+    let mut children = Vec::new();
+    if depth != Some(0) {
+        let reply = QueryTreeReply::from_bytes(&conn.request(&QueryTree::new(window).as_bytes())?)?;
+        for &child in &reply.children {
+            // Children can be destroyed while the tree is being walked.
+            let child = summarize(conn, net_wm_name, child, depth.map(|depth| depth - 1));
+            children.extend(unless_destroyed(child)?);
+        }
+        tree = Some(reply);
+    }
+
+    Ok(Summary {
+        id: window,
+        name,
+        geometry,
+        absolute: (translated.dst_x, translated.dst_y),
+        tree,
+        children,
+    })
+}
+
+fn quoted_name(name: &Option<String>) -> String {
+    match name {
+        Some(name) => format!("{:?}", name),
+        None => "(has no name)".to_string(),
+    }
+}
+
+fn write_children<W: Write>(out: &mut W, summary: &Summary, indent: usize) -> Result<()> {
+    let children = &summary.children;
+    writeln!(
+        out,
+        "{:indent$}{} child{}{}",
+        "",
+        children.len(),
+        if children.len() == 1 { "" } else { "ren" },
+        if children.is_empty() { "." } else { ":" },
+        indent = indent
+    )?;
+    for child in children {
+        let geometry = &child.geometry;
+        writeln!(
+            out,
+            "{:indent$}{:#x} {}: {}x{}{:+}{:+}  {:+}{:+}",
+            "",
+            child.id,
+            quoted_name(&child.name),
+            geometry.width,
+            geometry.height,
+            geometry.x,
+            geometry.y,
+            child.absolute.0,
+            child.absolute.1,
+            indent = indent + 3
+        )?;
+        if !child.children.is_empty() {
+            write_children(out, child, indent + 5)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_text<W: Write>(
+    out: &mut W,
+    init: &ServerInit,
+    summary: &Summary,
+    attributes: &GetWindowAttributesReply,
+    tree: &QueryTreeReply,
+) -> Result<()> {
+    let geometry = &summary.geometry;
+    let (x, y) = summary.absolute;
+    let outer_width = geometry.width as i32 + 2 * geometry.border_width as i32;
+    let outer_height = geometry.height as i32 + 2 * geometry.border_width as i32;
+    let screen = init.roots.iter().find(|screen| screen.root == tree.root);
+    let (right, bottom) = match screen {
+        Some(screen) => (
+            screen.width_pixels as i32 - x as i32 - outer_width,
+            screen.height_pixels as i32 - y as i32 - outer_height,
+        ),
+        None => (0, 0),
+    };
+    let visual_class = match visual_class(init, attributes.visual) {
+        Some(class) => format!("{:?}", class),
+        None => "(none)".to_string(),
+    };
+    let installed = if attributes.map_is_installed {
+        "installed"
+    } else {
+        "not installed"
+    };
+
+    writeln!(
+        out,
+        "Window id: {:#x} {}",
+        summary.id,
+        quoted_name(&summary.name)
+    )?;
+    writeln!(out)?;
+    writeln!(out, "  Absolute upper-left X:  {}", x)?;
+    writeln!(out, "  Absolute upper-left Y:  {}", y)?;
+    writeln!(out, "  Relative upper-left X:  {}", geometry.x)?;
+    writeln!(out, "  Relative upper-left Y:  {}", geometry.y)?;
+    writeln!(out, "  Width: {}", geometry.width)?;
+    writeln!(out, "  Height: {}", geometry.height)?;
+    writeln!(out, "  Depth: {}", geometry.depth)?;
+    writeln!(out, "  Visual: {:#x}", attributes.visual)?;
+    writeln!(out, "  Visual Class: {}", visual_class)?;
+    writeln!(out, "  Border width: {}", geometry.border_width)?;
+    writeln!(out, "  Class: {}", class_name(attributes.class))?;
+    writeln!(
+        out,
+        "  Colormap: {:#x} ({})",
+        attributes.colormap, installed
+    )?;
+    writeln!(
+        out,
+        "  Bit Gravity State: {}",
+        bit_gravity_name(attributes.bit_gravity)
+    )?;
+    writeln!(
+        out,
+        "  Window Gravity State: {}",
+        win_gravity_name(attributes.win_gravity)
+    )?;
+    writeln!(
+        out,
+        "  Backing Store State: {}",
+        backing_store_name(attributes.backing_store)
+    )?;
+    writeln!(out, "  Save Under State: {}", yes_no(attributes.save_under))?;
+    writeln!(out, "  Map State: {}", map_state_name(attributes.map_state))?;
+    writeln!(
+        out,
+        "  Override Redirect State: {}",
+        yes_no(attributes.override_redirect)
+    )?;
+    writeln!(
+        out,
+        "  Corners:  {:+}{:+}  -{}{:+}  -{}-{}  {:+}-{}",
+        x, y, right, y, right, bottom, x, bottom
+    )?;
+    writeln!(
+        out,
+        "  -geometry {}x{}{:+}{:+}",
+        geometry.width, geometry.height, x, y
+    )?;
+    writeln!(out)?;
+    writeln!(out, "  Root window id: {:#x}", tree.root)?;
+    if tree.parent == 0 {
+        writeln!(out, "  Parent window id: None (this is the root window)")?;
+    } else {
+        writeln!(out, "  Parent window id: {:#x}", tree.parent)?;
+    }
+    write_children(out, summary, 5)
+}
+
+fn summary_json(summary: &Summary) -> Vec<(&'static str, Json)> {
+    let geometry = &summary.geometry;
+    vec![
+        ("id", summary.id.into()),
+        ("name", summary.name.clone().into()),
+        ("x", geometry.x.into()),
+        ("y", geometry.y.into()),
+        ("absolute_x", summary.absolute.0.into()),
+        ("absolute_y", summary.absolute.1.into()),
+        ("width", geometry.width.into()),
+        ("height", geometry.height.into()),
+        ("border_width", geometry.border_width.into()),
+        ("depth", geometry.depth.into()),
+    ]
+}
+
+fn children_json(summary: &Summary) -> Json {
+    Json::Array(
+        summary
+            .children
+            .iter()
+            .map(|child| {
+                let mut fields = summary_json(child);
+                fields.push(("children", children_json(child)));
+                Json::Object(fields)
+            })
+            .collect(),
+    )
+}
+
+fn to_json(
+    init: &ServerInit,
+    summary: &Summary,
+    attributes: &GetWindowAttributesReply,
+    tree: &QueryTreeReply,
+) -> Json {
+    let visual_class = visual_class(init, attributes.visual).map(|class| format!("{:?}", class));
+    let mut fields = summary_json(summary);
+    fields.extend(vec![
+        ("visual", attributes.visual.into()),
+        ("visual_class", visual_class.into()),
+        ("class", class_name(attributes.class).into()),
+        ("colormap", attributes.colormap.into()),
+        ("colormap_installed", attributes.map_is_installed.into()),
+        (
+            "bit_gravity",
+            bit_gravity_name(attributes.bit_gravity).into(),
+        ),
+        (
+            "win_gravity",
+            win_gravity_name(attributes.win_gravity).into(),
+        ),
+        (
+            "backing_store",
+            backing_store_name(attributes.backing_store).into(),
+        ),
+        ("save_under", attributes.save_under.into()),
+        ("map_state", map_state_name(attributes.map_state).into()),
+        ("override_redirect", attributes.override_redirect.into()),
+        ("all_event_masks", attributes.all_event_masks.into()),
+        ("your_event_mask", attributes.your_event_mask.into()),
+        (
+            "do_not_propagate_mask",
+            attributes.do_not_propagate_mask.into(),
+        ),
+        ("root", tree.root.into()),
+        (
+            "parent",
+            Some(tree.parent).filter(|&parent| parent != 0).into(),
+        ),
+        ("children", children_json(summary)),
+    ]);
+    Json::Object(fields)
+}
+
+fn main() -> Result<()> {
+    let options = parse_args();
+    let (mut conn, screen) = Connection::open(options.display.as_deref())?;
+
+    let window = options.target.window(&mut conn, screen)?;
+
+    // Only looked up, so that inspecting windows does not create atoms on the server.
+    let intern = InternAtom::new(true, "_NET_WM_NAME".into());
+    let net_wm_name = InternAtomReply::from_bytes(&conn.request(&intern.as_bytes())?)?.atom;
+    let attributes = GetWindowAttributesReply::from_bytes(
+        &conn.request(&GetWindowAttributes::new(window).as_bytes())?,
+    )?;
+    let depth = if options.tree { None } else { Some(1) };
+    let summary = summarize(&mut conn, net_wm_name, window, depth)?;
+    // The depth is never 0 here, so the tree has been queried.
+    let tree = summary.tree.as_ref().unwrap();
+
+    let mut out = io::stdout().lock();
+    if options.json {
+        writeln!(
+            out,
+            "{}",
+            to_json(conn.server_init(), &summary, &attributes, tree)
+        )?;
+    } else {
+        write_text(&mut out, conn.server_init(), &summary, &attributes, tree)?;
+    }
+    out.flush()
+}
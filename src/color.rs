@@ -0,0 +1,100 @@
+use crate::color_table::COLORS;
+use crate::Rgb;
+
+impl Rgb {
+    /// Resolves a color name or numeric specification locally, without asking the server.
+    ///
+    /// This accepts the names from the X11 rgb.txt database (ignoring case and spaces, so
+    /// "slate gray" and "SlateGray" are the same), `#rgb`, `#rrggbb`, `#rrrgggbbb` and
+    /// `#rrrrggggbbbb`, `rgb:r/g/b` with one to four hex digits per channel, and `rgbi:r/g/b`
+    /// with channels between 0.0 and 1.0. The result can be turned into a pixel with
+    /// `Visual::rgb_pixel`.
+    pub fn parse(spec: &str) -> Option<Rgb> {
+        let spec = spec.trim();
+        if let Some(hex) = spec.strip_prefix('#') {
+            parse_sharp(hex)
+        } else if let Some(channels) = strip_prefix_ignore_case(spec, "rgb:") {
+            parse_channels(channels, |channel| {
+                if channel.is_empty() || channel.len() > 4 {
+                    return None;
+                }
+                let value = u32::from_str_radix(channel, 16).ok()?;
+                let max = (1 << (4 * channel.len())) - 1;
+                Some((value * 0xffff / max) as u16)
+            })
+        } else if let Some(channels) = strip_prefix_ignore_case(spec, "rgbi:") {
+            parse_channels(channels, |channel| {
+                let value: f64 = channel.parse().ok()?;
+                if !(0.0..=1.0).contains(&value) {
+                    return None;
+                }
+                Some((value * 65535.0).round() as u16)
+            })
+        } else {
+            lookup_name(spec)
+        }
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(spec: &'a str, prefix: &str) -> Option<&'a str> {
+    if spec.len() >= prefix.len()
+        && spec.is_char_boundary(prefix.len())
+        && spec[..prefix.len()].eq_ignore_ascii_case(prefix)
+    {
+        Some(&spec[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+/// Parses `#` specifications, where each channel's digits are its most significant bits.
+fn parse_sharp(hex: &str) -> Option<Rgb> {
+    if hex.is_empty() || hex.len() % 3 != 0 || hex.len() > 12 || !hex.is_ascii() {
+        return None;
+    }
+    let digits = hex.len() / 3;
+    let channel = |i: usize| {
+        let value = u16::from_str_radix(&hex[i * digits..][..digits], 16).ok()?;
+        Some(value << (16 - 4 * digits))
+    };
+
+    Some(Rgb {
+        red: channel(0)?,
+        green: channel(1)?,
+        blue: channel(2)?,
+    })
+}
+
+fn parse_channels<F>(channels: &str, parse: F) -> Option<Rgb>
+where
+    F: Fn(&str) -> Option<u16>,
+{
+    let mut channels = channels.split('/');
+    let red = parse(channels.next()?)?;
+    let green = parse(channels.next()?)?;
+    let blue = parse(channels.next()?)?;
+    if channels.next().is_some() {
+        return None;
+    }
+
+    Some(Rgb { red, green, blue })
+}
+
+fn lookup_name(name: &str) -> Option<Rgb> {
+    let name = name
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    let i = COLORS
+        .binary_search_by(|&(entry, _, _, _)| entry.cmp(name.as_str()))
+        .ok()?;
+    let (_, red, green, blue) = COLORS[i];
+
+    // Scale 8-bit channels to 16 bits so that 0xff becomes 0xffff.
+    Some(Rgb {
+        red: red as u16 * 257,
+        green: green as u16 * 257,
+        blue: blue as u16 * 257,
+    })
+}
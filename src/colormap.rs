@@ -0,0 +1,633 @@
+use std::io::{self, Read, Result, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{request_length, Connection};
+
+/// A color with 16-bit channels, as used throughout the colormap requests.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rgb {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+}
+
+impl Rgb {
+    fn read_from<T: Read>(buf: &mut T) -> Result<Self> {
+        let red = buf.read_u16::<BigEndian>()?;
+        let green = buf.read_u16::<BigEndian>()?;
+        let blue = buf.read_u16::<BigEndian>()?;
+        Ok(Rgb { red, green, blue })
+    }
+
+    pub(crate) fn write_to(&self, buf: &mut Vec<u8>) {
+        buf.write_u16::<BigEndian>(self.red).unwrap();
+        buf.write_u16::<BigEndian>(self.green).unwrap();
+        buf.write_u16::<BigEndian>(self.blue).unwrap();
+    }
+}
+
+/// Writes a string preceded by its 16-bit length and two unused bytes, and padded.
+fn write_name(ret: &mut Vec<u8>, name: &str) {
+    let name = name.as_bytes();
+    let padding = (4 - (name.len() % 4)) % 4;
+
+    ret.write_u16::<BigEndian>(name.len() as u16).unwrap();
+    ret.write_u16::<BigEndian>(0).unwrap();
+    ret.write_all(name).unwrap();
+    for _ in 0..padding {
+        ret.write_u8(0).unwrap();
+    }
+}
+
+fn set_length(ret: &mut [u8]) {
+    let length = request_length(ret.len());
+    ret[2..4].copy_from_slice(&length.to_be_bytes());
+}
+
+/// Returns a pixel for `color` on the visual with ID `visual`: computed from the masks of a
+/// TrueColor visual, or allocated as a read-only cell in `colormap` (with the closest color the
+/// hardware supports) for any other class.
+pub fn alloc_pixel<S: Read + Write>(
+    conn: &mut Connection<S>,
+    colormap: u32,
+    visual: u32,
+    color: Rgb,
+) -> Result<u32> {
+    let visual = conn
+        .server_init()
+        .roots
+        .iter()
+        .find_map(|screen| screen.find_visual(visual))
+        .map(|(_, visual)| visual)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown visual {:#x}", visual),
+            )
+        })?;
+    if let Some(pixel) = visual.rgb_pixel(color.red, color.green, color.blue) {
+        return Ok(pixel);
+    }
+
+    let request = AllocColor::new(colormap, color);
+    Ok(AllocColorReply::from_bytes(&conn.request(&request.as_bytes())?)?.pixel)
+}
+
+pub struct CreateColormap {
+    alloc_all: bool,
+    mid: u32,
+    window: u32,
+    visual: u32,
+}
+
+impl CreateColormap {
+    /// With `alloc_all`, every entry is allocated writable; this is only allowed for
+    /// GrayScale, PseudoColor and DirectColor visuals.
+    pub fn new(alloc_all: bool, mid: u32, window: u32, visual: u32) -> Self {
+        CreateColormap {
+            alloc_all,
+            mid,
+            window,
+            visual,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(78).unwrap();
+        ret.write_u8(self.alloc_all as u8).unwrap();
+        ret.write_u16::<BigEndian>(4).unwrap();
+        ret.write_u32::<BigEndian>(self.mid).unwrap();
+        ret.write_u32::<BigEndian>(self.window).unwrap();
+        ret.write_u32::<BigEndian>(self.visual).unwrap();
+
+        ret
+    }
+}
+
+/// Requests whose only argument is a colormap.
+fn colormap_request(opcode: u8, cmap: u32) -> Vec<u8> {
+    let mut ret = Vec::new();
+
+    ret.write_u8(opcode).unwrap();
+    ret.write_u8(0).unwrap();
+    ret.write_u16::<BigEndian>(2).unwrap();
+    ret.write_u32::<BigEndian>(cmap).unwrap();
+
+    ret
+}
+
+pub struct FreeColormap {
+    cmap: u32,
+}
+
+impl FreeColormap {
+    pub fn new(cmap: u32) -> Self {
+        FreeColormap { cmap }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        colormap_request(79, self.cmap)
+    }
+}
+
+/// Moves the client's allocations out of `src_cmap` into a new colormap `mid`.
+pub struct CopyColormapAndFree {
+    mid: u32,
+    src_cmap: u32,
+}
+
+impl CopyColormapAndFree {
+    pub fn new(mid: u32, src_cmap: u32) -> Self {
+        CopyColormapAndFree { mid, src_cmap }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(80).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(3).unwrap();
+        ret.write_u32::<BigEndian>(self.mid).unwrap();
+        ret.write_u32::<BigEndian>(self.src_cmap).unwrap();
+
+        ret
+    }
+}
+
+pub struct InstallColormap {
+    cmap: u32,
+}
+
+impl InstallColormap {
+    pub fn new(cmap: u32) -> Self {
+        InstallColormap { cmap }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        colormap_request(81, self.cmap)
+    }
+}
+
+pub struct UninstallColormap {
+    cmap: u32,
+}
+
+impl UninstallColormap {
+    pub fn new(cmap: u32) -> Self {
+        UninstallColormap { cmap }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        colormap_request(82, self.cmap)
+    }
+}
+
+pub struct ListInstalledColormaps {
+    window: u32,
+}
+
+impl ListInstalledColormaps {
+    pub fn new(window: u32) -> Self {
+        ListInstalledColormaps { window }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        colormap_request(83, self.window)
+    }
+}
+
+pub struct ListInstalledColormapsReply {
+    pub cmaps: Vec<u32>,
+}
+
+impl ListInstalledColormapsReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let cmaps_count = buf.read_u16::<BigEndian>()?;
+        buf.set_position(24);
+        let mut cmaps = Vec::new();
+        for _ in 0..cmaps_count {
+            cmaps.push(buf.read_u32::<BigEndian>()?);
+        }
+
+        Ok(ListInstalledColormapsReply { cmaps })
+    }
+}
+
+pub struct AllocColor {
+    cmap: u32,
+    color: Rgb,
+}
+
+impl AllocColor {
+    pub fn new(cmap: u32, color: Rgb) -> Self {
+        AllocColor { cmap, color }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(84).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(4).unwrap();
+        ret.write_u32::<BigEndian>(self.cmap).unwrap();
+        self.color.write_to(&mut ret);
+        ret.write_u16::<BigEndian>(0).unwrap();
+
+        ret
+    }
+}
+
+pub struct AllocColorReply {
+    /// The color actually stored, which is the closest the hardware supports.
+    pub color: Rgb,
+    pub pixel: u32,
+}
+
+impl AllocColorReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let color = Rgb::read_from(&mut buf)?;
+        buf.read_u16::<BigEndian>()?;
+        let pixel = buf.read_u32::<BigEndian>()?;
+
+        Ok(AllocColorReply { color, pixel })
+    }
+}
+
+pub struct AllocNamedColor {
+    cmap: u32,
+    name: String,
+}
+
+impl AllocNamedColor {
+    pub fn new(cmap: u32, name: String) -> Self {
+        AllocNamedColor { cmap, name }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(85).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(0).unwrap();
+        ret.write_u32::<BigEndian>(self.cmap).unwrap();
+        write_name(&mut ret, &self.name);
+        set_length(&mut ret);
+
+        ret
+    }
+}
+
+pub struct AllocNamedColorReply {
+    pub pixel: u32,
+    /// The color from the server's database.
+    pub exact: Rgb,
+    /// The color actually stored.
+    pub visual: Rgb,
+}
+
+impl AllocNamedColorReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let pixel = buf.read_u32::<BigEndian>()?;
+        let exact = Rgb::read_from(&mut buf)?;
+        let visual = Rgb::read_from(&mut buf)?;
+
+        Ok(AllocNamedColorReply {
+            pixel,
+            exact,
+            visual,
+        })
+    }
+}
+
+pub struct AllocColorCells {
+    contiguous: bool,
+    cmap: u32,
+    colors: u16,
+    planes: u16,
+}
+
+impl AllocColorCells {
+    pub fn new(contiguous: bool, cmap: u32, colors: u16, planes: u16) -> Self {
+        AllocColorCells {
+            contiguous,
+            cmap,
+            colors,
+            planes,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(86).unwrap();
+        ret.write_u8(self.contiguous as u8).unwrap();
+        ret.write_u16::<BigEndian>(3).unwrap();
+        ret.write_u32::<BigEndian>(self.cmap).unwrap();
+        ret.write_u16::<BigEndian>(self.colors).unwrap();
+        ret.write_u16::<BigEndian>(self.planes).unwrap();
+
+        ret
+    }
+}
+
+pub struct AllocColorCellsReply {
+    pub pixels: Vec<u32>,
+    pub masks: Vec<u32>,
+}
+
+impl AllocColorCellsReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let pixels_count = buf.read_u16::<BigEndian>()?;
+        let masks_count = buf.read_u16::<BigEndian>()?;
+        buf.set_position(24);
+        let mut pixels = Vec::new();
+        for _ in 0..pixels_count {
+            pixels.push(buf.read_u32::<BigEndian>()?);
+        }
+        let mut masks = Vec::new();
+        for _ in 0..masks_count {
+            masks.push(buf.read_u32::<BigEndian>()?);
+        }
+
+        Ok(AllocColorCellsReply { pixels, masks })
+    }
+}
+
+pub struct AllocColorPlanes {
+    contiguous: bool,
+    cmap: u32,
+    colors: u16,
+    reds: u16,
+    greens: u16,
+    blues: u16,
+}
+
+impl AllocColorPlanes {
+    pub fn new(
+        contiguous: bool,
+        cmap: u32,
+        colors: u16,
+        reds: u16,
+        greens: u16,
+        blues: u16,
+    ) -> Self {
+        AllocColorPlanes {
+            contiguous,
+            cmap,
+            colors,
+            reds,
+            greens,
+            blues,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(87).unwrap();
+        ret.write_u8(self.contiguous as u8).unwrap();
+        ret.write_u16::<BigEndian>(4).unwrap();
+        ret.write_u32::<BigEndian>(self.cmap).unwrap();
+        ret.write_u16::<BigEndian>(self.colors).unwrap();
+        ret.write_u16::<BigEndian>(self.reds).unwrap();
+        ret.write_u16::<BigEndian>(self.greens).unwrap();
+        ret.write_u16::<BigEndian>(self.blues).unwrap();
+
+        ret
+    }
+}
+
+pub struct AllocColorPlanesReply {
+    pub red_mask: u32,
+    pub green_mask: u32,
+    pub blue_mask: u32,
+    pub pixels: Vec<u32>,
+}
+
+impl AllocColorPlanesReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let pixels_count = buf.read_u16::<BigEndian>()?;
+        buf.read_u16::<BigEndian>()?;
+        let red_mask = buf.read_u32::<BigEndian>()?;
+        let green_mask = buf.read_u32::<BigEndian>()?;
+        let blue_mask = buf.read_u32::<BigEndian>()?;
+        buf.set_position(24);
+        let mut pixels = Vec::new();
+        for _ in 0..pixels_count {
+            pixels.push(buf.read_u32::<BigEndian>()?);
+        }
+
+        Ok(AllocColorPlanesReply {
+            red_mask,
+            green_mask,
+            blue_mask,
+            pixels,
+        })
+    }
+}
+
+pub struct FreeColors<'a> {
+    cmap: u32,
+    plane_mask: u32,
+    pixels: &'a [u32],
+}
+
+impl<'a> FreeColors<'a> {
+    pub fn new(cmap: u32, plane_mask: u32, pixels: &'a [u32]) -> Self {
+        FreeColors {
+            cmap,
+            plane_mask,
+            pixels,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(88).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(request_length(12 + self.pixels.len() * 4))
+            .unwrap();
+        ret.write_u32::<BigEndian>(self.cmap).unwrap();
+        ret.write_u32::<BigEndian>(self.plane_mask).unwrap();
+        for &pixel in self.pixels {
+            ret.write_u32::<BigEndian>(pixel).unwrap();
+        }
+
+        ret
+    }
+}
+
+/// Which channels of a colormap cell `StoreColors` and `StoreNamedColor` change.
+pub const DO_RED: u8 = 0x1;
+pub const DO_GREEN: u8 = 0x2;
+pub const DO_BLUE: u8 = 0x4;
+
+#[derive(Clone, Copy, Debug)]
+pub struct ColorItem {
+    pub pixel: u32,
+    pub color: Rgb,
+    /// A combination of `DO_RED`, `DO_GREEN` and `DO_BLUE`.
+    pub flags: u8,
+}
+
+pub struct StoreColors<'a> {
+    cmap: u32,
+    items: &'a [ColorItem],
+}
+
+impl<'a> StoreColors<'a> {
+    pub fn new(cmap: u32, items: &'a [ColorItem]) -> Self {
+        StoreColors { cmap, items }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(89).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(request_length(8 + self.items.len() * 12))
+            .unwrap();
+        ret.write_u32::<BigEndian>(self.cmap).unwrap();
+        for item in self.items {
+            ret.write_u32::<BigEndian>(item.pixel).unwrap();
+            item.color.write_to(&mut ret);
+            ret.write_u8(item.flags).unwrap();
+            ret.write_u8(0).unwrap();
+        }
+
+        ret
+    }
+}
+
+pub struct StoreNamedColor {
+    flags: u8,
+    cmap: u32,
+    pixel: u32,
+    name: String,
+}
+
+impl StoreNamedColor {
+    pub fn new(flags: u8, cmap: u32, pixel: u32, name: String) -> Self {
+        StoreNamedColor {
+            flags,
+            cmap,
+            pixel,
+            name,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(90).unwrap();
+        ret.write_u8(self.flags).unwrap();
+        ret.write_u16::<BigEndian>(0).unwrap();
+        ret.write_u32::<BigEndian>(self.cmap).unwrap();
+        ret.write_u32::<BigEndian>(self.pixel).unwrap();
+        write_name(&mut ret, &self.name);
+        set_length(&mut ret);
+
+        ret
+    }
+}
+
+pub struct QueryColors<'a> {
+    cmap: u32,
+    pixels: &'a [u32],
+}
+
+impl<'a> QueryColors<'a> {
+    pub fn new(cmap: u32, pixels: &'a [u32]) -> Self {
+        QueryColors { cmap, pixels }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(91).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(request_length(8 + self.pixels.len() * 4))
+            .unwrap();
+        ret.write_u32::<BigEndian>(self.cmap).unwrap();
+        for &pixel in self.pixels {
+            ret.write_u32::<BigEndian>(pixel).unwrap();
+        }
+
+        ret
+    }
+}
+
+pub struct QueryColorsReply {
+    /// The colors of the queried pixels, in the same order.
+    pub colors: Vec<Rgb>,
+}
+
+impl QueryColorsReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let colors_count = buf.read_u16::<BigEndian>()?;
+        buf.set_position(24);
+        let mut colors = Vec::new();
+        for _ in 0..colors_count {
+            colors.push(Rgb::read_from(&mut buf)?);
+            buf.read_u16::<BigEndian>()?;
+        }
+
+        Ok(QueryColorsReply { colors })
+    }
+}
+
+pub struct LookupColor {
+    cmap: u32,
+    name: String,
+}
+
+impl LookupColor {
+    pub fn new(cmap: u32, name: String) -> Self {
+        LookupColor { cmap, name }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(92).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(0).unwrap();
+        ret.write_u32::<BigEndian>(self.cmap).unwrap();
+        write_name(&mut ret, &self.name);
+        set_length(&mut ret);
+
+        ret
+    }
+}
+
+pub struct LookupColorReply {
+    pub exact: Rgb,
+    pub visual: Rgb,
+}
+
+impl LookupColorReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let exact = Rgb::read_from(&mut buf)?;
+        let visual = Rgb::read_from(&mut buf)?;
+
+        Ok(LookupColorReply { exact, visual })
+    }
+}
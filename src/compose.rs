@@ -0,0 +1,365 @@
+//! Compose sequences, as defined by libX11 Compose files, for dead keys and the Multi_key.
+
+use std::collections::HashMap;
+use std::io::{self, Result};
+use std::path::{Path, PathBuf};
+
+use crate::keysym;
+
+const SYSTEM_DIR: &str = "/usr/share/X11/locale";
+/// Deep enough for any sane tree of includes, and stops include loops.
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+#[derive(Default)]
+struct Node {
+    children: HashMap<u32, usize>,
+    result: Option<(String, u32)>,
+}
+
+/// A table of compose sequences, stored as a trie of keysyms.
+pub struct ComposeTable {
+    nodes: Vec<Node>,
+}
+
+/// The directory holding the locale Compose files: `XLOCALEDIR`, or the usual system one.
+fn system_dir() -> PathBuf {
+    std::env::var_os("XLOCALEDIR").map_or_else(|| PathBuf::from(SYSTEM_DIR), PathBuf::from)
+}
+
+/// The current locale for character handling, from the usual environment variables.
+fn current_locale() -> String {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|locale| !locale.is_empty())
+        .unwrap_or_else(|| "C".to_string())
+}
+
+/// Looks up `name` in a two-column file such as `compose.dir` or `locale.alias`, where the
+/// column holding the key is `key_column`.
+fn lookup_column(file: &Path, name: &str, key_column: usize) -> Option<String> {
+    let text = std::fs::read_to_string(file).ok()?;
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .find_map(|line| {
+            let columns = line.split_whitespace().collect::<Vec<_>>();
+            let key = columns.get(key_column)?.trim_end_matches(':');
+            let value = columns.get(1 - key_column)?.trim_end_matches(':');
+            (key == name).then(|| value.to_string())
+        })
+}
+
+/// Finds the Compose file for a locale such as `en_US.UTF-8` through `compose.dir`, resolving
+/// aliases such as `en_US.utf8` with `locale.alias`.
+pub fn locale_compose_file(locale: &str) -> Option<PathBuf> {
+    let dir = system_dir();
+    let compose_dir = dir.join("compose.dir");
+    let locale =
+        lookup_column(&dir.join("locale.alias"), locale, 0).unwrap_or_else(|| locale.to_string());
+
+    lookup_column(&compose_dir, &locale, 1).map(|file| dir.join(file))
+}
+
+/// Parses a double-quoted string with the escapes libX11 accepts, returning it and the rest of
+/// the line.
+fn parse_string(s: &str) -> Option<(String, &str)> {
+    let mut chars = s.strip_prefix('"')?.char_indices().peekable();
+    let mut ret = String::new();
+    let mut bytes = Vec::new();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                ret.push_str(&String::from_utf8_lossy(&bytes));
+                return Some((ret, &s[i + 2..]));
+            }
+            '\\' => {
+                let (_, escape) = chars.next()?;
+                let (radix, max_digits, first) = match escape {
+                    'x' | 'X' => (16, 2, None),
+                    '0'..='7' => (8, 3, escape.to_digit(8)),
+                    'n' => {
+                        bytes.push(b'\n');
+                        continue;
+                    }
+                    'r' => {
+                        bytes.push(b'\r');
+                        continue;
+                    }
+                    't' => {
+                        bytes.push(b'\t');
+                        continue;
+                    }
+                    _ => {
+                        bytes.extend_from_slice(escape.encode_utf8(&mut [0; 4]).as_bytes());
+                        continue;
+                    }
+                };
+                // Numeric escapes give raw bytes, which together form UTF-8.
+                let mut value = first.unwrap_or(0);
+                let mut digits = first.is_some() as usize;
+                while digits < max_digits {
+                    match chars.peek().and_then(|&(_, c)| c.to_digit(radix)) {
+                        Some(digit) => {
+                            value = value * radix + digit;
+                            digits += 1;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                bytes.push(value as u8);
+            }
+            _ => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+
+    None
+}
+
+/// One parsed line of a Compose file.
+enum Line {
+    Include(String),
+    Sequence(Vec<u32>, String, u32),
+}
+
+/// Parses a line, returning `None` for blank, comment and malformed lines, and for sequences
+/// that need modifiers, which are not supported.
+fn parse_line(line: &str) -> Option<Line> {
+    let line = line.trim_start();
+    if let Some(rest) = line.strip_prefix("include") {
+        let (path, _) = parse_string(rest.trim_start())?;
+        return Some(Line::Include(path));
+    }
+
+    let (lhs, rhs) = line.split_once(':')?;
+    let mut sequence = Vec::new();
+    for token in lhs.split_whitespace() {
+        let name = token.strip_prefix('<')?.strip_suffix('>')?;
+        sequence.push(keysym::from_name(name)?);
+    }
+    if sequence.is_empty() {
+        return None;
+    }
+
+    let rhs = rhs.trim_start();
+    let (string, rest) = match parse_string(rhs) {
+        Some((string, rest)) => (Some(string), rest.trim_start()),
+        None => (None, rhs),
+    };
+    let result_keysym = rest
+        .split_whitespace()
+        .next()
+        .filter(|word| !word.starts_with('#'))
+        .and_then(keysym::from_name)
+        .unwrap_or(0);
+    let string = match string {
+        Some(string) => string,
+        None => keysym::to_char(result_keysym)?.to_string(),
+    };
+
+    Some(Line::Sequence(sequence, string, result_keysym))
+}
+
+impl ComposeTable {
+    pub fn new() -> Self {
+        ComposeTable {
+            nodes: vec![Node::default()],
+        }
+    }
+
+    /// Loads the table libX11 would use: `XCOMPOSEFILE`, else `~/.XCompose` or
+    /// `$XDG_CONFIG_HOME/XCompose`, else the Compose file of the current locale.
+    ///
+    /// Returns an empty table if there is none.
+    pub fn load_default() -> Result<Self> {
+        let mut table = ComposeTable::new();
+
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|home| home.join(".config")));
+        let user_file = std::env::var_os("XCOMPOSEFILE")
+            .map(PathBuf::from)
+            .into_iter()
+            .chain(home.map(|home| home.join(".XCompose")))
+            .chain(config_home.map(|config| config.join("XCompose")))
+            .find(|file| file.is_file());
+
+        if let Some(file) = user_file.or_else(|| locale_compose_file(&current_locale())) {
+            table.add_file(&file)?;
+        }
+        Ok(table)
+    }
+
+    /// Adds the sequences of a Compose file, following its `include` directives.
+    pub fn add_file(&mut self, path: &Path) -> Result<()> {
+        self.add_file_at_depth(path, 0)
+    }
+
+    fn add_file_at_depth(&mut self, path: &Path, depth: usize) -> Result<()> {
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Compose includes nested too deeply at {}", path.display()),
+            ));
+        }
+        let text = std::fs::read_to_string(path)?;
+        self.add_str_at_depth(&text, depth)
+    }
+
+    /// Adds the sequences in the text of a Compose file. Later definitions replace earlier
+    /// ones, and malformed lines are skipped as libX11 does.
+    ///
+    /// In `include` paths, `%L` is the Compose file of the current locale, `%S` the system
+    /// directory and `%H` the home directory.
+    pub fn add_str(&mut self, text: &str) -> Result<()> {
+        self.add_str_at_depth(text, 0)
+    }
+
+    fn add_str_at_depth(&mut self, text: &str, depth: usize) -> Result<()> {
+        for line in text.lines() {
+            match parse_line(line) {
+                Some(Line::Include(path)) => {
+                    if let Some(path) = expand_include(&path) {
+                        self.add_file_at_depth(&path, depth + 1)?;
+                    }
+                }
+                Some(Line::Sequence(sequence, string, keysym)) => {
+                    self.insert(&sequence, string, keysym)
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, sequence: &[u32], string: String, keysym: u32) {
+        let mut node = 0;
+        for &key in sequence {
+            node = match self.nodes[node].children.get(&key) {
+                Some(&child) => child,
+                None => {
+                    self.nodes.push(Node::default());
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children.insert(key, child);
+                    child
+                }
+            };
+        }
+        self.nodes[node].result = Some((string, keysym));
+    }
+
+    /// Starts a compose state machine over this table.
+    pub fn state(&self) -> ComposeState<'_> {
+        ComposeState {
+            table: self,
+            node: 0,
+        }
+    }
+}
+
+impl Default for ComposeTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn expand_include(path: &str) -> Option<PathBuf> {
+    let mut ret = String::new();
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            ret.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'L' => ret.push_str(locale_compose_file(&current_locale())?.to_str()?),
+            'S' => ret.push_str(system_dir().to_str()?),
+            'H' => ret.push_str(&std::env::var("HOME").ok()?),
+            '%' => ret.push('%'),
+            _ => return None,
+        }
+    }
+    Some(PathBuf::from(ret))
+}
+
+/// What a key did to the compose state.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ComposeStatus {
+    /// The key is not part of a sequence and should be handled as usual.
+    Nothing,
+    /// The key continued a sequence; nothing should be typed yet.
+    Composing,
+    /// The key completed a sequence, which typed `string`.
+    Composed { string: String, keysym: u32 },
+    /// The key does not continue the sequence in progress, which was abandoned along with the
+    /// key.
+    Cancelled,
+}
+
+/// Tracks a sequence in progress, fed with the keysym of each key press.
+pub struct ComposeState<'a> {
+    table: &'a ComposeTable,
+    node: usize,
+}
+
+/// Modifier keys are pressed in the middle of sequences (to type uppercase letters, say) and
+/// must not interrupt them.
+fn is_modifier(keysym: u32) -> bool {
+    matches!(
+        keysym,
+        keysym::XK_Shift_L..=keysym::XK_Hyper_R
+            | keysym::XK_ISO_Lock..=keysym::XK_ISO_Level5_Lock
+            | keysym::XK_Mode_switch
+            | keysym::XK_Num_Lock
+    )
+}
+
+impl ComposeState<'_> {
+    pub fn feed(&mut self, keysym: u32) -> ComposeStatus {
+        if is_modifier(keysym) {
+            return if self.node == 0 {
+                ComposeStatus::Nothing
+            } else {
+                ComposeStatus::Composing
+            };
+        }
+
+        let node = &self.table.nodes[self.node];
+        let Some(&next) = node.children.get(&keysym) else {
+            let composing = self.node != 0;
+            self.node = 0;
+            return if composing {
+                ComposeStatus::Cancelled
+            } else {
+                ComposeStatus::Nothing
+            };
+        };
+
+        let next_node = &self.table.nodes[next];
+        if next_node.children.is_empty() {
+            self.node = 0;
+            match &next_node.result {
+                Some((string, keysym)) => ComposeStatus::Composed {
+                    string: string.clone(),
+                    keysym: *keysym,
+                },
+                None => ComposeStatus::Cancelled,
+            }
+        } else {
+            self.node = next;
+            ComposeStatus::Composing
+        }
+    }
+
+    /// Whether a sequence is in progress.
+    pub fn is_composing(&self) -> bool {
+        self.node != 0
+    }
+
+    /// Abandons any sequence in progress, for instance when focus moves elsewhere.
+    pub fn reset(&mut self) {
+        self.node = 0;
+    }
+}
@@ -0,0 +1,483 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, IoSlice, Read, Result, Write};
+use std::os::unix::net::UnixStream;
+use std::time::{Duration, Instant};
+
+use crate::split::{split_poly, split_poly_text};
+use crate::{
+    BigReqEnable, BigReqEnableReply, ClientInit, Event, QueryExtension, QueryExtensionReply,
+    QueryTextExtents, QueryTextExtentsReply, ServerInit, XError,
+};
+
+/// Requests are coalesced into a buffer of this size before being written to the socket.
+const BUFFER_SIZE: usize = 16384;
+
+/// Request payloads at least this large are handed to `writev` directly rather than being copied
+/// into the write buffer.
+const WRITEV_THRESHOLD: usize = 4096;
+
+/// A connection to an X server that owns the socket, buffers outgoing requests, and sorts
+/// incoming packets into replies and events.
+///
+/// Requests are not written until the buffer fills, `flush` is called, or the caller waits for a
+/// reply or an event.
+pub struct Connection<S> {
+    stream: S,
+    server_init: ServerInit,
+    buffer: Vec<u8>,
+    sequence: u16,
+    next_id: u32,
+    /// The BIG-REQUESTS maximum request length, once we have tried to enable the extension.
+    big_request_length: Option<Option<usize>>,
+    awaiting: HashSet<u16>,
+    replies: HashMap<u16, VecDeque<std::result::Result<Vec<u8>, XError>>>,
+    events: VecDeque<Event>,
+}
+
+/// Streams whose reads can be given a time limit, so that events can be waited for with a
+/// timeout.
+pub trait ReadTimeout {
+    /// Makes reads fail with `WouldBlock` or `TimedOut` after `timeout`, or block indefinitely
+    /// if it is `None`.
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()>;
+}
+
+impl ReadTimeout for UnixStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+impl Connection<UnixStream> {
+    /// Connects to the local display named by `display` (such as ":0" or ":1.1"), or by
+    /// `$DISPLAY` if it is `None`.
+    ///
+    /// Returns the connection and the screen number given in the display name.
+    pub fn open(display: Option<&str>) -> Result<(Self, usize)> {
+        let display = match display {
+            Some(display) => display.to_owned(),
+            None => std::env::var("DISPLAY")
+                .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "DISPLAY is not set"))?,
+        };
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid display name {:?}", display),
+            )
+        };
+
+        let colon = display.rfind(':').ok_or_else(invalid)?;
+        let host = &display[..colon];
+        if !host.is_empty() && host != "unix" {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("only local displays are supported, not {:?}", display),
+            ));
+        }
+        let mut numbers = display[colon + 1..].splitn(2, '.');
+        let number: u32 = numbers
+            .next()
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(invalid)?;
+        let screen = match numbers.next() {
+            Some(screen) => screen.parse().map_err(|_| invalid())?,
+            None => 0,
+        };
+
+        let socket = UnixStream::connect(format!("/tmp/.X11-unix/X{}", number))?;
+        Ok((Connection::new(socket)?, screen))
+    }
+}
+
+impl<S: Read + Write> Connection<S> {
+    /// Performs the connection setup handshake on `stream`.
+    pub fn new(mut stream: S) -> Result<Self> {
+        let client_init: Vec<u8> = ClientInit::new().into();
+        stream.write_all(&client_init)?;
+        let server_init = ServerInit::from_stream(&mut stream)?;
+
+        Ok(Connection {
+            stream,
+            server_init,
+            buffer: Vec::with_capacity(BUFFER_SIZE),
+            sequence: 0,
+            next_id: 0,
+            big_request_length: None,
+            awaiting: HashSet::new(),
+            replies: HashMap::new(),
+            events: VecDeque::new(),
+        })
+    }
+
+    pub fn server_init(&self) -> &ServerInit {
+        &self.server_init
+    }
+
+    /// Allocates a new resource ID for a window, pixmap, GC or other resource.
+    pub fn generate_id(&mut self) -> Result<u32> {
+        let mask = self.server_init.resource_id_mask;
+        let exhausted = || io::Error::other("resource IDs exhausted");
+        if mask == 0 {
+            return Err(exhausted());
+        }
+        let id = self.next_id << mask.trailing_zeros();
+        if id & !mask != 0 {
+            return Err(exhausted());
+        }
+        self.next_id += 1;
+
+        Ok(self.server_init.resource_id_base | id)
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns the longest request the server accepts, in units of four bytes.
+    ///
+    /// The first call enables the BIG-REQUESTS extension if the server supports it, which
+    /// requires a round trip.
+    pub fn maximum_request_length(&mut self) -> Result<usize> {
+        if self.big_request_length.is_none() {
+            let query = QueryExtension::new("BIG-REQUESTS".into());
+            let reply = QueryExtensionReply::from_bytes(&self.request(&query.as_bytes())?)?;
+            self.big_request_length = Some(if reply.present {
+                let enable = BigReqEnable::new(reply.major_opcode);
+                let reply = BigReqEnableReply::from_bytes(&self.request(&enable.as_bytes())?)?;
+                Some(reply.maximum_request_length as usize)
+            } else {
+                None
+            });
+        }
+
+        Ok(self
+            .big_request_length
+            .unwrap()
+            .unwrap_or(self.server_init.maximum_request_length as usize))
+    }
+
+    /// Queues a request that does not generate a reply, returning its sequence number.
+    ///
+    /// Poly drawing and text requests that exceed the maximum request length are split into
+    /// several requests; the sequence number of the last is returned.
+    pub fn send(&mut self, request: &[u8]) -> Result<u16> {
+        self.write_request(request, &[])
+    }
+
+    /// Queues a request whose trailing `data` is sent without copying it when it is large.
+    ///
+    /// `data` is padded to a multiple of four bytes here.
+    pub fn send_with_data(&mut self, header: &[u8], data: &[u8]) -> Result<u16> {
+        self.write_request(header, data)
+    }
+
+    /// Queues a request that generates a reply, returning the sequence number to pass to
+    /// `wait_for_reply`.
+    pub fn send_with_reply(&mut self, request: &[u8]) -> Result<u16> {
+        let sequence = self.write_request(request, &[])?;
+        self.awaiting.insert(sequence);
+        Ok(sequence)
+    }
+
+    /// Sends a request and blocks until its reply arrives.
+    pub fn request(&mut self, request: &[u8]) -> Result<Vec<u8>> {
+        let sequence = self.send_with_reply(request)?;
+        self.wait_for_reply(sequence)
+    }
+
+    /// Flushes pending requests and blocks until the reply for `sequence` arrives.
+    ///
+    /// An error from the server for that request is returned as an `io::Error` wrapping the
+    /// `XError`.
+    pub fn wait_for_reply(&mut self, sequence: u16) -> Result<Vec<u8>> {
+        Ok(self.wait_for_replies(sequence, |_| true)?.pop().unwrap())
+    }
+
+    /// Like `wait_for_reply`, for requests such as `ListFontsWithInfo` which generate more than
+    /// one reply; `is_last` is called on each reply to recognize the final one.
+    pub fn wait_for_replies<F>(&mut self, sequence: u16, mut is_last: F) -> Result<Vec<Vec<u8>>>
+    where
+        F: FnMut(&[u8]) -> bool,
+    {
+        let mut ret = Vec::new();
+        let result = self.flush().and_then(|_| loop {
+            match self
+                .replies
+                .get_mut(&sequence)
+                .and_then(VecDeque::pop_front)
+            {
+                Some(Ok(reply)) => {
+                    let last = is_last(&reply);
+                    ret.push(reply);
+                    if last {
+                        return Ok(());
+                    }
+                }
+                Some(Err(error)) => return Err(io::Error::other(error)),
+                None => self.read_packet()?,
+            }
+        });
+
+        self.awaiting.remove(&sequence);
+        self.replies.remove(&sequence);
+        result.map(|_| ret)
+    }
+
+    /// Flushes pending requests and blocks until an event (or an error for a request without a
+    /// reply) arrives.
+    pub fn next_event(&mut self) -> Result<Event> {
+        self.flush()?;
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+            self.read_packet()?;
+        }
+    }
+
+    /// Flushes pending requests and blocks until an event for which `matches` returns true
+    /// arrives. Other events stay queued for `next_event`.
+    pub fn wait_for_event<F>(&mut self, mut matches: F) -> Result<Event>
+    where
+        F: FnMut(&Event) -> bool,
+    {
+        self.flush()?;
+        let mut checked = 0;
+        loop {
+            if let Some(i) = self.events.iter().skip(checked).position(&mut matches) {
+                return Ok(self.events.remove(checked + i).unwrap());
+            }
+            checked = self.events.len();
+            self.read_packet()?;
+        }
+    }
+
+    /// Writes all buffered requests to the socket.
+    pub fn flush(&mut self) -> Result<()> {
+        if !self.buffer.is_empty() {
+            self.stream.write_all(&self.buffer)?;
+            self.buffer.clear();
+        }
+        self.stream.flush()
+    }
+
+    /// Buffers a request, filling in its length field from the actual size of `header` and
+    /// `data`.
+    fn write_request(&mut self, header: &[u8], data: &[u8]) -> Result<u16> {
+        let padding = (4 - (data.len() % 4)) % 4;
+        let length = (header.len() + data.len() + padding) / 4;
+
+        let big = length > self.server_init.maximum_request_length as usize;
+        if big {
+            let maximum = self.maximum_request_length()?;
+            if length + 1 > maximum {
+                if data.is_empty() {
+                    if let Some(sequence) = self.write_split(header, maximum)? {
+                        return Ok(sequence);
+                    }
+                }
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "request of {} bytes exceeds the maximum request length of {} bytes",
+                        length * 4,
+                        maximum * 4
+                    ),
+                ));
+            }
+        }
+
+        if data.len() < WRITEV_THRESHOLD
+            && self.buffer.len() + header.len() + data.len() + padding + 4 > BUFFER_SIZE
+        {
+            self.flush()?;
+        }
+
+        let start = self.buffer.len();
+        if big {
+            // BIG-REQUESTS: a zero length followed by a 32-bit length that counts itself.
+            self.buffer.extend_from_slice(&header[..2]);
+            self.buffer.extend_from_slice(&[0, 0]);
+            self.buffer
+                .extend_from_slice(&(length as u32 + 1).to_be_bytes());
+            self.buffer.extend_from_slice(&header[4..]);
+        } else {
+            self.buffer.extend_from_slice(header);
+            self.buffer[start + 2..start + 4].copy_from_slice(&(length as u16).to_be_bytes());
+        }
+
+        if data.len() >= WRITEV_THRESHOLD {
+            self.write_buffer_and(data, padding)?;
+        } else {
+            self.buffer.extend_from_slice(data);
+            self.buffer.extend_from_slice(&[0; 3][..padding]);
+            if self.buffer.len() >= BUFFER_SIZE {
+                self.flush()?;
+            }
+        }
+
+        self.sequence = self.sequence.wrapping_add(1);
+        Ok(self.sequence)
+    }
+
+    /// Writes an oversized request as several smaller ones if it is a kind that can be split.
+    fn write_split(&mut self, request: &[u8], maximum: usize) -> Result<Option<u16>> {
+        let max_bytes = if maximum > self.server_init.maximum_request_length as usize {
+            (maximum - 1) * 4
+        } else {
+            maximum * 4
+        };
+
+        let parts = match split_poly(request, max_bytes) {
+            Some(parts) => parts,
+            None => match split_poly_text(request, max_bytes, |fontable, string| {
+                let query = QueryTextExtents::new(fontable, string);
+                let reply = QueryTextExtentsReply::from_bytes(&self.request(&query.as_bytes())?)?;
+                Ok(reply.overall_width)
+            })? {
+                Some(parts) => parts,
+                None => return Ok(None),
+            },
+        };
+
+        let mut sequence = self.sequence;
+        for part in parts {
+            sequence = self.write_request(&part, &[])?;
+        }
+        Ok(Some(sequence))
+    }
+
+    /// Writes the buffer followed by `data` and `padding` zero bytes in as few syscalls as
+    /// possible.
+    fn write_buffer_and(&mut self, data: &[u8], padding: usize) -> Result<()> {
+        let pad = [0; 3];
+        let mut slices = [
+            IoSlice::new(&self.buffer),
+            IoSlice::new(data),
+            IoSlice::new(&pad[..padding]),
+        ];
+        let mut slices = &mut slices[..];
+        IoSlice::advance_slices(&mut slices, 0);
+
+        while !slices.is_empty() {
+            match self.stream.write_vectored(slices) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => IoSlice::advance_slices(&mut slices, n),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.buffer.clear();
+        Ok(())
+    }
+
+    fn read_packet(&mut self) -> Result<()> {
+        let mut header = [0; 32];
+        self.stream.read_exact(&mut header)?;
+        self.handle_packet(header)
+    }
+
+    fn handle_packet(&mut self, header: [u8; 32]) -> Result<()> {
+        let sequence = u16::from_be_bytes([header[2], header[3]]);
+        let length = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+
+        match header[0] {
+            0 => {
+                let error = XError::from_bytes(&header);
+                if self.awaiting.contains(&sequence) {
+                    self.replies
+                        .entry(sequence)
+                        .or_default()
+                        .push_back(Err(error));
+                } else {
+                    self.events.push_back(Event::Error(error));
+                }
+            }
+            1 => {
+                let mut reply = header.to_vec();
+                reply.resize(32 + length * 4, 0);
+                self.stream.read_exact(&mut reply[32..])?;
+                // Nothing will ever ask for the reply to a request queued with `send`.
+                if self.awaiting.contains(&sequence) {
+                    self.replies
+                        .entry(sequence)
+                        .or_default()
+                        .push_back(Ok(reply));
+                }
+            }
+            t if t & 0x7f == 35 => {
+                // GenericEvent carries extra data we don't decode yet.
+                let mut rest = vec![0; length * 4];
+                self.stream.read_exact(&mut rest)?;
+                self.events.push_back(Event::from_bytes(&header));
+            }
+            _ => self.events.push_back(Event::from_bytes(&header)),
+        }
+
+        Ok(())
+    }
+}
+
+impl<S: Read + Write + ReadTimeout> Connection<S> {
+    /// Like `wait_for_event`, but gives up and returns `None` if no matching event arrives
+    /// within `timeout`.
+    pub fn wait_for_event_timeout<F>(
+        &mut self,
+        timeout: Duration,
+        mut matches: F,
+    ) -> Result<Option<Event>>
+    where
+        F: FnMut(&Event) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        self.flush()?;
+        let mut checked = 0;
+        loop {
+            if let Some(i) = self.events.iter().skip(checked).position(&mut matches) {
+                return Ok(self.events.remove(checked + i));
+            }
+            checked = self.events.len();
+            if !self.read_packet_before(deadline)? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Like `next_event`, but returns `None` if no event arrives within `timeout`.
+    pub fn next_event_timeout(&mut self, timeout: Duration) -> Result<Option<Event>> {
+        self.wait_for_event_timeout(timeout, |_| true)
+    }
+
+    /// Reads a packet, returning false instead if none starts arriving before `deadline`.
+    ///
+    /// Only the first byte is read with a time limit: giving up partway through a packet would
+    /// lose our place in the stream.
+    fn read_packet_before(&mut self, deadline: Instant) -> Result<bool> {
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(false);
+        }
+
+        let mut header = [0; 32];
+        self.stream.set_read_timeout(Some(deadline - now))?;
+        let first = self.stream.read(&mut header[..1]);
+        self.stream.set_read_timeout(None)?;
+        match first {
+            Ok(0) => Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(_) => {
+                self.stream.read_exact(&mut header[1..])?;
+                self.handle_packet(header)?;
+                Ok(true)
+            }
+            Err(e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                Ok(false)
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => Ok(true),
+            Err(e) => Err(e),
+        }
+    }
+}
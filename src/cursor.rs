@@ -0,0 +1,355 @@
+//! Cursor requests.
+
+use std::io::{Read, Result, Write};
+
+use byteorder::{BigEndian, WriteBytesExt};
+
+use crate::{CloseFont, Connection, OpenFont, Rgb};
+
+/// Glyph indices of the standard shapes in the `cursor` font. The mask of each shape is the
+/// following glyph.
+pub mod cursor_font {
+    pub const NUM_GLYPHS: u16 = 154;
+    pub const X_CURSOR: u16 = 0;
+    pub const ARROW: u16 = 2;
+    pub const BASED_ARROW_DOWN: u16 = 4;
+    pub const BASED_ARROW_UP: u16 = 6;
+    pub const BOAT: u16 = 8;
+    pub const BOGOSITY: u16 = 10;
+    pub const BOTTOM_LEFT_CORNER: u16 = 12;
+    pub const BOTTOM_RIGHT_CORNER: u16 = 14;
+    pub const BOTTOM_SIDE: u16 = 16;
+    pub const BOTTOM_TEE: u16 = 18;
+    pub const BOX_SPIRAL: u16 = 20;
+    pub const CENTER_PTR: u16 = 22;
+    pub const CIRCLE: u16 = 24;
+    pub const CLOCK: u16 = 26;
+    pub const COFFEE_MUG: u16 = 28;
+    pub const CROSS: u16 = 30;
+    pub const CROSS_REVERSE: u16 = 32;
+    pub const CROSSHAIR: u16 = 34;
+    pub const DIAMOND_CROSS: u16 = 36;
+    pub const DOT: u16 = 38;
+    pub const DOTBOX: u16 = 40;
+    pub const DOUBLE_ARROW: u16 = 42;
+    pub const DRAFT_LARGE: u16 = 44;
+    pub const DRAFT_SMALL: u16 = 46;
+    pub const DRAPED_BOX: u16 = 48;
+    pub const EXCHANGE: u16 = 50;
+    pub const FLEUR: u16 = 52;
+    pub const GOBBLER: u16 = 54;
+    pub const GUMBY: u16 = 56;
+    pub const HAND1: u16 = 58;
+    pub const HAND2: u16 = 60;
+    pub const HEART: u16 = 62;
+    pub const ICON: u16 = 64;
+    pub const IRON_CROSS: u16 = 66;
+    pub const LEFT_PTR: u16 = 68;
+    pub const LEFT_SIDE: u16 = 70;
+    pub const LEFT_TEE: u16 = 72;
+    pub const LEFTBUTTON: u16 = 74;
+    pub const LL_ANGLE: u16 = 76;
+    pub const LR_ANGLE: u16 = 78;
+    pub const MAN: u16 = 80;
+    pub const MIDDLEBUTTON: u16 = 82;
+    pub const MOUSE: u16 = 84;
+    pub const PENCIL: u16 = 86;
+    pub const PIRATE: u16 = 88;
+    pub const PLUS: u16 = 90;
+    pub const QUESTION_ARROW: u16 = 92;
+    pub const RIGHT_PTR: u16 = 94;
+    pub const RIGHT_SIDE: u16 = 96;
+    pub const RIGHT_TEE: u16 = 98;
+    pub const RIGHTBUTTON: u16 = 100;
+    pub const RTL_LOGO: u16 = 102;
+    pub const SAILBOAT: u16 = 104;
+    pub const SB_DOWN_ARROW: u16 = 106;
+    pub const SB_H_DOUBLE_ARROW: u16 = 108;
+    pub const SB_LEFT_ARROW: u16 = 110;
+    pub const SB_RIGHT_ARROW: u16 = 112;
+    pub const SB_UP_ARROW: u16 = 114;
+    pub const SB_V_DOUBLE_ARROW: u16 = 116;
+    pub const SHUTTLE: u16 = 118;
+    pub const SIZING: u16 = 120;
+    pub const SPIDER: u16 = 122;
+    pub const SPRAYCAN: u16 = 124;
+    pub const STAR: u16 = 126;
+    pub const TARGET: u16 = 128;
+    pub const TCROSS: u16 = 130;
+    pub const TOP_LEFT_ARROW: u16 = 132;
+    pub const TOP_LEFT_CORNER: u16 = 134;
+    pub const TOP_RIGHT_CORNER: u16 = 136;
+    pub const TOP_SIDE: u16 = 138;
+    pub const TOP_TEE: u16 = 140;
+    pub const TREK: u16 = 142;
+    pub const UL_ANGLE: u16 = 144;
+    pub const UMBRELLA: u16 = 146;
+    pub const UR_ANGLE: u16 = 148;
+    pub const WATCH: u16 = 150;
+    pub const XTERM: u16 = 152;
+
+    const NAMES: &[(&str, u16)] = &[
+        ("X_cursor", X_CURSOR),
+        ("arrow", ARROW),
+        ("based_arrow_down", BASED_ARROW_DOWN),
+        ("based_arrow_up", BASED_ARROW_UP),
+        ("boat", BOAT),
+        ("bogosity", BOGOSITY),
+        ("bottom_left_corner", BOTTOM_LEFT_CORNER),
+        ("bottom_right_corner", BOTTOM_RIGHT_CORNER),
+        ("bottom_side", BOTTOM_SIDE),
+        ("bottom_tee", BOTTOM_TEE),
+        ("box_spiral", BOX_SPIRAL),
+        ("center_ptr", CENTER_PTR),
+        ("circle", CIRCLE),
+        ("clock", CLOCK),
+        ("coffee_mug", COFFEE_MUG),
+        ("cross", CROSS),
+        ("cross_reverse", CROSS_REVERSE),
+        ("crosshair", CROSSHAIR),
+        ("diamond_cross", DIAMOND_CROSS),
+        ("dot", DOT),
+        ("dotbox", DOTBOX),
+        ("double_arrow", DOUBLE_ARROW),
+        ("draft_large", DRAFT_LARGE),
+        ("draft_small", DRAFT_SMALL),
+        ("draped_box", DRAPED_BOX),
+        ("exchange", EXCHANGE),
+        ("fleur", FLEUR),
+        ("gobbler", GOBBLER),
+        ("gumby", GUMBY),
+        ("hand1", HAND1),
+        ("hand2", HAND2),
+        ("heart", HEART),
+        ("icon", ICON),
+        ("iron_cross", IRON_CROSS),
+        ("left_ptr", LEFT_PTR),
+        ("left_side", LEFT_SIDE),
+        ("left_tee", LEFT_TEE),
+        ("leftbutton", LEFTBUTTON),
+        ("ll_angle", LL_ANGLE),
+        ("lr_angle", LR_ANGLE),
+        ("man", MAN),
+        ("middlebutton", MIDDLEBUTTON),
+        ("mouse", MOUSE),
+        ("pencil", PENCIL),
+        ("pirate", PIRATE),
+        ("plus", PLUS),
+        ("question_arrow", QUESTION_ARROW),
+        ("right_ptr", RIGHT_PTR),
+        ("right_side", RIGHT_SIDE),
+        ("right_tee", RIGHT_TEE),
+        ("rightbutton", RIGHTBUTTON),
+        ("rtl_logo", RTL_LOGO),
+        ("sailboat", SAILBOAT),
+        ("sb_down_arrow", SB_DOWN_ARROW),
+        ("sb_h_double_arrow", SB_H_DOUBLE_ARROW),
+        ("sb_left_arrow", SB_LEFT_ARROW),
+        ("sb_right_arrow", SB_RIGHT_ARROW),
+        ("sb_up_arrow", SB_UP_ARROW),
+        ("sb_v_double_arrow", SB_V_DOUBLE_ARROW),
+        ("shuttle", SHUTTLE),
+        ("sizing", SIZING),
+        ("spider", SPIDER),
+        ("spraycan", SPRAYCAN),
+        ("star", STAR),
+        ("target", TARGET),
+        ("tcross", TCROSS),
+        ("top_left_arrow", TOP_LEFT_ARROW),
+        ("top_left_corner", TOP_LEFT_CORNER),
+        ("top_right_corner", TOP_RIGHT_CORNER),
+        ("top_side", TOP_SIDE),
+        ("top_tee", TOP_TEE),
+        ("trek", TREK),
+        ("ul_angle", UL_ANGLE),
+        ("umbrella", UMBRELLA),
+        ("ur_angle", UR_ANGLE),
+        ("watch", WATCH),
+        ("xterm", XTERM),
+    ];
+
+    /// Looks up a shape by its name without the `XC_` prefix, such as `left_ptr`. Cursor themes
+    /// use the same names.
+    pub fn by_name(name: &str) -> Option<u16> {
+        NAMES
+            .iter()
+            .find(|&&(shape_name, _)| shape_name == name)
+            .map(|&(_, shape)| shape)
+    }
+}
+
+pub struct CreateCursor {
+    cid: u32,
+    source: u32,
+    mask: u32,
+    foreground: Rgb,
+    background: Rgb,
+    x: u16,
+    y: u16,
+}
+
+impl CreateCursor {
+    /// `source` and `mask` are pixmaps of depth 1; `mask` may be 0 (None). `x` and `y` are the
+    /// hotspot.
+    pub fn new(
+        cid: u32,
+        source: u32,
+        mask: u32,
+        foreground: Rgb,
+        background: Rgb,
+        x: u16,
+        y: u16,
+    ) -> Self {
+        CreateCursor {
+            cid,
+            source,
+            mask,
+            foreground,
+            background,
+            x,
+            y,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(93).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(8).unwrap();
+        ret.write_u32::<BigEndian>(self.cid).unwrap();
+        ret.write_u32::<BigEndian>(self.source).unwrap();
+        ret.write_u32::<BigEndian>(self.mask).unwrap();
+        self.foreground.write_to(&mut ret);
+        self.background.write_to(&mut ret);
+        ret.write_u16::<BigEndian>(self.x).unwrap();
+        ret.write_u16::<BigEndian>(self.y).unwrap();
+
+        ret
+    }
+}
+
+pub struct CreateGlyphCursor {
+    cid: u32,
+    source_font: u32,
+    mask_font: u32,
+    source_char: u16,
+    mask_char: u16,
+    foreground: Rgb,
+    background: Rgb,
+}
+
+impl CreateGlyphCursor {
+    /// `mask_font` may be 0 (None), in which case `mask_char` is ignored.
+    pub fn new(
+        cid: u32,
+        source_font: u32,
+        mask_font: u32,
+        source_char: u16,
+        mask_char: u16,
+        foreground: Rgb,
+        background: Rgb,
+    ) -> Self {
+        CreateGlyphCursor {
+            cid,
+            source_font,
+            mask_font,
+            source_char,
+            mask_char,
+            foreground,
+            background,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(94).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(8).unwrap();
+        ret.write_u32::<BigEndian>(self.cid).unwrap();
+        ret.write_u32::<BigEndian>(self.source_font).unwrap();
+        ret.write_u32::<BigEndian>(self.mask_font).unwrap();
+        ret.write_u16::<BigEndian>(self.source_char).unwrap();
+        ret.write_u16::<BigEndian>(self.mask_char).unwrap();
+        self.foreground.write_to(&mut ret);
+        self.background.write_to(&mut ret);
+
+        ret
+    }
+}
+
+pub struct FreeCursor {
+    cursor: u32,
+}
+
+impl FreeCursor {
+    pub fn new(cursor: u32) -> Self {
+        FreeCursor { cursor }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(95).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(2).unwrap();
+        ret.write_u32::<BigEndian>(self.cursor).unwrap();
+
+        ret
+    }
+}
+
+pub struct RecolorCursor {
+    cursor: u32,
+    foreground: Rgb,
+    background: Rgb,
+}
+
+impl RecolorCursor {
+    pub fn new(cursor: u32, foreground: Rgb, background: Rgb) -> Self {
+        RecolorCursor {
+            cursor,
+            foreground,
+            background,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(96).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(5).unwrap();
+        ret.write_u32::<BigEndian>(self.cursor).unwrap();
+        self.foreground.write_to(&mut ret);
+        self.background.write_to(&mut ret);
+
+        ret
+    }
+}
+
+/// Creates a black-on-white cursor from a shape in `cursor_font`, for use as the `cursor`
+/// window attribute.
+pub fn create_font_cursor<S: Read + Write>(conn: &mut Connection<S>, shape: u16) -> Result<u32> {
+    let font = conn.generate_id()?;
+    conn.send(&OpenFont::new(font, "cursor".to_string()).as_bytes())?;
+
+    let cid = conn.generate_id()?;
+    let black = Rgb {
+        red: 0,
+        green: 0,
+        blue: 0,
+    };
+    let white = Rgb {
+        red: 0xffff,
+        green: 0xffff,
+        blue: 0xffff,
+    };
+    let cursor = CreateGlyphCursor::new(cid, font, font, shape, shape + 1, black, white);
+    conn.send(&cursor.as_bytes())?;
+    // The cursor keeps its own reference to the glyphs.
+    conn.send(&CloseFont::new(font).as_bytes())?;
+
+    Ok(cid)
+}
@@ -0,0 +1,494 @@
+use byteorder::{BigEndian, WriteBytesExt};
+
+use crate::request_length;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    pub x: i16,
+    pub y: i16,
+}
+
+impl Point {
+    fn write_to(&self, ret: &mut Vec<u8>) {
+        ret.write_i16::<BigEndian>(self.x).unwrap();
+        ret.write_i16::<BigEndian>(self.y).unwrap();
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rectangle {
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rectangle {
+    fn write_to(&self, ret: &mut Vec<u8>) {
+        ret.write_i16::<BigEndian>(self.x).unwrap();
+        ret.write_i16::<BigEndian>(self.y).unwrap();
+        ret.write_u16::<BigEndian>(self.width).unwrap();
+        ret.write_u16::<BigEndian>(self.height).unwrap();
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Segment {
+    pub x1: i16,
+    pub y1: i16,
+    pub x2: i16,
+    pub y2: i16,
+}
+
+impl Segment {
+    fn write_to(&self, ret: &mut Vec<u8>) {
+        ret.write_i16::<BigEndian>(self.x1).unwrap();
+        ret.write_i16::<BigEndian>(self.y1).unwrap();
+        ret.write_i16::<BigEndian>(self.x2).unwrap();
+        ret.write_i16::<BigEndian>(self.y2).unwrap();
+    }
+}
+
+/// An arc of the ellipse inscribed in the given rectangle; angles are in 64ths of a degree,
+/// counter-clockwise from three o'clock.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Arc {
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub angle1: i16,
+    pub angle2: i16,
+}
+
+impl Arc {
+    fn write_to(&self, ret: &mut Vec<u8>) {
+        ret.write_i16::<BigEndian>(self.x).unwrap();
+        ret.write_i16::<BigEndian>(self.y).unwrap();
+        ret.write_u16::<BigEndian>(self.width).unwrap();
+        ret.write_u16::<BigEndian>(self.height).unwrap();
+        ret.write_i16::<BigEndian>(self.angle1).unwrap();
+        ret.write_i16::<BigEndian>(self.angle2).unwrap();
+    }
+}
+
+/// Whether each point after the first is absolute or relative to the previous point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CoordinateMode {
+    Origin = 0,
+    Previous = 1,
+}
+
+/// A hint to the server about the polygon filled by `FillPoly`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Complex = 0,
+    Nonconvex = 1,
+    Convex = 2,
+}
+
+/// Writes the header shared by the poly drawing requests, which is followed by `body_len` bytes
+/// of points, segments, rectangles or arcs.
+fn poly_header(opcode: u8, data: u8, drawable: u32, gc: u32, body_len: usize) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(12 + body_len);
+
+    ret.write_u8(opcode).unwrap();
+    ret.write_u8(data).unwrap();
+    ret.write_u16::<BigEndian>(request_length(12 + body_len))
+        .unwrap();
+    ret.write_u32::<BigEndian>(drawable).unwrap();
+    ret.write_u32::<BigEndian>(gc).unwrap();
+
+    ret
+}
+
+pub struct PolyPoint<'a> {
+    coordinate_mode: CoordinateMode,
+    drawable: u32,
+    gc: u32,
+    points: &'a [Point],
+}
+
+impl<'a> PolyPoint<'a> {
+    pub fn new(
+        coordinate_mode: CoordinateMode,
+        drawable: u32,
+        gc: u32,
+        points: &'a [Point],
+    ) -> Self {
+        PolyPoint {
+            coordinate_mode,
+            drawable,
+            gc,
+            points,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = poly_header(
+            64,
+            self.coordinate_mode as u8,
+            self.drawable,
+            self.gc,
+            self.points.len() * 4,
+        );
+        for point in self.points {
+            point.write_to(&mut ret);
+        }
+
+        ret
+    }
+}
+
+pub struct PolyLine<'a> {
+    coordinate_mode: CoordinateMode,
+    drawable: u32,
+    gc: u32,
+    points: &'a [Point],
+}
+
+impl<'a> PolyLine<'a> {
+    pub fn new(
+        coordinate_mode: CoordinateMode,
+        drawable: u32,
+        gc: u32,
+        points: &'a [Point],
+    ) -> Self {
+        PolyLine {
+            coordinate_mode,
+            drawable,
+            gc,
+            points,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = poly_header(
+            65,
+            self.coordinate_mode as u8,
+            self.drawable,
+            self.gc,
+            self.points.len() * 4,
+        );
+        for point in self.points {
+            point.write_to(&mut ret);
+        }
+
+        ret
+    }
+}
+
+pub struct PolySegment<'a> {
+    drawable: u32,
+    gc: u32,
+    segments: &'a [Segment],
+}
+
+impl<'a> PolySegment<'a> {
+    pub fn new(drawable: u32, gc: u32, segments: &'a [Segment]) -> Self {
+        PolySegment {
+            drawable,
+            gc,
+            segments,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = poly_header(66, 0, self.drawable, self.gc, self.segments.len() * 8);
+        for segment in self.segments {
+            segment.write_to(&mut ret);
+        }
+
+        ret
+    }
+}
+
+pub struct PolyRectangle<'a> {
+    drawable: u32,
+    gc: u32,
+    rectangles: &'a [Rectangle],
+}
+
+impl<'a> PolyRectangle<'a> {
+    pub fn new(drawable: u32, gc: u32, rectangles: &'a [Rectangle]) -> Self {
+        PolyRectangle {
+            drawable,
+            gc,
+            rectangles,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = poly_header(67, 0, self.drawable, self.gc, self.rectangles.len() * 8);
+        for rectangle in self.rectangles {
+            rectangle.write_to(&mut ret);
+        }
+
+        ret
+    }
+}
+
+pub struct PolyArc<'a> {
+    drawable: u32,
+    gc: u32,
+    arcs: &'a [Arc],
+}
+
+impl<'a> PolyArc<'a> {
+    pub fn new(drawable: u32, gc: u32, arcs: &'a [Arc]) -> Self {
+        PolyArc { drawable, gc, arcs }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = poly_header(68, 0, self.drawable, self.gc, self.arcs.len() * 12);
+        for arc in self.arcs {
+            arc.write_to(&mut ret);
+        }
+
+        ret
+    }
+}
+
+pub struct FillPoly<'a> {
+    drawable: u32,
+    gc: u32,
+    shape: Shape,
+    coordinate_mode: CoordinateMode,
+    points: &'a [Point],
+}
+
+impl<'a> FillPoly<'a> {
+    pub fn new(
+        drawable: u32,
+        gc: u32,
+        shape: Shape,
+        coordinate_mode: CoordinateMode,
+        points: &'a [Point],
+    ) -> Self {
+        FillPoly {
+            drawable,
+            gc,
+            shape,
+            coordinate_mode,
+            points,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = poly_header(69, 0, self.drawable, self.gc, 4 + self.points.len() * 4);
+        ret.write_u8(self.shape as u8).unwrap();
+        ret.write_u8(self.coordinate_mode as u8).unwrap();
+        ret.write_u16::<BigEndian>(0).unwrap();
+        for point in self.points {
+            point.write_to(&mut ret);
+        }
+
+        ret
+    }
+}
+
+pub struct PolyFillRectangle<'a> {
+    drawable: u32,
+    gc: u32,
+    rectangles: &'a [Rectangle],
+}
+
+impl<'a> PolyFillRectangle<'a> {
+    pub fn new(drawable: u32, gc: u32, rectangles: &'a [Rectangle]) -> Self {
+        PolyFillRectangle {
+            drawable,
+            gc,
+            rectangles,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = poly_header(70, 0, self.drawable, self.gc, self.rectangles.len() * 8);
+        for rectangle in self.rectangles {
+            rectangle.write_to(&mut ret);
+        }
+
+        ret
+    }
+}
+
+pub struct PolyFillArc<'a> {
+    drawable: u32,
+    gc: u32,
+    arcs: &'a [Arc],
+}
+
+impl<'a> PolyFillArc<'a> {
+    pub fn new(drawable: u32, gc: u32, arcs: &'a [Arc]) -> Self {
+        PolyFillArc { drawable, gc, arcs }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = poly_header(71, 0, self.drawable, self.gc, self.arcs.len() * 12);
+        for arc in self.arcs {
+            arc.write_to(&mut ret);
+        }
+
+        ret
+    }
+}
+
+pub struct ClearArea {
+    exposures: bool,
+    window: u32,
+    x: i16,
+    y: i16,
+    width: u16,
+    height: u16,
+}
+
+impl ClearArea {
+    /// A `width` or `height` of zero extends the area to the edge of the window.
+    pub fn new(exposures: bool, window: u32, x: i16, y: i16, width: u16, height: u16) -> Self {
+        ClearArea {
+            exposures,
+            window,
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(61).unwrap();
+        ret.write_u8(self.exposures as u8).unwrap();
+        ret.write_u16::<BigEndian>(4).unwrap();
+        ret.write_u32::<BigEndian>(self.window).unwrap();
+        ret.write_i16::<BigEndian>(self.x).unwrap();
+        ret.write_i16::<BigEndian>(self.y).unwrap();
+        ret.write_u16::<BigEndian>(self.width).unwrap();
+        ret.write_u16::<BigEndian>(self.height).unwrap();
+
+        ret
+    }
+}
+
+pub struct CopyArea {
+    src_drawable: u32,
+    dst_drawable: u32,
+    gc: u32,
+    src_x: i16,
+    src_y: i16,
+    dst_x: i16,
+    dst_y: i16,
+    width: u16,
+    height: u16,
+}
+
+impl CopyArea {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        src_drawable: u32,
+        dst_drawable: u32,
+        gc: u32,
+        src_x: i16,
+        src_y: i16,
+        dst_x: i16,
+        dst_y: i16,
+        width: u16,
+        height: u16,
+    ) -> Self {
+        CopyArea {
+            src_drawable,
+            dst_drawable,
+            gc,
+            src_x,
+            src_y,
+            dst_x,
+            dst_y,
+            width,
+            height,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(62).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(7).unwrap();
+        ret.write_u32::<BigEndian>(self.src_drawable).unwrap();
+        ret.write_u32::<BigEndian>(self.dst_drawable).unwrap();
+        ret.write_u32::<BigEndian>(self.gc).unwrap();
+        ret.write_i16::<BigEndian>(self.src_x).unwrap();
+        ret.write_i16::<BigEndian>(self.src_y).unwrap();
+        ret.write_i16::<BigEndian>(self.dst_x).unwrap();
+        ret.write_i16::<BigEndian>(self.dst_y).unwrap();
+        ret.write_u16::<BigEndian>(self.width).unwrap();
+        ret.write_u16::<BigEndian>(self.height).unwrap();
+
+        ret
+    }
+}
+
+/// Copies a single bit plane of the source, drawing set bits in the GC's foreground and clear
+/// bits in its background.
+pub struct CopyPlane {
+    src_drawable: u32,
+    dst_drawable: u32,
+    gc: u32,
+    src_x: i16,
+    src_y: i16,
+    dst_x: i16,
+    dst_y: i16,
+    width: u16,
+    height: u16,
+    bit_plane: u32,
+}
+
+impl CopyPlane {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        src_drawable: u32,
+        dst_drawable: u32,
+        gc: u32,
+        src_x: i16,
+        src_y: i16,
+        dst_x: i16,
+        dst_y: i16,
+        width: u16,
+        height: u16,
+        bit_plane: u32,
+    ) -> Self {
+        CopyPlane {
+            src_drawable,
+            dst_drawable,
+            gc,
+            src_x,
+            src_y,
+            dst_x,
+            dst_y,
+            width,
+            height,
+            bit_plane,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(63).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(8).unwrap();
+        ret.write_u32::<BigEndian>(self.src_drawable).unwrap();
+        ret.write_u32::<BigEndian>(self.dst_drawable).unwrap();
+        ret.write_u32::<BigEndian>(self.gc).unwrap();
+        ret.write_i16::<BigEndian>(self.src_x).unwrap();
+        ret.write_i16::<BigEndian>(self.src_y).unwrap();
+        ret.write_i16::<BigEndian>(self.dst_x).unwrap();
+        ret.write_i16::<BigEndian>(self.dst_y).unwrap();
+        ret.write_u16::<BigEndian>(self.width).unwrap();
+        ret.write_u16::<BigEndian>(self.height).unwrap();
+        ret.write_u32::<BigEndian>(self.bit_plane).unwrap();
+
+        ret
+    }
+}
@@ -0,0 +1,457 @@
+use std::io::{Read, Result, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{request_length, Connection};
+
+/// Reads a LISTofSTR, where each string is preceded by its length in one byte.
+pub(crate) fn read_strings(mut data: &[u8], count: usize) -> Vec<String> {
+    let mut ret = Vec::new();
+    for _ in 0..count {
+        let Some((&len, rest)) = data.split_first() else {
+            break;
+        };
+        let len = std::cmp::min(len as usize, rest.len());
+        ret.push(String::from_utf8_lossy(&rest[..len]).into_owned());
+        data = &rest[len..];
+    }
+    ret
+}
+
+/// The requests that take a 16-bit maximum count and a pattern.
+fn list_fonts_request(opcode: u8, max_names: u16, pattern: &str) -> Vec<u8> {
+    let pattern = pattern.as_bytes();
+    let padding = (4 - (pattern.len() % 4)) % 4;
+    let mut ret = Vec::new();
+
+    ret.write_u8(opcode).unwrap();
+    ret.write_u8(0).unwrap();
+    ret.write_u16::<BigEndian>(request_length(8 + pattern.len() + padding))
+        .unwrap();
+    ret.write_u16::<BigEndian>(max_names).unwrap();
+    ret.write_u16::<BigEndian>(pattern.len() as u16).unwrap();
+    ret.write_all(pattern).unwrap();
+    for _ in 0..padding {
+        ret.write_u8(0).unwrap();
+    }
+
+    ret
+}
+
+pub struct OpenFont {
+    fid: u32,
+    name: String,
+}
+
+impl OpenFont {
+    /// `name` may be an XLFD pattern with wildcards, in which case the server picks a match.
+    pub fn new(fid: u32, name: String) -> Self {
+        OpenFont { fid, name }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let name = self.name.as_bytes();
+        let padding = (4 - (name.len() % 4)) % 4;
+        let mut ret = Vec::new();
+
+        ret.write_u8(45).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(request_length(12 + name.len() + padding))
+            .unwrap();
+        ret.write_u32::<BigEndian>(self.fid).unwrap();
+        ret.write_u16::<BigEndian>(name.len() as u16).unwrap();
+        ret.write_u16::<BigEndian>(0).unwrap();
+        ret.write_all(name).unwrap();
+        for _ in 0..padding {
+            ret.write_u8(0).unwrap();
+        }
+
+        ret
+    }
+}
+
+pub struct CloseFont {
+    font: u32,
+}
+
+impl CloseFont {
+    pub fn new(font: u32) -> Self {
+        CloseFont { font }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(46).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(2).unwrap();
+        ret.write_u32::<BigEndian>(self.font).unwrap();
+
+        ret
+    }
+}
+
+/// The metrics of one character, or the minimum or maximum over a font.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CharInfo {
+    pub left_side_bearing: i16,
+    pub right_side_bearing: i16,
+    pub character_width: i16,
+    pub ascent: i16,
+    pub descent: i16,
+    pub attributes: u16,
+}
+
+impl CharInfo {
+    fn read_from<T: Read>(buf: &mut T) -> Result<Self> {
+        let left_side_bearing = buf.read_i16::<BigEndian>()?;
+        let right_side_bearing = buf.read_i16::<BigEndian>()?;
+        let character_width = buf.read_i16::<BigEndian>()?;
+        let ascent = buf.read_i16::<BigEndian>()?;
+        let descent = buf.read_i16::<BigEndian>()?;
+        let attributes = buf.read_u16::<BigEndian>()?;
+
+        Ok(CharInfo {
+            left_side_bearing,
+            right_side_bearing,
+            character_width,
+            ascent,
+            descent,
+            attributes,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FontProp {
+    pub name: u32,
+    pub value: u32,
+}
+
+/// The font information shared by the `QueryFont` and `ListFontsWithInfo` replies.
+#[derive(Clone, Debug)]
+pub struct FontInfo {
+    pub min_bounds: CharInfo,
+    pub max_bounds: CharInfo,
+    pub min_char_or_byte2: u16,
+    pub max_char_or_byte2: u16,
+    pub default_char: u16,
+    /// 0 for LeftToRight, 1 for RightToLeft.
+    pub draw_direction: u8,
+    pub min_byte1: u8,
+    pub max_byte1: u8,
+    pub all_chars_exist: bool,
+    pub font_ascent: i16,
+    pub font_descent: i16,
+    pub properties: Vec<FontProp>,
+}
+
+impl FontInfo {
+    /// Parses everything from the minimum bounds onwards, returning the info and the count that
+    /// follows the font descent (of char infos or of further replies).
+    fn read_from<T: Read>(buf: &mut T) -> Result<(Self, u32)> {
+        let min_bounds = CharInfo::read_from(buf)?;
+        buf.read_u32::<BigEndian>()?;
+        let max_bounds = CharInfo::read_from(buf)?;
+        buf.read_u32::<BigEndian>()?;
+        let min_char_or_byte2 = buf.read_u16::<BigEndian>()?;
+        let max_char_or_byte2 = buf.read_u16::<BigEndian>()?;
+        let default_char = buf.read_u16::<BigEndian>()?;
+        let properties_count = buf.read_u16::<BigEndian>()?;
+        let draw_direction = buf.read_u8()?;
+        let min_byte1 = buf.read_u8()?;
+        let max_byte1 = buf.read_u8()?;
+        let all_chars_exist = buf.read_u8()? != 0;
+        let font_ascent = buf.read_i16::<BigEndian>()?;
+        let font_descent = buf.read_i16::<BigEndian>()?;
+        let count = buf.read_u32::<BigEndian>()?;
+        let mut properties = Vec::new();
+        for _ in 0..properties_count {
+            let name = buf.read_u32::<BigEndian>()?;
+            let value = buf.read_u32::<BigEndian>()?;
+            properties.push(FontProp { name, value });
+        }
+
+        Ok((
+            FontInfo {
+                min_bounds,
+                max_bounds,
+                min_char_or_byte2,
+                max_char_or_byte2,
+                default_char,
+                draw_direction,
+                min_byte1,
+                max_byte1,
+                all_chars_exist,
+                font_ascent,
+                font_descent,
+                properties,
+            },
+            count,
+        ))
+    }
+
+    /// Looks up the value of a font property, such as the atom for `FONT` or `POINT_SIZE`.
+    pub fn property(&self, name: u32) -> Option<u32> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+            .map(|property| property.value)
+    }
+}
+
+pub struct QueryFont {
+    font: u32,
+}
+
+impl QueryFont {
+    /// `font` may also be a GC, to query its current font.
+    pub fn new(font: u32) -> Self {
+        QueryFont { font }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(47).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(2).unwrap();
+        ret.write_u32::<BigEndian>(self.font).unwrap();
+
+        ret
+    }
+}
+
+pub struct QueryFontReply {
+    pub info: FontInfo,
+    /// Per-character metrics, empty if every character has the same metrics as `max_bounds`.
+    pub char_infos: Vec<CharInfo>,
+}
+
+impl QueryFontReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let (info, char_infos_count) = FontInfo::read_from(&mut buf)?;
+        let mut char_infos = Vec::new();
+        for _ in 0..char_infos_count {
+            char_infos.push(CharInfo::read_from(&mut buf)?);
+        }
+
+        Ok(QueryFontReply { info, char_infos })
+    }
+
+    /// Returns the metrics of a character (byte1 in the high byte, for two-byte fonts), or
+    /// `None` if the font does not have it.
+    pub fn char_info(&self, c: u16) -> Option<CharInfo> {
+        let info = &self.info;
+        let (byte1, byte2) = ((c >> 8) as u8, c & 0xff);
+        if byte1 < info.min_byte1
+            || byte1 > info.max_byte1
+            || byte2 < info.min_char_or_byte2
+            || byte2 > info.max_char_or_byte2
+        {
+            return None;
+        }
+        if self.char_infos.is_empty() {
+            return Some(info.max_bounds);
+        }
+
+        let columns = (info.max_char_or_byte2 - info.min_char_or_byte2 + 1) as usize;
+        let index =
+            (byte1 - info.min_byte1) as usize * columns + (byte2 - info.min_char_or_byte2) as usize;
+        let char_info = *self.char_infos.get(index)?;
+        // Nonexistent characters have all-zero metrics.
+        if char_info == CharInfo::default() {
+            None
+        } else {
+            Some(char_info)
+        }
+    }
+
+    /// Computes the width of a string locally, substituting the default character for missing
+    /// ones as the server does.
+    pub fn text_width(&self, string: &[u16]) -> i32 {
+        string
+            .iter()
+            .filter_map(|&c| {
+                self.char_info(c)
+                    .or_else(|| self.char_info(self.info.default_char))
+            })
+            .map(|char_info| char_info.character_width as i32)
+            .sum()
+    }
+}
+
+pub struct ListFonts {
+    max_names: u16,
+    pattern: String,
+}
+
+impl ListFonts {
+    pub fn new(max_names: u16, pattern: String) -> Self {
+        ListFonts { max_names, pattern }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        list_fonts_request(49, self.max_names, &self.pattern)
+    }
+}
+
+pub struct ListFontsReply {
+    pub names: Vec<String>,
+}
+
+impl ListFontsReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let names_count = (&data[8..]).read_u16::<BigEndian>()?;
+
+        Ok(ListFontsReply {
+            names: read_strings(&data[32..], names_count as usize),
+        })
+    }
+}
+
+pub struct ListFontsWithInfo {
+    max_names: u16,
+    pattern: String,
+}
+
+impl ListFontsWithInfo {
+    pub fn new(max_names: u16, pattern: String) -> Self {
+        ListFontsWithInfo { max_names, pattern }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        list_fonts_request(50, self.max_names, &self.pattern)
+    }
+}
+
+/// One of the replies to `ListFontsWithInfo`, which sends one per matching font.
+pub struct ListFontsWithInfoReply {
+    pub name: String,
+    pub info: FontInfo,
+    /// A hint at how many more replies will follow.
+    pub replies_hint: u32,
+}
+
+impl ListFontsWithInfoReply {
+    /// Parses a reply, or returns `None` for the empty reply that ends the series.
+    pub fn from_bytes(data: &[u8]) -> Result<Option<Self>> {
+        let name_len = data[1] as usize;
+        if name_len == 0 {
+            return Ok(None);
+        }
+
+        let mut buf = std::io::Cursor::new(&data[8..]);
+        let (info, replies_hint) = FontInfo::read_from(&mut buf)?;
+        let start = 8 + buf.position() as usize;
+        let name = String::from_utf8_lossy(&data[start..][..name_len]).into_owned();
+
+        Ok(Some(ListFontsWithInfoReply {
+            name,
+            info,
+            replies_hint,
+        }))
+    }
+}
+
+/// Sends `ListFontsWithInfo` and collects its replies.
+pub fn list_fonts_with_info<S: Read + Write>(
+    conn: &mut Connection<S>,
+    max_names: u16,
+    pattern: String,
+) -> Result<Vec<ListFontsWithInfoReply>> {
+    let sequence = conn.send_with_reply(&ListFontsWithInfo::new(max_names, pattern).as_bytes())?;
+    let replies = conn.wait_for_replies(sequence, |reply| reply[1] == 0)?;
+
+    let mut ret = Vec::new();
+    for reply in replies {
+        ret.extend(ListFontsWithInfoReply::from_bytes(&reply)?);
+    }
+    Ok(ret)
+}
+
+pub struct SetFontPath {
+    path: Vec<String>,
+}
+
+impl SetFontPath {
+    /// An empty path restores the server's default. Fails if an element is longer than 255
+    /// bytes, the most a STR can hold.
+    pub fn new(path: Vec<String>) -> Result<Self> {
+        if let Some(element) = path.iter().find(|element| element.len() > 255) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("font path element {:?} is longer than 255 bytes", element),
+            ));
+        }
+        if path.len() > u16::MAX as usize {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "font path has too many elements",
+            ));
+        }
+        Ok(SetFontPath { path })
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(51).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(0).unwrap();
+        ret.write_u16::<BigEndian>(self.path.len() as u16).unwrap();
+        ret.write_u16::<BigEndian>(0).unwrap();
+        for element in &self.path {
+            ret.write_u8(element.len() as u8).unwrap();
+            ret.write_all(element.as_bytes()).unwrap();
+        }
+        let padding = (4 - (ret.len() % 4)) % 4;
+        for _ in 0..padding {
+            ret.write_u8(0).unwrap();
+        }
+
+        let length = request_length(ret.len());
+        ret[2..4].copy_from_slice(&length.to_be_bytes());
+        ret
+    }
+}
+
+pub struct GetFontPath;
+
+impl GetFontPath {
+    pub fn new() -> Self {
+        GetFontPath
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(52).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(1).unwrap();
+
+        ret
+    }
+}
+
+impl Default for GetFontPath {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct GetFontPathReply {
+    pub path: Vec<String>,
+}
+
+impl GetFontPathReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let path_count = (&data[8..]).read_u16::<BigEndian>()?;
+
+        Ok(GetFontPathReply {
+            path: read_strings(&data[32..], path_count as usize),
+        })
+    }
+}
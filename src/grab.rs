@@ -0,0 +1,518 @@
+//! Active and passive grabs of the pointer and keyboard, and server grabs.
+
+use std::io::{self, Read, Result, Write};
+
+use byteorder::{BigEndian, WriteBytesExt};
+
+use crate::{Connection, Keymap};
+
+/// The `time` that stands for the server's current time.
+pub const CURRENT_TIME: u32 = 0;
+/// The `modifiers` of a passive grab that match any combination of modifiers.
+pub const ANY_MODIFIER: u16 = 0x8000;
+/// The `key` of a passive grab that matches any key.
+pub const ANY_KEY: u8 = 0;
+/// The `button` of a passive grab that matches any button.
+pub const ANY_BUTTON: u8 = 0;
+
+const LOCK_MASK: u16 = 1 << 1;
+
+/// Whether events are processed normally or frozen until `AllowEvents` while grabbed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GrabMode {
+    Synchronous = 0,
+    Asynchronous = 1,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GrabStatus {
+    Success = 0,
+    AlreadyGrabbed = 1,
+    InvalidTime = 2,
+    NotViewable = 3,
+    Frozen = 4,
+}
+
+impl GrabStatus {
+    fn from_u8(status: u8) -> Result<Self> {
+        match status {
+            0 => Ok(GrabStatus::Success),
+            1 => Ok(GrabStatus::AlreadyGrabbed),
+            2 => Ok(GrabStatus::InvalidTime),
+            3 => Ok(GrabStatus::NotViewable),
+            4 => Ok(GrabStatus::Frozen),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown grab status {}", status),
+            )),
+        }
+    }
+}
+
+/// How `AllowEvents` releases frozen or queued events.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AllowEventsMode {
+    AsyncPointer = 0,
+    SyncPointer = 1,
+    ReplayPointer = 2,
+    AsyncKeyboard = 3,
+    SyncKeyboard = 4,
+    ReplayKeyboard = 5,
+    AsyncBoth = 6,
+    SyncBoth = 7,
+}
+
+/// Requests with nothing but a time after the header.
+fn time_request(opcode: u8, time: u32) -> Vec<u8> {
+    let mut ret = Vec::new();
+
+    ret.write_u8(opcode).unwrap();
+    ret.write_u8(0).unwrap();
+    ret.write_u16::<BigEndian>(2).unwrap();
+    ret.write_u32::<BigEndian>(time).unwrap();
+
+    ret
+}
+
+pub struct GrabPointer {
+    owner_events: bool,
+    grab_window: u32,
+    event_mask: u16,
+    pointer_mode: GrabMode,
+    keyboard_mode: GrabMode,
+    confine_to: u32,
+    cursor: u32,
+    time: u32,
+}
+
+impl GrabPointer {
+    /// `confine_to` and `cursor` may be 0 (None).
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        owner_events: bool,
+        grab_window: u32,
+        event_mask: u16,
+        pointer_mode: GrabMode,
+        keyboard_mode: GrabMode,
+        confine_to: u32,
+        cursor: u32,
+        time: u32,
+    ) -> Self {
+        GrabPointer {
+            owner_events,
+            grab_window,
+            event_mask,
+            pointer_mode,
+            keyboard_mode,
+            confine_to,
+            cursor,
+            time,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(26).unwrap();
+        ret.write_u8(self.owner_events as u8).unwrap();
+        ret.write_u16::<BigEndian>(6).unwrap();
+        ret.write_u32::<BigEndian>(self.grab_window).unwrap();
+        ret.write_u16::<BigEndian>(self.event_mask).unwrap();
+        ret.write_u8(self.pointer_mode as u8).unwrap();
+        ret.write_u8(self.keyboard_mode as u8).unwrap();
+        ret.write_u32::<BigEndian>(self.confine_to).unwrap();
+        ret.write_u32::<BigEndian>(self.cursor).unwrap();
+        ret.write_u32::<BigEndian>(self.time).unwrap();
+
+        ret
+    }
+}
+
+pub struct GrabPointerReply {
+    pub status: GrabStatus,
+}
+
+impl GrabPointerReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Ok(GrabPointerReply {
+            status: GrabStatus::from_u8(data[1])?,
+        })
+    }
+}
+
+pub struct UngrabPointer {
+    time: u32,
+}
+
+impl UngrabPointer {
+    pub fn new(time: u32) -> Self {
+        UngrabPointer { time }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        time_request(27, self.time)
+    }
+}
+
+pub struct GrabButton {
+    owner_events: bool,
+    grab_window: u32,
+    event_mask: u16,
+    pointer_mode: GrabMode,
+    keyboard_mode: GrabMode,
+    confine_to: u32,
+    cursor: u32,
+    button: u8,
+    modifiers: u16,
+}
+
+impl GrabButton {
+    /// `button` may be `ANY_BUTTON` and `modifiers` may be `ANY_MODIFIER`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        owner_events: bool,
+        grab_window: u32,
+        event_mask: u16,
+        pointer_mode: GrabMode,
+        keyboard_mode: GrabMode,
+        confine_to: u32,
+        cursor: u32,
+        button: u8,
+        modifiers: u16,
+    ) -> Self {
+        GrabButton {
+            owner_events,
+            grab_window,
+            event_mask,
+            pointer_mode,
+            keyboard_mode,
+            confine_to,
+            cursor,
+            button,
+            modifiers,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(28).unwrap();
+        ret.write_u8(self.owner_events as u8).unwrap();
+        ret.write_u16::<BigEndian>(6).unwrap();
+        ret.write_u32::<BigEndian>(self.grab_window).unwrap();
+        ret.write_u16::<BigEndian>(self.event_mask).unwrap();
+        ret.write_u8(self.pointer_mode as u8).unwrap();
+        ret.write_u8(self.keyboard_mode as u8).unwrap();
+        ret.write_u32::<BigEndian>(self.confine_to).unwrap();
+        ret.write_u32::<BigEndian>(self.cursor).unwrap();
+        ret.write_u8(self.button).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(self.modifiers).unwrap();
+
+        ret
+    }
+}
+
+pub struct UngrabButton {
+    button: u8,
+    grab_window: u32,
+    modifiers: u16,
+}
+
+impl UngrabButton {
+    pub fn new(button: u8, grab_window: u32, modifiers: u16) -> Self {
+        UngrabButton {
+            button,
+            grab_window,
+            modifiers,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(29).unwrap();
+        ret.write_u8(self.button).unwrap();
+        ret.write_u16::<BigEndian>(3).unwrap();
+        ret.write_u32::<BigEndian>(self.grab_window).unwrap();
+        ret.write_u16::<BigEndian>(self.modifiers).unwrap();
+        ret.write_u16::<BigEndian>(0).unwrap();
+
+        ret
+    }
+}
+
+pub struct ChangeActivePointerGrab {
+    cursor: u32,
+    time: u32,
+    event_mask: u16,
+}
+
+impl ChangeActivePointerGrab {
+    pub fn new(cursor: u32, time: u32, event_mask: u16) -> Self {
+        ChangeActivePointerGrab {
+            cursor,
+            time,
+            event_mask,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(30).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(4).unwrap();
+        ret.write_u32::<BigEndian>(self.cursor).unwrap();
+        ret.write_u32::<BigEndian>(self.time).unwrap();
+        ret.write_u16::<BigEndian>(self.event_mask).unwrap();
+        ret.write_u16::<BigEndian>(0).unwrap();
+
+        ret
+    }
+}
+
+pub struct GrabKeyboard {
+    owner_events: bool,
+    grab_window: u32,
+    time: u32,
+    pointer_mode: GrabMode,
+    keyboard_mode: GrabMode,
+}
+
+impl GrabKeyboard {
+    pub fn new(
+        owner_events: bool,
+        grab_window: u32,
+        time: u32,
+        pointer_mode: GrabMode,
+        keyboard_mode: GrabMode,
+    ) -> Self {
+        GrabKeyboard {
+            owner_events,
+            grab_window,
+            time,
+            pointer_mode,
+            keyboard_mode,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(31).unwrap();
+        ret.write_u8(self.owner_events as u8).unwrap();
+        ret.write_u16::<BigEndian>(4).unwrap();
+        ret.write_u32::<BigEndian>(self.grab_window).unwrap();
+        ret.write_u32::<BigEndian>(self.time).unwrap();
+        ret.write_u8(self.pointer_mode as u8).unwrap();
+        ret.write_u8(self.keyboard_mode as u8).unwrap();
+        ret.write_u16::<BigEndian>(0).unwrap();
+
+        ret
+    }
+}
+
+pub struct GrabKeyboardReply {
+    pub status: GrabStatus,
+}
+
+impl GrabKeyboardReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Ok(GrabKeyboardReply {
+            status: GrabStatus::from_u8(data[1])?,
+        })
+    }
+}
+
+pub struct UngrabKeyboard {
+    time: u32,
+}
+
+impl UngrabKeyboard {
+    pub fn new(time: u32) -> Self {
+        UngrabKeyboard { time }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        time_request(32, self.time)
+    }
+}
+
+pub struct GrabKey {
+    owner_events: bool,
+    grab_window: u32,
+    modifiers: u16,
+    key: u8,
+    pointer_mode: GrabMode,
+    keyboard_mode: GrabMode,
+}
+
+impl GrabKey {
+    /// `key` may be `ANY_KEY` and `modifiers` may be `ANY_MODIFIER`.
+    pub fn new(
+        owner_events: bool,
+        grab_window: u32,
+        modifiers: u16,
+        key: u8,
+        pointer_mode: GrabMode,
+        keyboard_mode: GrabMode,
+    ) -> Self {
+        GrabKey {
+            owner_events,
+            grab_window,
+            modifiers,
+            key,
+            pointer_mode,
+            keyboard_mode,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(33).unwrap();
+        ret.write_u8(self.owner_events as u8).unwrap();
+        ret.write_u16::<BigEndian>(4).unwrap();
+        ret.write_u32::<BigEndian>(self.grab_window).unwrap();
+        ret.write_u16::<BigEndian>(self.modifiers).unwrap();
+        ret.write_u8(self.key).unwrap();
+        ret.write_u8(self.pointer_mode as u8).unwrap();
+        ret.write_u8(self.keyboard_mode as u8).unwrap();
+        ret.write_all(&[0; 3]).unwrap();
+
+        ret
+    }
+}
+
+pub struct UngrabKey {
+    key: u8,
+    grab_window: u32,
+    modifiers: u16,
+}
+
+impl UngrabKey {
+    pub fn new(key: u8, grab_window: u32, modifiers: u16) -> Self {
+        UngrabKey {
+            key,
+            grab_window,
+            modifiers,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(34).unwrap();
+        ret.write_u8(self.key).unwrap();
+        ret.write_u16::<BigEndian>(3).unwrap();
+        ret.write_u32::<BigEndian>(self.grab_window).unwrap();
+        ret.write_u16::<BigEndian>(self.modifiers).unwrap();
+        ret.write_u16::<BigEndian>(0).unwrap();
+
+        ret
+    }
+}
+
+pub struct AllowEvents {
+    mode: AllowEventsMode,
+    time: u32,
+}
+
+impl AllowEvents {
+    pub fn new(mode: AllowEventsMode, time: u32) -> Self {
+        AllowEvents { mode, time }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = time_request(35, self.time);
+        ret[1] = self.mode as u8;
+        ret
+    }
+}
+
+pub struct GrabServer;
+
+impl GrabServer {
+    pub fn new() -> Self {
+        GrabServer
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        vec![36, 0, 0, 1]
+    }
+}
+
+impl Default for GrabServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct UngrabServer;
+
+impl UngrabServer {
+    pub fn new() -> Self {
+        UngrabServer
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        vec![37, 0, 0, 1]
+    }
+}
+
+impl Default for UngrabServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Every combination of Lock and the Num_Lock modifier, to add to the modifiers of a passive
+/// grab so that it works whichever locks are on.
+pub fn lock_combinations(keymap: &Keymap) -> Vec<u16> {
+    let num_lock = keymap.num_lock_mask();
+    let mut ret = vec![0, LOCK_MASK];
+    if num_lock != 0 && num_lock != LOCK_MASK {
+        ret.extend([num_lock, num_lock | LOCK_MASK]);
+    }
+    ret
+}
+
+/// Grabs `key` with `modifiers` on `grab_window` asynchronously, as a global hotkey, whatever
+/// the state of CapsLock and NumLock.
+///
+/// A key already grabbed by another client causes an Access error event.
+pub fn grab_hotkey<S: Read + Write>(
+    conn: &mut Connection<S>,
+    keymap: &Keymap,
+    grab_window: u32,
+    modifiers: u16,
+    key: u8,
+) -> Result<()> {
+    for locks in lock_combinations(keymap) {
+        let grab = GrabKey::new(
+            false,
+            grab_window,
+            modifiers | locks,
+            key,
+            GrabMode::Asynchronous,
+            GrabMode::Asynchronous,
+        );
+        conn.send(&grab.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Releases a grab made with `grab_hotkey`.
+pub fn ungrab_hotkey<S: Read + Write>(
+    conn: &mut Connection<S>,
+    keymap: &Keymap,
+    grab_window: u32,
+    modifiers: u16,
+    key: u8,
+) -> Result<()> {
+    for locks in lock_combinations(keymap) {
+        conn.send(&UngrabKey::new(key, grab_window, modifiers | locks).as_bytes())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,648 @@
+//! Conversion between RGBA8 buffers and the image formats used by `PutImage` and `GetImage`.
+//!
+//! An RGBA8 buffer is `width * height` pixels in row-major order, four bytes per pixel.
+
+use std::io::{self, Read, Result, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{request_length, Connection, Rectangle, ServerInit, Visual};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageFormat {
+    Bitmap = 0,
+    XYPixmap = 1,
+    ZPixmap = 2,
+}
+
+/// How the server lays out images of one depth, gathered from `ServerInit` and a `Visual`.
+#[derive(Clone, Debug)]
+pub struct ImageLayout {
+    pub depth: u8,
+    pub bits_per_pixel: u8,
+    pub scanline_pad: u8,
+    pub image_byte_order: u8,
+    pub bitmap_format_bit_order: u8,
+    pub bitmap_format_scanline_unit: u8,
+    pub bitmap_format_scanline_pad: u8,
+    pub red_mask: u32,
+    pub green_mask: u32,
+    pub blue_mask: u32,
+}
+
+impl ImageLayout {
+    /// `visual` supplies the color masks, and may be `None` for depth 1.
+    pub fn new(server_init: &ServerInit, depth: u8, visual: Option<&Visual>) -> Result<Self> {
+        let format = server_init
+            .pixmap_formats
+            .iter()
+            .find(|format| format.depth == depth)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("server has no pixmap format for depth {}", depth),
+                )
+            })?;
+
+        Ok(ImageLayout {
+            depth,
+            bits_per_pixel: format.bits_per_pixel,
+            scanline_pad: format.scanline_pad,
+            image_byte_order: server_init.image_byte_order,
+            bitmap_format_bit_order: server_init.bitmap_format_bit_order,
+            bitmap_format_scanline_unit: server_init.bitmap_format_scanline_unit,
+            bitmap_format_scanline_pad: server_init.bitmap_format_scanline_pad,
+            red_mask: visual.map_or(0, |v| v.red_mask),
+            green_mask: visual.map_or(0, |v| v.green_mask),
+            blue_mask: visual.map_or(0, |v| v.blue_mask),
+        })
+    }
+
+    /// The number of bytes in one scanline (of one plane, for `XYPixmap`).
+    pub fn bytes_per_line(&self, format: ImageFormat, width: u16) -> usize {
+        let (bits, pad) = match format {
+            ImageFormat::ZPixmap => (
+                width as usize * self.bits_per_pixel as usize,
+                self.scanline_pad as usize,
+            ),
+            ImageFormat::Bitmap | ImageFormat::XYPixmap => {
+                (width as usize, self.bitmap_format_scanline_pad as usize)
+            }
+        };
+        bits.div_ceil(pad) * pad / 8
+    }
+
+    /// Computes the pixel value for an RGBA8 color from the visual's masks.
+    ///
+    /// At depth 1 the pixel is set where the color is at least half opaque, as for bitmaps.
+    pub fn pixel(&self, rgba: &[u8]) -> u32 {
+        if self.depth == 1 {
+            return (rgba[3] >= 128) as u32;
+        }
+        scale_to_mask(rgba[0], self.red_mask)
+            | scale_to_mask(rgba[1], self.green_mask)
+            | scale_to_mask(rgba[2], self.blue_mask)
+    }
+
+    /// Computes the RGBA8 color of a pixel value from the visual's masks.
+    ///
+    /// At depth 1, set pixels are opaque white and clear pixels transparent black.
+    pub fn rgba(&self, pixel: u32) -> [u8; 4] {
+        if self.depth == 1 {
+            return if pixel & 1 != 0 { [255; 4] } else { [0; 4] };
+        }
+        [
+            scale_from_mask(pixel, self.red_mask),
+            scale_from_mask(pixel, self.green_mask),
+            scale_from_mask(pixel, self.blue_mask),
+            255,
+        ]
+    }
+
+    fn msb_first(&self) -> bool {
+        self.image_byte_order == 1
+    }
+
+    /// Sets the bit for pixel `x` of a scanline in bitmap format.
+    fn set_bitmap_bit(&self, line: &mut [u8], x: usize) {
+        let (offset, bit) = self.bitmap_bit(x);
+        line[offset] |= 1 << bit;
+    }
+
+    /// Locates pixel `x` of a bitmap scanline, honoring the scanline unit, bit order and byte
+    /// order.
+    fn bitmap_bit(&self, x: usize) -> (usize, usize) {
+        let unit = self.bitmap_format_scanline_unit as usize;
+        let i = x % unit;
+        let position = if self.bitmap_format_bit_order == 1 {
+            unit - 1 - i
+        } else {
+            i
+        };
+        let byte = if self.msb_first() {
+            unit / 8 - 1 - position / 8
+        } else {
+            position / 8
+        };
+        (x / unit * unit / 8 + byte, position % 8)
+    }
+
+    fn bitmap_bit_is_set(&self, line: &[u8], x: usize) -> bool {
+        let (offset, bit) = self.bitmap_bit(x);
+        line[offset] & (1 << bit) != 0
+    }
+
+    fn read_zpixmap_pixel(&self, line: &[u8], x: usize) -> u32 {
+        match self.bits_per_pixel {
+            1 => self.bitmap_bit_is_set(line, x) as u32,
+            4 => {
+                let high = (x % 2 == 0) == self.msb_first();
+                let byte = line[x / 2];
+                (if high { byte >> 4 } else { byte & 0xf }) as u32
+            }
+            bpp => {
+                let size = bpp as usize / 8;
+                let mut bytes = [0; 4];
+                for i in 0..size {
+                    let byte = if self.msb_first() { size - 1 - i } else { i };
+                    bytes[byte] = line[x * size + i];
+                }
+                u32::from_le_bytes(bytes)
+            }
+        }
+    }
+
+    fn write_zpixmap_pixel(&self, line: &mut [u8], x: usize, pixel: u32) {
+        match self.bits_per_pixel {
+            1 => {
+                if pixel & 1 != 0 {
+                    self.set_bitmap_bit(line, x);
+                }
+            }
+            4 => {
+                let high = (x % 2 == 0) == self.msb_first();
+                let nibble = (pixel & 0xf) as u8;
+                line[x / 2] |= if high { nibble << 4 } else { nibble };
+            }
+            bpp => {
+                let size = bpp as usize / 8;
+                let bytes = pixel.to_le_bytes();
+                for i in 0..size {
+                    let byte = if self.msb_first() { size - 1 - i } else { i };
+                    line[x * size + i] = bytes[byte];
+                }
+            }
+        }
+    }
+}
+
+fn scale_to_mask(value: u8, mask: u32) -> u32 {
+    if mask == 0 {
+        return 0;
+    }
+    let shift = mask.trailing_zeros();
+    let max = mask >> shift;
+    ((value as u32 * max + 127) / 255) << shift
+}
+
+fn scale_from_mask(pixel: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let shift = mask.trailing_zeros();
+    let max = (mask >> shift) as u64;
+    ((((pixel & mask) >> shift) as u64 * 255 + max / 2) / max) as u8
+}
+
+fn masks_missing(layout: &ImageLayout, format: ImageFormat) -> Option<io::Error> {
+    if format != ImageFormat::Bitmap
+        && layout.depth > 1
+        && (layout.red_mask | layout.green_mask | layout.blue_mask) == 0
+    {
+        Some(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "converting between RGBA and pixels requires a visual with color masks",
+        ))
+    } else {
+        None
+    }
+}
+
+/// Image data in the server's layout, ready to be sent with `PutImage`.
+pub struct Image {
+    pub format: ImageFormat,
+    pub width: u16,
+    pub height: u16,
+    pub depth: u8,
+    pub bytes_per_line: usize,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    /// Converts an RGBA8 buffer to `format`.
+    ///
+    /// Pixel values come from the layout's color masks, so ZPixmap and XYPixmap conversion
+    /// above depth 1 needs a TrueColor or DirectColor visual. Bitmap images set a bit for every
+    /// pixel whose alpha is at least half opaque.
+    pub fn from_rgba(
+        layout: &ImageLayout,
+        format: ImageFormat,
+        rgba: &[u8],
+        width: u16,
+        height: u16,
+    ) -> Result<Self> {
+        let pixel_count = width as usize * height as usize;
+        if rgba.len() != pixel_count * 4 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "expected {} bytes of RGBA data for a {}x{} image, got {}",
+                    pixel_count * 4,
+                    width,
+                    height,
+                    rgba.len()
+                ),
+            ));
+        }
+        if let Some(err) = masks_missing(layout, format) {
+            return Err(err);
+        }
+
+        let bytes_per_line = layout.bytes_per_line(format, width);
+        let pixels = rgba.chunks_exact(4);
+        let (depth, data) = match format {
+            ImageFormat::Bitmap => {
+                let mut data = vec![0; bytes_per_line * height as usize];
+                for (i, rgba) in pixels.enumerate() {
+                    if rgba[3] >= 128 {
+                        let line = i / width as usize;
+                        let line = &mut data[line * bytes_per_line..][..bytes_per_line];
+                        layout.set_bitmap_bit(line, i % width as usize);
+                    }
+                }
+                (1, data)
+            }
+            ImageFormat::XYPixmap => {
+                let plane_size = bytes_per_line * height as usize;
+                let mut data = vec![0; plane_size * layout.depth as usize];
+                for (i, rgba) in pixels.enumerate() {
+                    let pixel = layout.pixel(rgba);
+                    let line = i / width as usize;
+                    // Planes are sent most significant first.
+                    for plane in 0..layout.depth as usize {
+                        if pixel & (1 << (layout.depth as usize - 1 - plane)) != 0 {
+                            let offset = plane * plane_size + line * bytes_per_line;
+                            let line = &mut data[offset..][..bytes_per_line];
+                            layout.set_bitmap_bit(line, i % width as usize);
+                        }
+                    }
+                }
+                (layout.depth, data)
+            }
+            ImageFormat::ZPixmap => {
+                let mut data = vec![0; bytes_per_line * height as usize];
+                for (i, rgba) in pixels.enumerate() {
+                    let line = i / width as usize;
+                    let line = &mut data[line * bytes_per_line..][..bytes_per_line];
+                    layout.write_zpixmap_pixel(line, i % width as usize, layout.pixel(rgba));
+                }
+                (layout.depth, data)
+            }
+        };
+
+        Ok(Image {
+            format,
+            width,
+            height,
+            depth,
+            bytes_per_line,
+            data,
+        })
+    }
+
+    /// Packs raw pixel values in row-major order into a ZPixmap image; the inverse of
+    /// `to_pixels`.
+    pub fn from_pixels(
+        layout: &ImageLayout,
+        pixels: &[u32],
+        width: u16,
+        height: u16,
+    ) -> Result<Self> {
+        if pixels.len() != width as usize * height as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "expected {} pixels for a {}x{} image, got {}",
+                    width as usize * height as usize,
+                    width,
+                    height,
+                    pixels.len()
+                ),
+            ));
+        }
+
+        let bytes_per_line = layout.bytes_per_line(ImageFormat::ZPixmap, width);
+        let mut data = vec![0; bytes_per_line * height as usize];
+        for (i, &pixel) in pixels.iter().enumerate() {
+            let line = i / width as usize;
+            let line = &mut data[line * bytes_per_line..][..bytes_per_line];
+            layout.write_zpixmap_pixel(line, i % width as usize, pixel);
+        }
+
+        Ok(Image {
+            format: ImageFormat::ZPixmap,
+            width,
+            height,
+            depth: layout.depth,
+            bytes_per_line,
+            data,
+        })
+    }
+
+    /// Wraps the data of a `GetImage` reply for a `width` by `height` area.
+    pub fn from_reply(
+        layout: &ImageLayout,
+        format: ImageFormat,
+        width: u16,
+        height: u16,
+        reply: GetImageReply,
+    ) -> Self {
+        Image {
+            format,
+            width,
+            height,
+            depth: reply.depth,
+            bytes_per_line: layout.bytes_per_line(format, width),
+            data: reply.data,
+        }
+    }
+
+    /// Reads `area` of `drawable` with `GetImage`.
+    pub fn get<S: Read + Write>(
+        conn: &mut Connection<S>,
+        layout: &ImageLayout,
+        format: ImageFormat,
+        drawable: u32,
+        area: Rectangle,
+    ) -> Result<Self> {
+        let request = GetImage::new(
+            format,
+            drawable,
+            area.x,
+            area.y,
+            area.width,
+            area.height,
+            !0,
+        );
+        let reply = GetImageReply::from_bytes(&conn.request(&request.as_bytes())?)?;
+        Ok(Image::from_reply(
+            layout,
+            format,
+            area.width,
+            area.height,
+            reply,
+        ))
+    }
+
+    /// Converts the image to an RGBA8 buffer; the inverse of `from_rgba`.
+    pub fn to_rgba(&self, layout: &ImageLayout) -> Result<Vec<u8>> {
+        if let Some(err) = masks_missing(layout, self.format) {
+            return Err(err);
+        }
+        Ok(self
+            .to_pixels(layout)?
+            .into_iter()
+            .flat_map(|pixel| layout.rgba(pixel))
+            .collect())
+    }
+
+    /// Extracts the raw pixel values in row-major order, for visuals whose colors have to be
+    /// looked up in a colormap.
+    pub fn to_pixels(&self, layout: &ImageLayout) -> Result<Vec<u32>> {
+        let planes = match self.format {
+            ImageFormat::XYPixmap => self.depth as usize,
+            _ => 1,
+        };
+        let plane_size = self.bytes_per_line * self.height as usize;
+        if self.data.len() < plane_size * planes {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "expected {} bytes of image data, got {}",
+                    plane_size * planes,
+                    self.data.len()
+                ),
+            ));
+        }
+
+        let mut ret = Vec::with_capacity(self.width as usize * self.height as usize);
+        for y in 0..self.height as usize {
+            let offset = y * self.bytes_per_line;
+            for x in 0..self.width as usize {
+                ret.push(match self.format {
+                    ImageFormat::ZPixmap => {
+                        layout.read_zpixmap_pixel(&self.data[offset..][..self.bytes_per_line], x)
+                    }
+                    ImageFormat::Bitmap | ImageFormat::XYPixmap => {
+                        (0..planes).fold(0, |pixel, plane| {
+                            let line = &self.data[plane * plane_size + offset..];
+                            pixel << 1 | layout.bitmap_bit_is_set(line, x) as u32
+                        })
+                    }
+                });
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Draws the image at (`dst_x`, `dst_y`), using as many `PutImage` requests as the maximum
+    /// request length requires.
+    pub fn put<S: Read + Write>(
+        &self,
+        conn: &mut Connection<S>,
+        drawable: u32,
+        gc: u32,
+        dst_x: i16,
+        dst_y: i16,
+    ) -> Result<()> {
+        let planes = match self.format {
+            ImageFormat::XYPixmap => self.depth as usize,
+            _ => 1,
+        };
+        let row_size = self.bytes_per_line * planes;
+        // Leave room for the header and a BIG-REQUESTS length.
+        let max_rows = (conn.maximum_request_length()? * 4 - 28) / row_size.max(1);
+        if max_rows == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a single scanline exceeds the maximum request length",
+            ));
+        }
+
+        let plane_size = self.bytes_per_line * self.height as usize;
+        let mut y = 0;
+        while y < self.height as usize {
+            let rows = std::cmp::min(max_rows, self.height as usize - y);
+            let put = |data: &[u8]| {
+                PutImage::new(
+                    self.format,
+                    drawable,
+                    gc,
+                    self.width,
+                    rows as u16,
+                    dst_x,
+                    dst_y.wrapping_add(y as i16),
+                    0,
+                    self.depth,
+                    data,
+                )
+                .header()
+            };
+
+            if planes == 1 {
+                let data = &self.data[y * self.bytes_per_line..][..rows * self.bytes_per_line];
+                conn.send_with_data(&put(data), data)?;
+            } else {
+                let mut data = Vec::with_capacity(rows * row_size);
+                for plane in 0..planes {
+                    let offset = plane * plane_size + y * self.bytes_per_line;
+                    data.extend_from_slice(&self.data[offset..][..rows * self.bytes_per_line]);
+                }
+                conn.send_with_data(&put(&data), &data)?;
+            }
+
+            y += rows;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct PutImage<'a> {
+    format: ImageFormat,
+    drawable: u32,
+    gc: u32,
+    width: u16,
+    height: u16,
+    dst_x: i16,
+    dst_y: i16,
+    left_pad: u8,
+    depth: u8,
+    data: &'a [u8],
+}
+
+impl<'a> PutImage<'a> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        format: ImageFormat,
+        drawable: u32,
+        gc: u32,
+        width: u16,
+        height: u16,
+        dst_x: i16,
+        dst_y: i16,
+        left_pad: u8,
+        depth: u8,
+        data: &'a [u8],
+    ) -> Self {
+        PutImage {
+            format,
+            drawable,
+            gc,
+            width,
+            height,
+            dst_x,
+            dst_y,
+            left_pad,
+            depth,
+            data,
+        }
+    }
+
+    /// The request without its image data, for `Connection::send_with_data`.
+    pub fn header(&self) -> Vec<u8> {
+        let padding = (4 - (self.data.len() % 4)) % 4;
+        let mut ret = Vec::new();
+
+        ret.write_u8(72).unwrap();
+        ret.write_u8(self.format as u8).unwrap();
+        ret.write_u16::<BigEndian>(request_length(24 + self.data.len() + padding))
+            .unwrap();
+        ret.write_u32::<BigEndian>(self.drawable).unwrap();
+        ret.write_u32::<BigEndian>(self.gc).unwrap();
+        ret.write_u16::<BigEndian>(self.width).unwrap();
+        ret.write_u16::<BigEndian>(self.height).unwrap();
+        ret.write_i16::<BigEndian>(self.dst_x).unwrap();
+        ret.write_i16::<BigEndian>(self.dst_y).unwrap();
+        ret.write_u8(self.left_pad).unwrap();
+        ret.write_u8(self.depth).unwrap();
+        ret.write_u16::<BigEndian>(0).unwrap();
+
+        ret
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let padding = (4 - (self.data.len() % 4)) % 4;
+        let mut ret = self.header();
+
+        ret.write_all(self.data).unwrap();
+        for _ in 0..padding {
+            ret.write_u8(0).unwrap();
+        }
+
+        ret
+    }
+}
+
+pub struct GetImage {
+    format: ImageFormat,
+    drawable: u32,
+    x: i16,
+    y: i16,
+    width: u16,
+    height: u16,
+    plane_mask: u32,
+}
+
+impl GetImage {
+    /// `format` must be `XYPixmap` or `ZPixmap`.
+    pub fn new(
+        format: ImageFormat,
+        drawable: u32,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+        plane_mask: u32,
+    ) -> Self {
+        GetImage {
+            format,
+            drawable,
+            x,
+            y,
+            width,
+            height,
+            plane_mask,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(73).unwrap();
+        ret.write_u8(self.format as u8).unwrap();
+        ret.write_u16::<BigEndian>(5).unwrap();
+        ret.write_u32::<BigEndian>(self.drawable).unwrap();
+        ret.write_i16::<BigEndian>(self.x).unwrap();
+        ret.write_i16::<BigEndian>(self.y).unwrap();
+        ret.write_u16::<BigEndian>(self.width).unwrap();
+        ret.write_u16::<BigEndian>(self.height).unwrap();
+        ret.write_u32::<BigEndian>(self.plane_mask).unwrap();
+
+        ret
+    }
+}
+
+pub struct GetImageReply {
+    pub depth: u8,
+    /// The visual of a window, or 0 (None) for a pixmap.
+    pub visual: u32,
+    pub data: Vec<u8>,
+}
+
+impl GetImageReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[1..]);
+
+        let depth = buf.read_u8()?;
+        buf.set_position(7);
+        let visual = buf.read_u32::<BigEndian>()?;
+
+        Ok(GetImageReply {
+            depth,
+            visual,
+            data: data[32..].to_vec(),
+        })
+    }
+}
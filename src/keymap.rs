@@ -0,0 +1,342 @@
+//! Translation of key events to keysyms, following the rules in the "Keyboards" section of the
+//! core protocol.
+
+use std::io::{Read, Result, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::keysym::{
+    XK_Caps_Lock, XK_KP_Equal, XK_KP_Space, XK_Mode_switch, XK_Num_Lock, XK_Shift_Lock,
+};
+use crate::{Connection, Event};
+
+const NO_SYMBOL: u32 = 0;
+
+const SHIFT_MASK: u16 = 1 << 0;
+const LOCK_MASK: u16 = 1 << 1;
+
+pub struct GetKeyboardMapping {
+    first_keycode: u8,
+    count: u8,
+}
+
+impl GetKeyboardMapping {
+    pub fn new(first_keycode: u8, count: u8) -> Self {
+        GetKeyboardMapping {
+            first_keycode,
+            count,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(101).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(2).unwrap();
+        ret.write_u8(self.first_keycode).unwrap();
+        ret.write_u8(self.count).unwrap();
+        ret.write_u16::<BigEndian>(0).unwrap();
+
+        ret
+    }
+}
+
+pub struct GetKeyboardMappingReply {
+    pub keysyms_per_keycode: u8,
+    /// `keysyms_per_keycode` keysyms for each keycode requested.
+    pub keysyms: Vec<u32>,
+}
+
+impl GetKeyboardMappingReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[1..]);
+
+        let keysyms_per_keycode = buf.read_u8()?;
+        buf.read_u16::<BigEndian>()?;
+        let length = buf.read_u32::<BigEndian>()?;
+        buf.set_position(31);
+        let mut keysyms = Vec::with_capacity(length as usize);
+        for _ in 0..length {
+            keysyms.push(buf.read_u32::<BigEndian>()?);
+        }
+
+        Ok(GetKeyboardMappingReply {
+            keysyms_per_keycode,
+            keysyms,
+        })
+    }
+}
+
+pub struct GetModifierMapping;
+
+impl GetModifierMapping {
+    pub fn new() -> Self {
+        GetModifierMapping
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(119).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(1).unwrap();
+
+        ret
+    }
+}
+
+impl Default for GetModifierMapping {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct GetModifierMappingReply {
+    pub keycodes_per_modifier: u8,
+    /// The keycodes of Shift, Lock, Control and Mod1 to Mod5 in turn, with zeros for unused
+    /// entries.
+    pub keycodes: Vec<u8>,
+}
+
+impl GetModifierMappingReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let keycodes_per_modifier = data[1];
+        let len = keycodes_per_modifier as usize * 8;
+        let keycodes = data.get(32..32 + len).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "modifier mapping reply is too short",
+            )
+        })?;
+
+        Ok(GetModifierMappingReply {
+            keycodes_per_modifier,
+            keycodes: keycodes.to_vec(),
+        })
+    }
+
+    /// The keycodes bound to modifier `index` (0 for Shift through 7 for Mod5).
+    pub fn modifier(&self, index: usize) -> impl Iterator<Item = u8> + '_ {
+        let n = self.keycodes_per_modifier as usize;
+        self.keycodes[index * n..][..n]
+            .iter()
+            .copied()
+            .filter(|&keycode| keycode != 0)
+    }
+}
+
+/// How the Lock modifier is interpreted, from the keysyms bound to it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LockMode {
+    None,
+    CapsLock,
+    ShiftLock,
+}
+
+/// Returns the lowercase and uppercase forms of a keysym, which are equal for keysyms without
+/// case.
+pub fn convert_case(keysym: u32) -> (u32, u32) {
+    match keysym {
+        // Unicode keysyms.
+        0x0100_0000..=0x0110_ffff => {
+            let Some(c) = char::from_u32(keysym - 0x0100_0000) else {
+                return (keysym, keysym);
+            };
+            let mut lower = c.to_lowercase();
+            let mut upper = c.to_uppercase();
+            match (lower.next(), lower.next(), upper.next(), upper.next()) {
+                (Some(lower), None, Some(upper), None) => {
+                    (0x0100_0000 + lower as u32, 0x0100_0000 + upper as u32)
+                }
+                _ => (keysym, keysym),
+            }
+        }
+        // Latin-1 letters.
+        0x41..=0x5a | 0xc0..=0xd6 | 0xd8..=0xde => (keysym + 0x20, keysym),
+        0x61..=0x7a | 0xe0..=0xf6 | 0xf8..=0xfe => (keysym, keysym - 0x20),
+        // Cyrillic.
+        0x6e0..=0x6ff => (keysym - 0x20, keysym),
+        0x6c0..=0x6df => (keysym, keysym + 0x20),
+        // Greek.
+        0x7c1..=0x7d9 => (keysym + 0x20, keysym),
+        0x7e1..=0x7f9 => (keysym, keysym - 0x20),
+        _ => (keysym, keysym),
+    }
+}
+
+fn is_keypad(keysym: u32) -> bool {
+    (XK_KP_Space..=XK_KP_Equal).contains(&keysym) || (0x1100_ff80..=0x1100_ffff).contains(&keysym)
+}
+
+/// The keyboard and modifier mappings, for translating keycodes to keysyms.
+pub struct Keymap {
+    min_keycode: u8,
+    keysyms_per_keycode: usize,
+    keysyms: Vec<u32>,
+    modifiers: GetModifierMappingReply,
+    mode_switch_mask: u16,
+    num_lock_mask: u16,
+    lock_mode: LockMode,
+}
+
+impl Keymap {
+    /// Builds a keymap from the replies to `GetKeyboardMapping`, starting at `min_keycode`, and
+    /// `GetModifierMapping`.
+    pub fn new(
+        min_keycode: u8,
+        keyboard: GetKeyboardMappingReply,
+        modifiers: GetModifierMappingReply,
+    ) -> Self {
+        let mut keymap = Keymap {
+            min_keycode,
+            keysyms_per_keycode: keyboard.keysyms_per_keycode as usize,
+            keysyms: keyboard.keysyms,
+            modifiers,
+            mode_switch_mask: 0,
+            num_lock_mask: 0,
+            lock_mode: LockMode::None,
+        };
+        keymap.update_modifiers();
+        keymap
+    }
+
+    /// Fetches the mappings for every keycode the server supports.
+    pub fn load<S: Read + Write>(conn: &mut Connection<S>) -> Result<Self> {
+        let (min_keycode, keyboard) = Self::get_keyboard_mapping(conn)?;
+        let modifiers = conn.request(&GetModifierMapping::new().as_bytes())?;
+        Ok(Keymap::new(
+            min_keycode,
+            keyboard,
+            GetModifierMappingReply::from_bytes(&modifiers)?,
+        ))
+    }
+
+    fn get_keyboard_mapping<S: Read + Write>(
+        conn: &mut Connection<S>,
+    ) -> Result<(u8, GetKeyboardMappingReply)> {
+        let min_keycode = conn.server_init().min_keycode;
+        let count = conn.server_init().max_keycode - min_keycode + 1;
+        let reply = conn.request(&GetKeyboardMapping::new(min_keycode, count).as_bytes())?;
+        Ok((min_keycode, GetKeyboardMappingReply::from_bytes(&reply)?))
+    }
+
+    /// Refetches the affected mapping if `event` is a `MappingNotify` for the keyboard or the
+    /// modifiers; other events are ignored.
+    pub fn update<S: Read + Write>(
+        &mut self,
+        conn: &mut Connection<S>,
+        event: &Event,
+    ) -> Result<()> {
+        match event {
+            Event::MappingNotify { request: 0, .. } => {
+                let reply = conn.request(&GetModifierMapping::new().as_bytes())?;
+                self.modifiers = GetModifierMappingReply::from_bytes(&reply)?;
+            }
+            Event::MappingNotify { request: 1, .. } => {
+                let (min_keycode, keyboard) = Self::get_keyboard_mapping(conn)?;
+                self.min_keycode = min_keycode;
+                self.keysyms_per_keycode = keyboard.keysyms_per_keycode as usize;
+                self.keysyms = keyboard.keysyms;
+            }
+            _ => return Ok(()),
+        }
+        self.update_modifiers();
+        Ok(())
+    }
+
+    /// Works out which modifiers act as Mode_switch and Num_Lock, and what Lock means.
+    fn update_modifiers(&mut self) {
+        self.mode_switch_mask = 0;
+        self.num_lock_mask = 0;
+        self.lock_mode = LockMode::None;
+
+        for index in 0..8 {
+            let keysyms = self
+                .modifiers
+                .modifier(index)
+                .flat_map(|keycode| self.keysyms_for(keycode).iter().copied())
+                .collect::<Vec<_>>();
+            if index == 1 {
+                if keysyms.contains(&XK_Caps_Lock) {
+                    self.lock_mode = LockMode::CapsLock;
+                } else if keysyms.contains(&XK_Shift_Lock) {
+                    self.lock_mode = LockMode::ShiftLock;
+                }
+            }
+            if keysyms.contains(&XK_Mode_switch) {
+                self.mode_switch_mask |= 1 << index;
+            }
+            if keysyms.contains(&XK_Num_Lock) {
+                self.num_lock_mask |= 1 << index;
+            }
+        }
+    }
+
+    /// All keysyms bound to a keycode, including any trailing NoSymbol entries.
+    pub fn keysyms_for(&self, keycode: u8) -> &[u32] {
+        let n = self.keysyms_per_keycode;
+        let Some(index) = keycode.checked_sub(self.min_keycode) else {
+            return &[];
+        };
+        self.keysyms
+            .get(index as usize * n..)
+            .map_or(&[], |keysyms| &keysyms[..n])
+    }
+
+    pub fn lock_mode(&self) -> LockMode {
+        self.lock_mode
+    }
+
+    /// The modifier bits bound to Num_Lock, or 0 if there are none.
+    pub fn num_lock_mask(&self) -> u16 {
+        self.num_lock_mask
+    }
+
+    /// Translates the keycode and modifier state of a key event to a keysym, or 0 (NoSymbol).
+    pub fn keysym(&self, keycode: u8, state: u16) -> u32 {
+        let mut keysyms = self.keysyms_for(keycode);
+        while let Some((&NO_SYMBOL, rest)) = keysyms.split_last() {
+            keysyms = rest;
+        }
+        // Without a second group, Mode_switch selects the first.
+        let group = if state & self.mode_switch_mask != 0 && keysyms.len() > 2 {
+            &keysyms[2..]
+        } else {
+            keysyms
+        };
+
+        // A group with one keysym is treated as its lowercase and uppercase forms.
+        let first = group.first().copied().unwrap_or(NO_SYMBOL);
+        let second = group.get(1).copied().unwrap_or(NO_SYMBOL);
+        let (first, second) = if second == NO_SYMBOL {
+            let (lower, upper) = convert_case(first);
+            (lower, if lower == upper { first } else { upper })
+        } else {
+            (first, second)
+        };
+
+        let shift = state & SHIFT_MASK != 0;
+        let lock = state & LOCK_MASK != 0;
+        let caps_lock = lock && self.lock_mode == LockMode::CapsLock;
+        let shift_lock = lock && self.lock_mode == LockMode::ShiftLock;
+
+        if state & self.num_lock_mask != 0 && is_keypad(second) {
+            if shift || shift_lock {
+                first
+            } else {
+                second
+            }
+        } else if !shift && !lock {
+            first
+        } else if !shift && caps_lock {
+            convert_case(first).1
+        } else if shift && caps_lock {
+            convert_case(second).1
+        } else if shift || shift_lock {
+            second
+        } else {
+            first
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Keysym constants, names and conversion to and from Unicode.
+//!
+//! Constants keep their C names, such as `XK_Return` and `XF86XK_AudioMute`. Names used by
+//! `from_name` and `name` drop the `XK_` prefix, as in `Return` and `XF86AudioMute`.
+
+#![allow(non_upper_case_globals)]
+
+pub use crate::keysym_table::*;
+use crate::keysym_table::{NAMES, UNICODE, VALUES};
+
+/// The keysym for a Unicode code point outside Latin-1 is this plus the code point.
+const UNICODE_OFFSET: u32 = 0x0100_0000;
+
+/// Looks up a keysym by name, accepting the `U+`-style names `U20AC` and hexadecimal `0x...`
+/// values as `XStringToKeysym` does.
+pub fn from_name(name: &str) -> Option<u32> {
+    if let Ok(i) = NAMES.binary_search_by(|&(n, _)| n.cmp(name)) {
+        return Some(NAMES[i].1);
+    }
+    if let Some(hex) = name.strip_prefix('U') {
+        let c = u32::from_str_radix(hex, 16).ok()?;
+        return match c {
+            0x20..=0x7e | 0xa0..=0xff => Some(c),
+            0x100..=0x10_ffff => Some(UNICODE_OFFSET + c),
+            _ => None,
+        };
+    }
+    if let Some(hex) = name.strip_prefix("0x") {
+        return u32::from_str_radix(hex, 16).ok();
+    }
+    None
+}
+
+/// Returns the name of a keysym, as used in configuration files; Unicode keysyms without a
+/// name of their own are named like `U20AC`.
+pub fn name(keysym: u32) -> Option<String> {
+    if let Ok(i) = VALUES.binary_search_by_key(&keysym, |&(value, _)| value) {
+        return Some(VALUES[i].1.to_string());
+    }
+    match keysym {
+        UNICODE_OFFSET..=0x0110_ffff => Some(format!("U{:04X}", keysym - UNICODE_OFFSET)),
+        _ => None,
+    }
+}
+
+/// Converts a keysym to the character it types, if any.
+///
+/// Besides Latin-1, Unicode keysyms and the legacy character sets, this covers the function
+/// keys that produce control characters (BackSpace, Tab, Return, Escape, Delete...) and the
+/// keypad.
+pub fn to_char(keysym: u32) -> Option<char> {
+    let c = match keysym {
+        0x20..=0x7e | 0xa0..=0xff => keysym,
+        UNICODE_OFFSET..=0x0110_ffff => keysym - UNICODE_OFFSET,
+        XK_BackSpace | XK_Tab | XK_Linefeed | XK_Clear | XK_Return | XK_Escape => keysym & 0x7f,
+        XK_Delete => 0x7f,
+        XK_KP_Space => 0x20,
+        XK_KP_Tab | XK_KP_Enter => keysym & 0x7f,
+        XK_KP_Multiply..=XK_KP_9 | XK_KP_Equal => keysym & 0x7f,
+        _ => {
+            let i = UNICODE
+                .binary_search_by_key(&keysym, |&(keysym, _)| keysym)
+                .ok()?;
+            UNICODE[i].1
+        }
+    };
+    char::from_u32(c)
+}
+
+/// Returns the keysym that types `c`, for key binding configuration: the Latin-1 or legacy
+/// keysym a keyboard layout would produce if there is one, and otherwise the Unicode keysym.
+pub fn from_char(c: char) -> u32 {
+    let code = c as u32;
+    match code {
+        0x20..=0x7e | 0xa0..=0xff => code,
+        0x08 | 0x09 | 0x0a | 0x0b | 0x0d | 0x1b => 0xff00 | code,
+        0x7f => XK_Delete,
+        _ => UNICODE
+            .iter()
+            .find(|&&(_, unicode)| unicode == code)
+            .map_or(UNICODE_OFFSET + code, |&(keysym, _)| keysym),
+    }
+}
@@ -1,8 +1,61 @@
 #[cfg(test)]
 mod tests;
 
+pub mod image;
+pub mod keysym;
+
+mod color;
+mod color_table;
+mod colormap;
+mod compose;
+mod connection;
+mod cursor;
+mod draw;
+mod font;
+mod grab;
+mod keymap;
+mod keysym_table;
+mod pixmap;
+mod pointer;
+mod property;
+mod render;
+mod selection;
+mod split;
+mod text;
+mod window;
+mod xcursor;
+mod xlfd;
+
+pub use colormap::*;
+pub use compose::*;
+pub use connection::{Connection, ReadTimeout};
+pub use cursor::*;
+pub use draw::*;
+pub use font::*;
+pub use grab::*;
+pub use image::{GetImage, GetImageReply, ImageFormat, PutImage};
+pub use keymap::*;
+pub use pixmap::*;
+pub use pointer::*;
+pub use property::*;
+pub use render::*;
+pub use selection::*;
+pub use text::*;
+pub use window::*;
+pub use xcursor::*;
+pub use xlfd::*;
+
 use std::io::{Read, Result, Write};
 
+/// Encodes the length field of a request that is `bytes` long.
+///
+/// Requests too long for the 16-bit field are encoded as 0; `Connection` rewrites the length of
+/// every request it sends, using the BIG-REQUESTS extended length where necessary.
+fn request_length(bytes: usize) -> u16 {
+    use std::convert::TryFrom;
+    u16::try_from(bytes / 4).unwrap_or(0)
+}
+
 pub struct ClientInit<'a> {
     major: u16,
     minor: u16,
@@ -11,6 +64,7 @@ pub struct ClientInit<'a> {
 }
 
 impl<'a> ClientInit<'a> {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         ClientInit {
             major: 11,
@@ -21,6 +75,7 @@ impl<'a> ClientInit<'a> {
     }
 }
 
+#[allow(clippy::from_over_into, clippy::needless_arbitrary_self_type)]
 impl<'a> Into<Vec<u8>> for ClientInit<'a> {
     fn into(self: Self) -> Vec<u8> {
         use byteorder::{BigEndian, WriteBytesExt};
@@ -166,6 +221,17 @@ pub struct Screen {
 }
 
 impl Screen {
+    /// Finds a visual supported by this screen, along with its depth.
+    pub fn find_visual(&self, id: u32) -> Option<(u8, &Visual)> {
+        self.allowed_depths.iter().find_map(|depth| {
+            depth
+                .visuals
+                .iter()
+                .find(|visual| visual.id == id)
+                .map(|visual| (depth.depth, visual))
+        })
+    }
+
     pub fn from_stream<T: Read>(stream: &mut T) -> Result<Self> {
         use byteorder::{BigEndian, ReadBytesExt};
         let root = stream.read_u32::<BigEndian>()?;
@@ -233,9 +299,19 @@ impl Depth {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VisualClass {
+    StaticGray = 0,
+    GrayScale = 1,
+    StaticColor = 2,
+    PseudoColor = 3,
+    TrueColor = 4,
+    DirectColor = 5,
+}
+
 pub struct Visual {
     pub id: u32,
-    pub class: u8, // TODO: enum
+    pub class: VisualClass,
     pub bits_per_rgb_value: u8,
     pub colormap_entries: u16,
     pub red_mask: u32,
@@ -247,7 +323,20 @@ impl Visual {
     pub fn from_stream<T: Read>(stream: &mut T) -> Result<Self> {
         use byteorder::{BigEndian, ReadBytesExt};
         let id = stream.read_u32::<BigEndian>()?;
-        let class = stream.read_u8()?;
+        let class = match stream.read_u8()? {
+            0 => VisualClass::StaticGray,
+            1 => VisualClass::GrayScale,
+            2 => VisualClass::StaticColor,
+            3 => VisualClass::PseudoColor,
+            4 => VisualClass::TrueColor,
+            5 => VisualClass::DirectColor,
+            class => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("unknown visual class {}", class),
+                ))
+            }
+        };
         let bits_per_rgb_value = stream.read_u8()?;
         let colormap_entries = stream.read_u16::<BigEndian>()?;
         let red_mask = stream.read_u32::<BigEndian>()?;
@@ -265,6 +354,80 @@ impl Visual {
             blue_mask,
         })
     }
+
+    /// Computes the pixel for a color with 16-bit channels from the masks of a TrueColor
+    /// visual, or returns `None` for visuals whose pixels must be allocated in a colormap and
+    /// for malformed visuals with an empty channel mask.
+    pub fn rgb_pixel(&self, red: u16, green: u16, blue: u16) -> Option<u32> {
+        fn scale(value: u16, mask: u32) -> u32 {
+            let shift = mask.trailing_zeros();
+            let max = (mask >> shift) as u64;
+            (((value as u64 * max + 32767) / 65535) as u32) << shift
+        }
+
+        let masks = [self.red_mask, self.green_mask, self.blue_mask];
+        if self.class != VisualClass::TrueColor || masks.contains(&0) {
+            return None;
+        }
+        Some(
+            scale(red, self.red_mask) | scale(green, self.green_mask) | scale(blue, self.blue_mask),
+        )
+    }
+}
+
+/// The optional values of a `CreateWindow` or `ChangeWindowAttributes` request.
+#[derive(Clone, Debug, Default)]
+pub struct WindowAttributes {
+    pub background_pixmap: Option<u32>,
+    pub background_pixel: Option<u32>,
+    pub border_pixmap: Option<u32>,
+    pub border_pixel: Option<u32>,
+    pub bit_gravity: Option<u8>,
+    pub win_gravity: Option<u8>,
+    pub backing_store: Option<u8>,
+    pub backing_planes: Option<u32>,
+    pub backing_pixel: Option<u32>,
+    pub override_redirect: Option<bool>,
+    pub save_under: Option<bool>,
+    pub event_mask: Option<u32>,
+    pub do_not_propagate_mask: Option<u32>,
+    pub colormap: Option<u32>,
+    pub cursor: Option<u32>,
+}
+
+impl WindowAttributes {
+    /// Encodes the value-mask and value-list.
+    fn write_to(&self, ret: &mut Vec<u8>) {
+        use byteorder::{BigEndian, WriteBytesExt};
+
+        let values = [
+            self.background_pixmap,
+            self.background_pixel,
+            self.border_pixmap,
+            self.border_pixel,
+            self.bit_gravity.map(u32::from),
+            self.win_gravity.map(u32::from),
+            self.backing_store.map(u32::from),
+            self.backing_planes,
+            self.backing_pixel,
+            self.override_redirect.map(u32::from),
+            self.save_under.map(u32::from),
+            self.event_mask,
+            self.do_not_propagate_mask,
+            self.colormap,
+            self.cursor,
+        ];
+
+        let mask = values
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| value.is_some())
+            .fold(0, |mask, (bit, _)| mask | 1 << bit);
+        ret.write_u32::<BigEndian>(mask).unwrap();
+        for value in values.iter().flatten() {
+            ret.write_u32::<BigEndian>(*value).unwrap();
+        }
+    }
 }
 
 pub struct CreateWindow {
@@ -278,10 +441,11 @@ pub struct CreateWindow {
     class: u16,
     visual: u32,
     depth: u8,
-    // TODO: Option soup for values
+    attributes: WindowAttributes,
 }
 
 impl CreateWindow {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         depth: u8,
         wid: u32,
@@ -305,16 +469,21 @@ impl CreateWindow {
             border_width,
             class,
             visual,
+            attributes: WindowAttributes::default(),
         }
     }
 
+    pub fn with_attributes(self, attributes: WindowAttributes) -> Self {
+        CreateWindow { attributes, ..self }
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         use byteorder::{BigEndian, WriteBytesExt};
         let mut ret = Vec::new();
 
         ret.write_u8(1).unwrap();
         ret.write_u8(self.depth).unwrap();
-        ret.write_u16::<BigEndian>(10).unwrap(); // TODO: length
+        ret.write_u16::<BigEndian>(0).unwrap();
         ret.write_u32::<BigEndian>(self.wid).unwrap();
         ret.write_u32::<BigEndian>(self.parent).unwrap();
         ret.write_u16::<BigEndian>(self.x).unwrap();
@@ -324,13 +493,36 @@ impl CreateWindow {
         ret.write_u16::<BigEndian>(self.border_width).unwrap();
         ret.write_u16::<BigEndian>(self.class).unwrap();
         ret.write_u32::<BigEndian>(self.visual).unwrap();
-        // TODO: actually create value-mask and value-list
-        ret.write_u32::<BigEndian>(0x2 /* background-pixel */ | 0x800 /* event-mask */)
-            .unwrap();
-        ret.write_u32::<BigEndian>(0xcc_ff_cc).unwrap();
-        ret.write_u32::<BigEndian>(0x1 /* KeyPress */ | 0x8000 /* Exposure */)
-            .unwrap();
+        self.attributes.write_to(&mut ret);
+
+        let length = request_length(ret.len());
+        ret[2..4].copy_from_slice(&length.to_be_bytes());
+        ret
+    }
+}
+
+pub struct ChangeWindowAttributes {
+    window: u32,
+    attributes: WindowAttributes,
+}
+
+impl ChangeWindowAttributes {
+    pub fn new(window: u32, attributes: WindowAttributes) -> Self {
+        ChangeWindowAttributes { window, attributes }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        use byteorder::{BigEndian, WriteBytesExt};
+        let mut ret = Vec::new();
+
+        ret.write_u8(2).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(0).unwrap();
+        ret.write_u32::<BigEndian>(self.window).unwrap();
+        self.attributes.write_to(&mut ret);
 
+        let length = request_length(ret.len());
+        ret[2..4].copy_from_slice(&length.to_be_bytes());
         ret
     }
 }
@@ -388,45 +580,63 @@ impl CreateGc {
     }
 }
 
-pub struct PolyFillRectangle {
-    drawable: u32,
+pub struct FreeGc {
     gc: u32,
-    x: i16,
-    y: i16,
-    width: u16,
-    height: u16,
 }
 
-impl PolyFillRectangle {
-    pub fn new(drawable: u32, gc: u32, x: i16, y: i16, width: u16, height: u16) -> Self {
-        PolyFillRectangle {
-            drawable,
-            gc,
-            x,
-            y,
-            width,
-            height,
-        }
+impl FreeGc {
+    pub fn new(gc: u32) -> Self {
+        FreeGc { gc }
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
         use byteorder::{BigEndian, WriteBytesExt};
         let mut ret = Vec::new();
 
-        ret.write_u8(70).unwrap();
+        ret.write_u8(60).unwrap();
         ret.write_u8(0).unwrap();
-        ret.write_u16::<BigEndian>(5).unwrap();
-        ret.write_u32::<BigEndian>(self.drawable).unwrap();
+        ret.write_u16::<BigEndian>(2).unwrap();
         ret.write_u32::<BigEndian>(self.gc).unwrap();
-        ret.write_i16::<BigEndian>(self.x).unwrap();
-        ret.write_i16::<BigEndian>(self.y).unwrap();
-        ret.write_u16::<BigEndian>(self.width).unwrap();
-        ret.write_u16::<BigEndian>(self.height).unwrap();
 
         ret
     }
 }
 
+/// The bits of an event mask, as selected with `WindowAttributes::event_mask` or sent with
+/// `SendEvent`.
+pub mod event_mask {
+    pub const KEY_PRESS: u32 = 1 << 0;
+    pub const KEY_RELEASE: u32 = 1 << 1;
+    pub const BUTTON_PRESS: u32 = 1 << 2;
+    pub const BUTTON_RELEASE: u32 = 1 << 3;
+    pub const ENTER_WINDOW: u32 = 1 << 4;
+    pub const LEAVE_WINDOW: u32 = 1 << 5;
+    pub const POINTER_MOTION: u32 = 1 << 6;
+    pub const POINTER_MOTION_HINT: u32 = 1 << 7;
+    pub const BUTTON1_MOTION: u32 = 1 << 8;
+    pub const BUTTON2_MOTION: u32 = 1 << 9;
+    pub const BUTTON3_MOTION: u32 = 1 << 10;
+    pub const BUTTON4_MOTION: u32 = 1 << 11;
+    pub const BUTTON5_MOTION: u32 = 1 << 12;
+    pub const BUTTON_MOTION: u32 = 1 << 13;
+    pub const KEYMAP_STATE: u32 = 1 << 14;
+    pub const EXPOSURE: u32 = 1 << 15;
+    pub const VISIBILITY_CHANGE: u32 = 1 << 16;
+    pub const STRUCTURE_NOTIFY: u32 = 1 << 17;
+    pub const RESIZE_REDIRECT: u32 = 1 << 18;
+    pub const SUBSTRUCTURE_NOTIFY: u32 = 1 << 19;
+    pub const SUBSTRUCTURE_REDIRECT: u32 = 1 << 20;
+    pub const FOCUS_CHANGE: u32 = 1 << 21;
+    pub const PROPERTY_CHANGE: u32 = 1 << 22;
+    pub const COLORMAP_CHANGE: u32 = 1 << 23;
+    pub const OWNER_GRAB_BUTTON: u32 = 1 << 24;
+
+    /// Every event that is only reported, leaving out the bits that change how the window
+    /// behaves: PointerMotionHint, ResizeRedirect, SubstructureRedirect and OwnerGrabButton.
+    pub const ALL_EVENTS: u32 = ((OWNER_GRAB_BUTTON << 1) - 1)
+        & !(POINTER_MOTION_HINT | RESIZE_REDIRECT | SUBSTRUCTURE_REDIRECT | OWNER_GRAB_BUTTON);
+}
+
 #[derive(Debug)]
 pub enum Event {
     Expose {
@@ -452,16 +662,310 @@ pub enum Event {
         state: u16,
         same_screen: bool,
     },
+    KeyRelease {
+        detail: u8,
+        sequence: u16,
+        time: u32,
+        root: u32,
+        event: u32,
+        child: u32,
+        root_x: i16,
+        root_y: i16,
+        event_x: i16,
+        event_y: i16,
+        state: u16,
+        same_screen: bool,
+    },
+    ButtonPress {
+        /// The button number, 1 to 5.
+        detail: u8,
+        sequence: u16,
+        time: u32,
+        root: u32,
+        event: u32,
+        child: u32,
+        root_x: i16,
+        root_y: i16,
+        event_x: i16,
+        event_y: i16,
+        state: u16,
+        same_screen: bool,
+    },
+    ButtonRelease {
+        detail: u8,
+        sequence: u16,
+        time: u32,
+        root: u32,
+        event: u32,
+        child: u32,
+        root_x: i16,
+        root_y: i16,
+        event_x: i16,
+        event_y: i16,
+        state: u16,
+        same_screen: bool,
+    },
+    MotionNotify {
+        /// 0 for Normal, 1 for Hint.
+        detail: u8,
+        sequence: u16,
+        time: u32,
+        root: u32,
+        event: u32,
+        child: u32,
+        root_x: i16,
+        root_y: i16,
+        event_x: i16,
+        event_y: i16,
+        state: u16,
+        same_screen: bool,
+    },
+    EnterNotify {
+        /// 0 for Ancestor, 1 for Virtual, 2 for Inferior, 3 for Nonlinear, 4 for
+        /// NonlinearVirtual.
+        detail: u8,
+        sequence: u16,
+        time: u32,
+        root: u32,
+        event: u32,
+        child: u32,
+        root_x: i16,
+        root_y: i16,
+        event_x: i16,
+        event_y: i16,
+        state: u16,
+        /// 0 for Normal, 1 for Grab, 2 for Ungrab.
+        mode: u8,
+        same_screen: bool,
+        focus: bool,
+    },
+    LeaveNotify {
+        detail: u8,
+        sequence: u16,
+        time: u32,
+        root: u32,
+        event: u32,
+        child: u32,
+        root_x: i16,
+        root_y: i16,
+        event_x: i16,
+        event_y: i16,
+        state: u16,
+        mode: u8,
+        same_screen: bool,
+        focus: bool,
+    },
+    FocusIn {
+        /// As for `EnterNotify`, plus 5 for Pointer, 6 for PointerRoot and 7 for None.
+        detail: u8,
+        sequence: u16,
+        event: u32,
+        /// 0 for Normal, 1 for Grab, 2 for Ungrab, 3 for WhileGrabbed.
+        mode: u8,
+    },
+    FocusOut {
+        detail: u8,
+        sequence: u16,
+        event: u32,
+        mode: u8,
+    },
+    /// Sent after `EnterNotify` and `FocusIn` to clients that select KeymapState.
+    KeymapNotify {
+        /// A bit for each keycode from 8 to 255, least significant bit first.
+        keys: [u8; 31],
+    },
+    GraphicsExposure {
+        sequence: u16,
+        drawable: u32,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+        minor_opcode: u16,
+        count: u16,
+        major_opcode: u8,
+    },
+    NoExposure {
+        sequence: u16,
+        drawable: u32,
+        minor_opcode: u16,
+        major_opcode: u8,
+    },
+    VisibilityNotify {
+        sequence: u16,
+        window: u32,
+        /// 0 for Unobscured, 1 for PartiallyObscured, 2 for FullyObscured.
+        state: u8,
+    },
+    CreateNotify {
+        sequence: u16,
+        parent: u32,
+        window: u32,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+        border_width: u16,
+        override_redirect: bool,
+    },
+    DestroyNotify {
+        sequence: u16,
+        event: u32,
+        window: u32,
+    },
+    UnmapNotify {
+        sequence: u16,
+        event: u32,
+        window: u32,
+        from_configure: bool,
+    },
+    MapNotify {
+        sequence: u16,
+        event: u32,
+        window: u32,
+        override_redirect: bool,
+    },
+    MapRequest {
+        sequence: u16,
+        parent: u32,
+        window: u32,
+    },
+    ReparentNotify {
+        sequence: u16,
+        event: u32,
+        window: u32,
+        parent: u32,
+        x: i16,
+        y: i16,
+        override_redirect: bool,
+    },
+    ConfigureNotify {
+        sequence: u16,
+        event: u32,
+        window: u32,
+        /// The sibling the window is stacked just above, or 0 (None) if it is at the bottom.
+        above_sibling: u32,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+        border_width: u16,
+        override_redirect: bool,
+    },
+    ConfigureRequest {
+        /// 0 for Above, 1 for Below, 2 for TopIf, 3 for BottomIf, 4 for Opposite.
+        stack_mode: u8,
+        sequence: u16,
+        parent: u32,
+        window: u32,
+        sibling: u32,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+        border_width: u16,
+        /// Which of the fields the client asked to change, as in `ConfigureWindow`.
+        value_mask: u16,
+    },
+    GravityNotify {
+        sequence: u16,
+        event: u32,
+        window: u32,
+        x: i16,
+        y: i16,
+    },
+    ResizeRequest {
+        sequence: u16,
+        window: u32,
+        width: u16,
+        height: u16,
+    },
+    CirculateNotify {
+        sequence: u16,
+        event: u32,
+        window: u32,
+        /// 0 for Top, 1 for Bottom.
+        place: u8,
+    },
+    CirculateRequest {
+        sequence: u16,
+        parent: u32,
+        window: u32,
+        place: u8,
+    },
+    PropertyNotify {
+        sequence: u16,
+        window: u32,
+        atom: u32,
+        time: u32,
+        /// 0 for NewValue, 1 for Deleted.
+        state: u8,
+    },
+    SelectionClear {
+        sequence: u16,
+        time: u32,
+        owner: u32,
+        selection: u32,
+    },
+    SelectionRequest {
+        sequence: u16,
+        time: u32,
+        owner: u32,
+        requestor: u32,
+        selection: u32,
+        target: u32,
+        property: u32,
+    },
+    SelectionNotify {
+        sequence: u16,
+        time: u32,
+        requestor: u32,
+        selection: u32,
+        target: u32,
+        /// None if the conversion failed.
+        property: u32,
+    },
+    ColormapNotify {
+        sequence: u16,
+        window: u32,
+        /// The window's colormap, or 0 (None).
+        colormap: u32,
+        /// True if the window's colormap attribute changed, false if the colormap was installed
+        /// or uninstalled.
+        new: bool,
+        /// 0 for Uninstalled, 1 for Installed.
+        state: u8,
+    },
+    ClientMessage {
+        /// 8, 16 or 32, saying how `data` is to be read.
+        format: u8,
+        sequence: u16,
+        window: u32,
+        message_type: u32,
+        /// With 16- and 32-bit items in the server's (big-endian) byte order.
+        data: [u8; 20],
+    },
+    MappingNotify {
+        sequence: u16,
+        /// 0 for Modifier, 1 for Keyboard, 2 for Pointer.
+        request: u8,
+        first_keycode: u8,
+        count: u8,
+    },
+    Error(XError),
     Unknown(u8, [u8; 31]),
 }
 
 impl Event {
+    /// Decodes an event, including one delivered by `SendEvent`, whose type has the top bit
+    /// set.
     pub fn from_bytes(data: &[u8; 32]) -> Self {
         use byteorder::{BigEndian, ReadBytesExt};
         let mut buf = std::io::Cursor::new(data);
 
         let t = buf.read_u8().unwrap();
-        match t {
+        match t & 0x7f {
+            0 => Event::Error(XError::from_bytes(data)),
             12 => {
                 buf.read_u8().unwrap();
                 let sequence = buf.read_u16::<BigEndian>().unwrap();
@@ -481,7 +985,7 @@ impl Event {
                     count,
                 }
             }
-            2 => {
+            2..=6 => {
                 let detail = buf.read_u8().unwrap();
                 let sequence = buf.read_u16::<BigEndian>().unwrap();
                 let time = buf.read_u32::<BigEndian>().unwrap();
@@ -494,30 +998,603 @@ impl Event {
                 let event_y = buf.read_i16::<BigEndian>().unwrap();
                 let state = buf.read_u16::<BigEndian>().unwrap();
                 let same_screen = buf.read_u8().unwrap() != 0;
-                Event::KeyPress {
-                    detail,
-                    sequence,
-                    time,
-                    root,
-                    event,
-                    child,
-                    root_x,
-                    root_y,
-                    event_x,
-                    event_y,
-                    state,
-                    same_screen,
+                match t & 0x7f {
+                    2 => Event::KeyPress {
+                        detail,
+                        sequence,
+                        time,
+                        root,
+                        event,
+                        child,
+                        root_x,
+                        root_y,
+                        event_x,
+                        event_y,
+                        state,
+                        same_screen,
+                    },
+                    3 => Event::KeyRelease {
+                        detail,
+                        sequence,
+                        time,
+                        root,
+                        event,
+                        child,
+                        root_x,
+                        root_y,
+                        event_x,
+                        event_y,
+                        state,
+                        same_screen,
+                    },
+                    4 => Event::ButtonPress {
+                        detail,
+                        sequence,
+                        time,
+                        root,
+                        event,
+                        child,
+                        root_x,
+                        root_y,
+                        event_x,
+                        event_y,
+                        state,
+                        same_screen,
+                    },
+                    5 => Event::ButtonRelease {
+                        detail,
+                        sequence,
+                        time,
+                        root,
+                        event,
+                        child,
+                        root_x,
+                        root_y,
+                        event_x,
+                        event_y,
+                        state,
+                        same_screen,
+                    },
+                    _ => Event::MotionNotify {
+                        detail,
+                        sequence,
+                        time,
+                        root,
+                        event,
+                        child,
+                        root_x,
+                        root_y,
+                        event_x,
+                        event_y,
+                        state,
+                        same_screen,
+                    },
                 }
             }
-            _ => {
-                let mut rest = [0 as u8; 31];
-                rest.clone_from_slice(&data[1..32]);
-                Event::Unknown(t, rest)
-            }
-        }
-    }
-}
-
+            7 | 8 => {
+                let detail = buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let time = buf.read_u32::<BigEndian>().unwrap();
+                let root = buf.read_u32::<BigEndian>().unwrap();
+                let event = buf.read_u32::<BigEndian>().unwrap();
+                let child = buf.read_u32::<BigEndian>().unwrap();
+                let root_x = buf.read_i16::<BigEndian>().unwrap();
+                let root_y = buf.read_i16::<BigEndian>().unwrap();
+                let event_x = buf.read_i16::<BigEndian>().unwrap();
+                let event_y = buf.read_i16::<BigEndian>().unwrap();
+                let state = buf.read_u16::<BigEndian>().unwrap();
+                let mode = buf.read_u8().unwrap();
+                let flags = buf.read_u8().unwrap();
+                let same_screen = flags & 0x2 != 0;
+                let focus = flags & 0x1 != 0;
+                if t & 0x7f == 7 {
+                    Event::EnterNotify {
+                        detail,
+                        sequence,
+                        time,
+                        root,
+                        event,
+                        child,
+                        root_x,
+                        root_y,
+                        event_x,
+                        event_y,
+                        state,
+                        mode,
+                        same_screen,
+                        focus,
+                    }
+                } else {
+                    Event::LeaveNotify {
+                        detail,
+                        sequence,
+                        time,
+                        root,
+                        event,
+                        child,
+                        root_x,
+                        root_y,
+                        event_x,
+                        event_y,
+                        state,
+                        mode,
+                        same_screen,
+                        focus,
+                    }
+                }
+            }
+            9 | 10 => {
+                let detail = buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let event = buf.read_u32::<BigEndian>().unwrap();
+                let mode = buf.read_u8().unwrap();
+                if t & 0x7f == 9 {
+                    Event::FocusIn {
+                        detail,
+                        sequence,
+                        event,
+                        mode,
+                    }
+                } else {
+                    Event::FocusOut {
+                        detail,
+                        sequence,
+                        event,
+                        mode,
+                    }
+                }
+            }
+            11 => {
+                let mut keys = [0u8; 31];
+                keys.clone_from_slice(&data[1..32]);
+                Event::KeymapNotify { keys }
+            }
+            13 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let drawable = buf.read_u32::<BigEndian>().unwrap();
+                let x = buf.read_u16::<BigEndian>().unwrap();
+                let y = buf.read_u16::<BigEndian>().unwrap();
+                let width = buf.read_u16::<BigEndian>().unwrap();
+                let height = buf.read_u16::<BigEndian>().unwrap();
+                let minor_opcode = buf.read_u16::<BigEndian>().unwrap();
+                let count = buf.read_u16::<BigEndian>().unwrap();
+                let major_opcode = buf.read_u8().unwrap();
+                Event::GraphicsExposure {
+                    sequence,
+                    drawable,
+                    x,
+                    y,
+                    width,
+                    height,
+                    minor_opcode,
+                    count,
+                    major_opcode,
+                }
+            }
+            14 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let drawable = buf.read_u32::<BigEndian>().unwrap();
+                let minor_opcode = buf.read_u16::<BigEndian>().unwrap();
+                let major_opcode = buf.read_u8().unwrap();
+                Event::NoExposure {
+                    sequence,
+                    drawable,
+                    minor_opcode,
+                    major_opcode,
+                }
+            }
+            15 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let window = buf.read_u32::<BigEndian>().unwrap();
+                let state = buf.read_u8().unwrap();
+                Event::VisibilityNotify {
+                    sequence,
+                    window,
+                    state,
+                }
+            }
+            16 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let parent = buf.read_u32::<BigEndian>().unwrap();
+                let window = buf.read_u32::<BigEndian>().unwrap();
+                let x = buf.read_i16::<BigEndian>().unwrap();
+                let y = buf.read_i16::<BigEndian>().unwrap();
+                let width = buf.read_u16::<BigEndian>().unwrap();
+                let height = buf.read_u16::<BigEndian>().unwrap();
+                let border_width = buf.read_u16::<BigEndian>().unwrap();
+                let override_redirect = buf.read_u8().unwrap() != 0;
+                Event::CreateNotify {
+                    sequence,
+                    parent,
+                    window,
+                    x,
+                    y,
+                    width,
+                    height,
+                    border_width,
+                    override_redirect,
+                }
+            }
+            17 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let event = buf.read_u32::<BigEndian>().unwrap();
+                let window = buf.read_u32::<BigEndian>().unwrap();
+                Event::DestroyNotify {
+                    sequence,
+                    event,
+                    window,
+                }
+            }
+            18 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let event = buf.read_u32::<BigEndian>().unwrap();
+                let window = buf.read_u32::<BigEndian>().unwrap();
+                let from_configure = buf.read_u8().unwrap() != 0;
+                Event::UnmapNotify {
+                    sequence,
+                    event,
+                    window,
+                    from_configure,
+                }
+            }
+            19 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let event = buf.read_u32::<BigEndian>().unwrap();
+                let window = buf.read_u32::<BigEndian>().unwrap();
+                let override_redirect = buf.read_u8().unwrap() != 0;
+                Event::MapNotify {
+                    sequence,
+                    event,
+                    window,
+                    override_redirect,
+                }
+            }
+            20 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let parent = buf.read_u32::<BigEndian>().unwrap();
+                let window = buf.read_u32::<BigEndian>().unwrap();
+                Event::MapRequest {
+                    sequence,
+                    parent,
+                    window,
+                }
+            }
+            21 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let event = buf.read_u32::<BigEndian>().unwrap();
+                let window = buf.read_u32::<BigEndian>().unwrap();
+                let parent = buf.read_u32::<BigEndian>().unwrap();
+                let x = buf.read_i16::<BigEndian>().unwrap();
+                let y = buf.read_i16::<BigEndian>().unwrap();
+                let override_redirect = buf.read_u8().unwrap() != 0;
+                Event::ReparentNotify {
+                    sequence,
+                    event,
+                    window,
+                    parent,
+                    x,
+                    y,
+                    override_redirect,
+                }
+            }
+            22 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let event = buf.read_u32::<BigEndian>().unwrap();
+                let window = buf.read_u32::<BigEndian>().unwrap();
+                let above_sibling = buf.read_u32::<BigEndian>().unwrap();
+                let x = buf.read_i16::<BigEndian>().unwrap();
+                let y = buf.read_i16::<BigEndian>().unwrap();
+                let width = buf.read_u16::<BigEndian>().unwrap();
+                let height = buf.read_u16::<BigEndian>().unwrap();
+                let border_width = buf.read_u16::<BigEndian>().unwrap();
+                let override_redirect = buf.read_u8().unwrap() != 0;
+                Event::ConfigureNotify {
+                    sequence,
+                    event,
+                    window,
+                    above_sibling,
+                    x,
+                    y,
+                    width,
+                    height,
+                    border_width,
+                    override_redirect,
+                }
+            }
+            23 => {
+                let stack_mode = buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let parent = buf.read_u32::<BigEndian>().unwrap();
+                let window = buf.read_u32::<BigEndian>().unwrap();
+                let sibling = buf.read_u32::<BigEndian>().unwrap();
+                let x = buf.read_i16::<BigEndian>().unwrap();
+                let y = buf.read_i16::<BigEndian>().unwrap();
+                let width = buf.read_u16::<BigEndian>().unwrap();
+                let height = buf.read_u16::<BigEndian>().unwrap();
+                let border_width = buf.read_u16::<BigEndian>().unwrap();
+                let value_mask = buf.read_u16::<BigEndian>().unwrap();
+                Event::ConfigureRequest {
+                    stack_mode,
+                    sequence,
+                    parent,
+                    window,
+                    sibling,
+                    x,
+                    y,
+                    width,
+                    height,
+                    border_width,
+                    value_mask,
+                }
+            }
+            24 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let event = buf.read_u32::<BigEndian>().unwrap();
+                let window = buf.read_u32::<BigEndian>().unwrap();
+                let x = buf.read_i16::<BigEndian>().unwrap();
+                let y = buf.read_i16::<BigEndian>().unwrap();
+                Event::GravityNotify {
+                    sequence,
+                    event,
+                    window,
+                    x,
+                    y,
+                }
+            }
+            25 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let window = buf.read_u32::<BigEndian>().unwrap();
+                let width = buf.read_u16::<BigEndian>().unwrap();
+                let height = buf.read_u16::<BigEndian>().unwrap();
+                Event::ResizeRequest {
+                    sequence,
+                    window,
+                    width,
+                    height,
+                }
+            }
+            26 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let event = buf.read_u32::<BigEndian>().unwrap();
+                let window = buf.read_u32::<BigEndian>().unwrap();
+                buf.read_u32::<BigEndian>().unwrap();
+                let place = buf.read_u8().unwrap();
+                Event::CirculateNotify {
+                    sequence,
+                    event,
+                    window,
+                    place,
+                }
+            }
+            27 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let parent = buf.read_u32::<BigEndian>().unwrap();
+                let window = buf.read_u32::<BigEndian>().unwrap();
+                buf.read_u32::<BigEndian>().unwrap();
+                let place = buf.read_u8().unwrap();
+                Event::CirculateRequest {
+                    sequence,
+                    parent,
+                    window,
+                    place,
+                }
+            }
+            28 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let window = buf.read_u32::<BigEndian>().unwrap();
+                let atom = buf.read_u32::<BigEndian>().unwrap();
+                let time = buf.read_u32::<BigEndian>().unwrap();
+                let state = buf.read_u8().unwrap();
+                Event::PropertyNotify {
+                    sequence,
+                    window,
+                    atom,
+                    time,
+                    state,
+                }
+            }
+            29 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let time = buf.read_u32::<BigEndian>().unwrap();
+                let owner = buf.read_u32::<BigEndian>().unwrap();
+                let selection = buf.read_u32::<BigEndian>().unwrap();
+                Event::SelectionClear {
+                    sequence,
+                    time,
+                    owner,
+                    selection,
+                }
+            }
+            30 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let time = buf.read_u32::<BigEndian>().unwrap();
+                let owner = buf.read_u32::<BigEndian>().unwrap();
+                let requestor = buf.read_u32::<BigEndian>().unwrap();
+                let selection = buf.read_u32::<BigEndian>().unwrap();
+                let target = buf.read_u32::<BigEndian>().unwrap();
+                let property = buf.read_u32::<BigEndian>().unwrap();
+                Event::SelectionRequest {
+                    sequence,
+                    time,
+                    owner,
+                    requestor,
+                    selection,
+                    target,
+                    property,
+                }
+            }
+            31 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let time = buf.read_u32::<BigEndian>().unwrap();
+                let requestor = buf.read_u32::<BigEndian>().unwrap();
+                let selection = buf.read_u32::<BigEndian>().unwrap();
+                let target = buf.read_u32::<BigEndian>().unwrap();
+                let property = buf.read_u32::<BigEndian>().unwrap();
+                Event::SelectionNotify {
+                    sequence,
+                    time,
+                    requestor,
+                    selection,
+                    target,
+                    property,
+                }
+            }
+            32 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let window = buf.read_u32::<BigEndian>().unwrap();
+                let colormap = buf.read_u32::<BigEndian>().unwrap();
+                let new = buf.read_u8().unwrap() != 0;
+                let state = buf.read_u8().unwrap();
+                Event::ColormapNotify {
+                    sequence,
+                    window,
+                    colormap,
+                    new,
+                    state,
+                }
+            }
+            33 => {
+                let format = buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let window = buf.read_u32::<BigEndian>().unwrap();
+                let message_type = buf.read_u32::<BigEndian>().unwrap();
+                let mut message = [0u8; 20];
+                message.clone_from_slice(&data[12..32]);
+                Event::ClientMessage {
+                    format,
+                    sequence,
+                    window,
+                    message_type,
+                    data: message,
+                }
+            }
+            34 => {
+                buf.read_u8().unwrap();
+                let sequence = buf.read_u16::<BigEndian>().unwrap();
+                let request = buf.read_u8().unwrap();
+                let first_keycode = buf.read_u8().unwrap();
+                let count = buf.read_u8().unwrap();
+                Event::MappingNotify {
+                    sequence,
+                    request,
+                    first_keycode,
+                    count,
+                }
+            }
+            _ => {
+                #[allow(clippy::unnecessary_cast)]
+                let mut rest = [0 as u8; 31];
+                rest.clone_from_slice(&data[1..32]);
+                Event::Unknown(t, rest)
+            }
+        }
+    }
+}
+
+/// Sends an event to a window, as selection owners do to answer a `SelectionRequest`.
+pub struct SendEvent {
+    propagate: bool,
+    destination: u32,
+    event_mask: u32,
+    event: [u8; 32],
+}
+
+impl SendEvent {
+    /// `event` is the event as the server would send it; the sequence number is ignored.
+    pub fn new(propagate: bool, destination: u32, event_mask: u32, event: [u8; 32]) -> Self {
+        SendEvent {
+            propagate,
+            destination,
+            event_mask,
+            event,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        use byteorder::{BigEndian, WriteBytesExt};
+        let mut ret = Vec::new();
+
+        ret.write_u8(25).unwrap();
+        ret.write_u8(self.propagate as u8).unwrap();
+        ret.write_u16::<BigEndian>(11).unwrap();
+        ret.write_u32::<BigEndian>(self.destination).unwrap();
+        ret.write_u32::<BigEndian>(self.event_mask).unwrap();
+        ret.write_all(&self.event).unwrap();
+
+        ret
+    }
+}
+
+/// An error packet sent by the server in response to a failed request.
+#[derive(Debug, Clone)]
+pub struct XError {
+    pub code: u8,
+    pub sequence: u16,
+    pub bad_value: u32,
+    pub minor_opcode: u16,
+    pub major_opcode: u8,
+}
+
+impl XError {
+    /// The code of the error for a request naming a window that does not exist.
+    pub const BAD_WINDOW: u8 = 3;
+
+    pub fn from_bytes(data: &[u8; 32]) -> Self {
+        use byteorder::{BigEndian, ReadBytesExt};
+        let mut buf = std::io::Cursor::new(&data[1..]);
+
+        let code = buf.read_u8().unwrap();
+        let sequence = buf.read_u16::<BigEndian>().unwrap();
+        let bad_value = buf.read_u32::<BigEndian>().unwrap();
+        let minor_opcode = buf.read_u16::<BigEndian>().unwrap();
+        let major_opcode = buf.read_u8().unwrap();
+        XError {
+            code,
+            sequence,
+            bad_value,
+            minor_opcode,
+            major_opcode,
+        }
+    }
+
+    /// The error from the server that `Connection::wait_for_reply` returned as `error`, or
+    /// `None` if `error` is a failure of the connection itself.
+    pub fn from_io_error(error: &std::io::Error) -> Option<&XError> {
+        error.get_ref()?.downcast_ref()
+    }
+}
+
+impl std::fmt::Display for XError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "X error {} on request {}.{} (sequence {}, bad value {:#x})",
+            self.code, self.major_opcode, self.minor_opcode, self.sequence, self.bad_value
+        )
+    }
+}
+
+impl std::error::Error for XError {}
+
 pub struct ChangeWmName {
     window: u32,
     name: String,
@@ -535,7 +1612,7 @@ impl ChangeWmName {
 
         ret.write_u8(18).unwrap(); // ChangeProperty
         ret.write_u8(0).unwrap(); // Replace
-        ret.write_u16::<BigEndian>(6 + (name.len() + padding) as u16 / 4)
+        ret.write_u16::<BigEndian>(request_length(24 + name.len() + padding))
             .unwrap();
         ret.write_u32::<BigEndian>(self.window).unwrap();
         ret.write_u32::<BigEndian>(39).unwrap(); // predefined WM_NAME
@@ -553,3 +1630,137 @@ impl ChangeWmName {
         ret
     }
 }
+
+pub struct QueryExtension {
+    name: String,
+}
+
+impl QueryExtension {
+    pub fn new(name: String) -> Self {
+        QueryExtension { name }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        use byteorder::{BigEndian, WriteBytesExt};
+        let mut ret = Vec::new();
+        let name = self.name.as_bytes();
+        let padding = (4 - (name.len() % 4)) % 4;
+
+        ret.write_u8(98).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(request_length(8 + name.len() + padding))
+            .unwrap();
+        ret.write_u16::<BigEndian>(name.len() as u16).unwrap();
+        ret.write_u16::<BigEndian>(0).unwrap();
+        ret.write_all(name).unwrap();
+        for _ in 0..padding {
+            ret.write_u8(0).unwrap();
+        }
+
+        ret
+    }
+}
+
+pub struct QueryExtensionReply {
+    pub present: bool,
+    pub major_opcode: u8,
+    pub first_event: u8,
+    pub first_error: u8,
+}
+
+impl QueryExtensionReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        use byteorder::ReadBytesExt;
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let present = buf.read_u8()? != 0;
+        let major_opcode = buf.read_u8()?;
+        let first_event = buf.read_u8()?;
+        let first_error = buf.read_u8()?;
+
+        Ok(QueryExtensionReply {
+            present,
+            major_opcode,
+            first_event,
+            first_error,
+        })
+    }
+}
+
+/// Lists the names of the extensions the server supports.
+pub struct ListExtensions;
+
+impl ListExtensions {
+    pub fn new() -> Self {
+        ListExtensions
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        use byteorder::{BigEndian, WriteBytesExt};
+        let mut ret = Vec::new();
+
+        ret.write_u8(99).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(1).unwrap();
+
+        ret
+    }
+}
+
+impl Default for ListExtensions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct ListExtensionsReply {
+    pub names: Vec<String>,
+}
+
+impl ListExtensionsReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let names_count = data[1];
+        Ok(ListExtensionsReply {
+            names: font::read_strings(&data[32..], names_count as usize),
+        })
+    }
+}
+
+/// The BIG-REQUESTS extension's only request, which raises the maximum request length.
+pub struct BigReqEnable {
+    major_opcode: u8,
+}
+
+impl BigReqEnable {
+    pub fn new(major_opcode: u8) -> Self {
+        BigReqEnable { major_opcode }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        use byteorder::{BigEndian, WriteBytesExt};
+        let mut ret = Vec::new();
+
+        ret.write_u8(self.major_opcode).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(1).unwrap();
+
+        ret
+    }
+}
+
+pub struct BigReqEnableReply {
+    pub maximum_request_length: u32,
+}
+
+impl BigReqEnableReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        use byteorder::{BigEndian, ReadBytesExt};
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let maximum_request_length = buf.read_u32::<BigEndian>()?;
+
+        Ok(BigReqEnableReply {
+            maximum_request_length,
+        })
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::{self, Result};
+
+use byteorder::{BigEndian, WriteBytesExt};
+
+use crate::{Screen, ServerInit};
+
+pub struct CreatePixmap {
+    depth: u8,
+    pid: u32,
+    drawable: u32,
+    width: u16,
+    height: u16,
+}
+
+impl CreatePixmap {
+    /// `drawable` only determines the screen the pixmap is created on.
+    pub fn new(depth: u8, pid: u32, drawable: u32, width: u16, height: u16) -> Self {
+        CreatePixmap {
+            depth,
+            pid,
+            drawable,
+            width,
+            height,
+        }
+    }
+
+    /// Checks the request against what the server advertised at connection setup, so that an
+    /// unsupported depth is reported here rather than as an asynchronous `BadValue`.
+    ///
+    /// `screen` must be the screen that `drawable` is on.
+    pub fn validate(&self, server_init: &ServerInit, screen: &Screen) -> Result<()> {
+        if self.width == 0 || self.height == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "pixmap width and height must be nonzero",
+            ));
+        }
+        if !server_init
+            .pixmap_formats
+            .iter()
+            .any(|format| format.depth == self.depth)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("server has no pixmap format for depth {}", self.depth),
+            ));
+        }
+        // Depth 1 is always supported, even if the screen does not list it.
+        if self.depth != 1
+            && !screen
+                .allowed_depths
+                .iter()
+                .any(|depth| depth.depth == self.depth)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("screen does not support depth {}", self.depth),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(53).unwrap();
+        ret.write_u8(self.depth).unwrap();
+        ret.write_u16::<BigEndian>(4).unwrap();
+        ret.write_u32::<BigEndian>(self.pid).unwrap();
+        ret.write_u32::<BigEndian>(self.drawable).unwrap();
+        ret.write_u16::<BigEndian>(self.width).unwrap();
+        ret.write_u16::<BigEndian>(self.height).unwrap();
+
+        ret
+    }
+}
+
+pub struct FreePixmap {
+    pixmap: u32,
+}
+
+impl FreePixmap {
+    pub fn new(pixmap: u32) -> Self {
+        FreePixmap { pixmap }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(54).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(2).unwrap();
+        ret.write_u32::<BigEndian>(self.pixmap).unwrap();
+
+        ret
+    }
+}
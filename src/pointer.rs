@@ -0,0 +1,511 @@
+//! Pointer and input focus requests.
+
+use std::io::{self, Read, Result, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{request_length, Connection};
+
+/// The `focus` of `SetInputFocus` that sends keyboard input to the window under the pointer.
+pub const POINTER_ROOT: u32 = 1;
+
+/// Requests with nothing after the header.
+fn empty_request(opcode: u8) -> Vec<u8> {
+    let mut ret = Vec::new();
+
+    ret.write_u8(opcode).unwrap();
+    ret.write_u8(0).unwrap();
+    ret.write_u16::<BigEndian>(1).unwrap();
+
+    ret
+}
+
+pub struct QueryPointer {
+    window: u32,
+}
+
+impl QueryPointer {
+    pub fn new(window: u32) -> Self {
+        QueryPointer { window }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(38).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(2).unwrap();
+        ret.write_u32::<BigEndian>(self.window).unwrap();
+
+        ret
+    }
+}
+
+#[derive(Debug)]
+pub struct QueryPointerReply {
+    /// False if the pointer is on another screen than `window`, in which case `child` is 0 and
+    /// the window coordinates are 0.
+    pub same_screen: bool,
+    pub root: u32,
+    pub child: u32,
+    pub root_x: i16,
+    pub root_y: i16,
+    pub win_x: i16,
+    pub win_y: i16,
+    /// The state of the modifiers and buttons, as in key and button events.
+    pub mask: u16,
+}
+
+impl QueryPointerReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[1..]);
+
+        let same_screen = buf.read_u8()? != 0;
+        buf.set_position(7);
+        let root = buf.read_u32::<BigEndian>()?;
+        let child = buf.read_u32::<BigEndian>()?;
+        let root_x = buf.read_i16::<BigEndian>()?;
+        let root_y = buf.read_i16::<BigEndian>()?;
+        let win_x = buf.read_i16::<BigEndian>()?;
+        let win_y = buf.read_i16::<BigEndian>()?;
+        let mask = buf.read_u16::<BigEndian>()?;
+
+        Ok(QueryPointerReply {
+            same_screen,
+            root,
+            child,
+            root_x,
+            root_y,
+            win_x,
+            win_y,
+            mask,
+        })
+    }
+}
+
+pub struct GetMotionEvents {
+    window: u32,
+    start: u32,
+    stop: u32,
+}
+
+impl GetMotionEvents {
+    pub fn new(window: u32, start: u32, stop: u32) -> Self {
+        GetMotionEvents {
+            window,
+            start,
+            stop,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(39).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(4).unwrap();
+        ret.write_u32::<BigEndian>(self.window).unwrap();
+        ret.write_u32::<BigEndian>(self.start).unwrap();
+        ret.write_u32::<BigEndian>(self.stop).unwrap();
+
+        ret
+    }
+}
+
+/// A pointer position from the motion history, relative to the requested window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeCoord {
+    pub time: u32,
+    pub x: i16,
+    pub y: i16,
+}
+
+pub struct GetMotionEventsReply {
+    pub events: Vec<TimeCoord>,
+}
+
+impl GetMotionEventsReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let events_count = buf.read_u32::<BigEndian>()?;
+        buf.set_position(24);
+        let mut events = Vec::new();
+        for _ in 0..events_count {
+            let time = buf.read_u32::<BigEndian>()?;
+            let x = buf.read_i16::<BigEndian>()?;
+            let y = buf.read_i16::<BigEndian>()?;
+            events.push(TimeCoord { time, x, y });
+        }
+
+        Ok(GetMotionEventsReply { events })
+    }
+}
+
+/// Fetches the pointer motion history of `window` between two times, without a round trip if
+/// the server keeps no history (a `motion_buffer_size` of 0).
+pub fn get_motion_events<S: Read + Write>(
+    conn: &mut Connection<S>,
+    window: u32,
+    start: u32,
+    stop: u32,
+) -> Result<Vec<TimeCoord>> {
+    if conn.server_init().motion_buffer_size == 0 {
+        return Ok(Vec::new());
+    }
+    let request = GetMotionEvents::new(window, start, stop);
+    Ok(GetMotionEventsReply::from_bytes(&conn.request(&request.as_bytes())?)?.events)
+}
+
+pub struct WarpPointer {
+    src_window: u32,
+    dst_window: u32,
+    src_x: i16,
+    src_y: i16,
+    src_width: u16,
+    src_height: u16,
+    dst_x: i16,
+    dst_y: i16,
+}
+
+impl WarpPointer {
+    /// Moves the pointer to (`dst_x`, `dst_y`) relative to `dst_window`, or by that offset if
+    /// `dst_window` is 0 (None).
+    ///
+    /// If `src_window` is not 0, the pointer only moves if it is within that part of
+    /// `src_window`; a width or height of 0 extends to the window's edge.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        src_window: u32,
+        dst_window: u32,
+        src_x: i16,
+        src_y: i16,
+        src_width: u16,
+        src_height: u16,
+        dst_x: i16,
+        dst_y: i16,
+    ) -> Self {
+        WarpPointer {
+            src_window,
+            dst_window,
+            src_x,
+            src_y,
+            src_width,
+            src_height,
+            dst_x,
+            dst_y,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(41).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(6).unwrap();
+        ret.write_u32::<BigEndian>(self.src_window).unwrap();
+        ret.write_u32::<BigEndian>(self.dst_window).unwrap();
+        ret.write_i16::<BigEndian>(self.src_x).unwrap();
+        ret.write_i16::<BigEndian>(self.src_y).unwrap();
+        ret.write_u16::<BigEndian>(self.src_width).unwrap();
+        ret.write_u16::<BigEndian>(self.src_height).unwrap();
+        ret.write_i16::<BigEndian>(self.dst_x).unwrap();
+        ret.write_i16::<BigEndian>(self.dst_y).unwrap();
+
+        ret
+    }
+}
+
+/// Where the focus goes if the focus window becomes unviewable.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RevertTo {
+    None = 0,
+    PointerRoot = 1,
+    Parent = 2,
+}
+
+pub struct SetInputFocus {
+    revert_to: RevertTo,
+    focus: u32,
+    time: u32,
+}
+
+impl SetInputFocus {
+    /// `focus` may be a window, 0 (None) or `POINTER_ROOT`.
+    pub fn new(revert_to: RevertTo, focus: u32, time: u32) -> Self {
+        SetInputFocus {
+            revert_to,
+            focus,
+            time,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(42).unwrap();
+        ret.write_u8(self.revert_to as u8).unwrap();
+        ret.write_u16::<BigEndian>(3).unwrap();
+        ret.write_u32::<BigEndian>(self.focus).unwrap();
+        ret.write_u32::<BigEndian>(self.time).unwrap();
+
+        ret
+    }
+}
+
+pub struct GetInputFocus;
+
+impl GetInputFocus {
+    pub fn new() -> Self {
+        GetInputFocus
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        empty_request(43)
+    }
+}
+
+impl Default for GetInputFocus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+pub struct GetInputFocusReply {
+    pub revert_to: RevertTo,
+    /// A window, 0 (None) or `POINTER_ROOT`.
+    pub focus: u32,
+}
+
+impl GetInputFocusReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let revert_to = match data[1] {
+            0 => RevertTo::None,
+            1 => RevertTo::PointerRoot,
+            2 => RevertTo::Parent,
+            revert_to => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown revert-to {}", revert_to),
+                ))
+            }
+        };
+        let focus = (&data[8..]).read_u32::<BigEndian>()?;
+
+        Ok(GetInputFocusReply { revert_to, focus })
+    }
+}
+
+pub struct QueryKeymap;
+
+impl QueryKeymap {
+    pub fn new() -> Self {
+        QueryKeymap
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        empty_request(44)
+    }
+}
+
+impl Default for QueryKeymap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct QueryKeymapReply {
+    /// A bit for each keycode, least significant first within each byte.
+    pub keys: [u8; 32],
+}
+
+impl QueryKeymapReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut keys = [0; 32];
+        (&data[8..]).read_exact(&mut keys)?;
+
+        Ok(QueryKeymapReply { keys })
+    }
+
+    pub fn is_pressed(&self, keycode: u8) -> bool {
+        self.keys[keycode as usize / 8] & (1 << (keycode % 8)) != 0
+    }
+
+    /// The keycodes of all keys that are down.
+    pub fn pressed(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..=255).filter(move |&keycode| self.is_pressed(keycode))
+    }
+}
+
+/// The outcome of changing a mapping while keys or buttons may be held down.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MappingStatus {
+    Success = 0,
+    /// Some of the affected buttons or keys were down, and nothing changed.
+    Busy = 1,
+    Failed = 2,
+}
+
+impl MappingStatus {
+    fn from_u8(status: u8) -> Result<Self> {
+        match status {
+            0 => Ok(MappingStatus::Success),
+            1 => Ok(MappingStatus::Busy),
+            2 => Ok(MappingStatus::Failed),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown mapping status {}", status),
+            )),
+        }
+    }
+}
+
+pub struct SetPointerMapping<'a> {
+    map: &'a [u8],
+}
+
+impl<'a> SetPointerMapping<'a> {
+    /// `map[i]` is the logical button for physical button `i + 1`, or 0 to disable it; the
+    /// length must match the one from `GetPointerMapping`.
+    pub fn new(map: &'a [u8]) -> Self {
+        SetPointerMapping { map }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let padding = (4 - (self.map.len() % 4)) % 4;
+        let mut ret = Vec::new();
+
+        ret.write_u8(116).unwrap();
+        ret.write_u8(self.map.len() as u8).unwrap();
+        ret.write_u16::<BigEndian>(request_length(4 + self.map.len() + padding))
+            .unwrap();
+        ret.write_all(self.map).unwrap();
+        ret.write_all(&[0; 3][..padding]).unwrap();
+
+        ret
+    }
+}
+
+pub struct SetPointerMappingReply {
+    pub status: MappingStatus,
+}
+
+impl SetPointerMappingReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Ok(SetPointerMappingReply {
+            status: MappingStatus::from_u8(data[1])?,
+        })
+    }
+}
+
+pub struct GetPointerMapping;
+
+impl GetPointerMapping {
+    pub fn new() -> Self {
+        GetPointerMapping
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        empty_request(117)
+    }
+}
+
+impl Default for GetPointerMapping {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct GetPointerMappingReply {
+    pub map: Vec<u8>,
+}
+
+impl GetPointerMappingReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let len = data[1] as usize;
+        let map = data.get(32..32 + len).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "pointer mapping reply is too short",
+            )
+        })?;
+
+        Ok(GetPointerMappingReply { map: map.to_vec() })
+    }
+}
+
+pub struct ChangePointerControl {
+    acceleration: Option<(i16, i16)>,
+    threshold: Option<i16>,
+}
+
+impl ChangePointerControl {
+    /// `acceleration` is a numerator and denominator, and `threshold` the number of pixels the
+    /// pointer must move at once before acceleration applies. `None` leaves a setting alone, and
+    /// -1 restores its default.
+    pub fn new(acceleration: Option<(i16, i16)>, threshold: Option<i16>) -> Self {
+        ChangePointerControl {
+            acceleration,
+            threshold,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let (numerator, denominator) = self.acceleration.unwrap_or((0, 0));
+        let mut ret = Vec::new();
+
+        ret.write_u8(105).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(3).unwrap();
+        ret.write_i16::<BigEndian>(numerator).unwrap();
+        ret.write_i16::<BigEndian>(denominator).unwrap();
+        ret.write_i16::<BigEndian>(self.threshold.unwrap_or(0))
+            .unwrap();
+        ret.write_u8(self.acceleration.is_some() as u8).unwrap();
+        ret.write_u8(self.threshold.is_some() as u8).unwrap();
+
+        ret
+    }
+}
+
+pub struct GetPointerControl;
+
+impl GetPointerControl {
+    pub fn new() -> Self {
+        GetPointerControl
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        empty_request(106)
+    }
+}
+
+impl Default for GetPointerControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+pub struct GetPointerControlReply {
+    pub acceleration_numerator: u16,
+    pub acceleration_denominator: u16,
+    pub threshold: u16,
+}
+
+impl GetPointerControlReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let acceleration_numerator = buf.read_u16::<BigEndian>()?;
+        let acceleration_denominator = buf.read_u16::<BigEndian>()?;
+        let threshold = buf.read_u16::<BigEndian>()?;
+
+        Ok(GetPointerControlReply {
+            acceleration_numerator,
+            acceleration_denominator,
+            threshold,
+        })
+    }
+}
@@ -0,0 +1,614 @@
+//! Atoms and window properties.
+
+use std::io::{Read, Result, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{request_length, Connection};
+
+/// The atoms every server predefines, which need no `InternAtom` round trip.
+pub mod atom {
+    /// AnyPropertyType in `GetProperty`, and None elsewhere.
+    pub const NONE: u32 = 0;
+    pub const PRIMARY: u32 = 1;
+    pub const SECONDARY: u32 = 2;
+    pub const ARC: u32 = 3;
+    pub const ATOM: u32 = 4;
+    pub const BITMAP: u32 = 5;
+    pub const CARDINAL: u32 = 6;
+    pub const COLORMAP: u32 = 7;
+    pub const CURSOR: u32 = 8;
+    pub const CUT_BUFFER0: u32 = 9;
+    pub const CUT_BUFFER1: u32 = 10;
+    pub const CUT_BUFFER2: u32 = 11;
+    pub const CUT_BUFFER3: u32 = 12;
+    pub const CUT_BUFFER4: u32 = 13;
+    pub const CUT_BUFFER5: u32 = 14;
+    pub const CUT_BUFFER6: u32 = 15;
+    pub const CUT_BUFFER7: u32 = 16;
+    pub const DRAWABLE: u32 = 17;
+    pub const FONT: u32 = 18;
+    pub const INTEGER: u32 = 19;
+    pub const PIXMAP: u32 = 20;
+    pub const POINT: u32 = 21;
+    pub const RECTANGLE: u32 = 22;
+    pub const RESOURCE_MANAGER: u32 = 23;
+    pub const RGB_COLOR_MAP: u32 = 24;
+    pub const RGB_BEST_MAP: u32 = 25;
+    pub const RGB_BLUE_MAP: u32 = 26;
+    pub const RGB_DEFAULT_MAP: u32 = 27;
+    pub const RGB_GRAY_MAP: u32 = 28;
+    pub const RGB_GREEN_MAP: u32 = 29;
+    pub const RGB_RED_MAP: u32 = 30;
+    pub const STRING: u32 = 31;
+    pub const VISUALID: u32 = 32;
+    pub const WINDOW: u32 = 33;
+    pub const WM_COMMAND: u32 = 34;
+    pub const WM_HINTS: u32 = 35;
+    pub const WM_CLIENT_MACHINE: u32 = 36;
+    pub const WM_ICON_NAME: u32 = 37;
+    pub const WM_ICON_SIZE: u32 = 38;
+    pub const WM_NAME: u32 = 39;
+    pub const WM_NORMAL_HINTS: u32 = 40;
+    pub const WM_SIZE_HINTS: u32 = 41;
+    pub const WM_ZOOM_HINTS: u32 = 42;
+    pub const MIN_SPACE: u32 = 43;
+    pub const NORM_SPACE: u32 = 44;
+    pub const MAX_SPACE: u32 = 45;
+    pub const END_SPACE: u32 = 46;
+    pub const SUPERSCRIPT_X: u32 = 47;
+    pub const SUPERSCRIPT_Y: u32 = 48;
+    pub const SUBSCRIPT_X: u32 = 49;
+    pub const SUBSCRIPT_Y: u32 = 50;
+    pub const UNDERLINE_POSITION: u32 = 51;
+    pub const UNDERLINE_THICKNESS: u32 = 52;
+    pub const STRIKEOUT_ASCENT: u32 = 53;
+    pub const STRIKEOUT_DESCENT: u32 = 54;
+    pub const ITALIC_ANGLE: u32 = 55;
+    pub const X_HEIGHT: u32 = 56;
+    pub const QUAD_WIDTH: u32 = 57;
+    pub const WEIGHT: u32 = 58;
+    pub const POINT_SIZE: u32 = 59;
+    pub const RESOLUTION: u32 = 60;
+    pub const COPYRIGHT: u32 = 61;
+    pub const NOTICE: u32 = 62;
+    pub const FONT_NAME: u32 = 63;
+    pub const FAMILY_NAME: u32 = 64;
+    pub const FULL_NAME: u32 = 65;
+    pub const CAP_HEIGHT: u32 = 66;
+    pub const WM_CLASS: u32 = 67;
+    pub const WM_TRANSIENT_FOR: u32 = 68;
+
+    const NAMES: [&str; 68] = [
+        "PRIMARY",
+        "SECONDARY",
+        "ARC",
+        "ATOM",
+        "BITMAP",
+        "CARDINAL",
+        "COLORMAP",
+        "CURSOR",
+        "CUT_BUFFER0",
+        "CUT_BUFFER1",
+        "CUT_BUFFER2",
+        "CUT_BUFFER3",
+        "CUT_BUFFER4",
+        "CUT_BUFFER5",
+        "CUT_BUFFER6",
+        "CUT_BUFFER7",
+        "DRAWABLE",
+        "FONT",
+        "INTEGER",
+        "PIXMAP",
+        "POINT",
+        "RECTANGLE",
+        "RESOURCE_MANAGER",
+        "RGB_COLOR_MAP",
+        "RGB_BEST_MAP",
+        "RGB_BLUE_MAP",
+        "RGB_DEFAULT_MAP",
+        "RGB_GRAY_MAP",
+        "RGB_GREEN_MAP",
+        "RGB_RED_MAP",
+        "STRING",
+        "VISUALID",
+        "WINDOW",
+        "WM_COMMAND",
+        "WM_HINTS",
+        "WM_CLIENT_MACHINE",
+        "WM_ICON_NAME",
+        "WM_ICON_SIZE",
+        "WM_NAME",
+        "WM_NORMAL_HINTS",
+        "WM_SIZE_HINTS",
+        "WM_ZOOM_HINTS",
+        "MIN_SPACE",
+        "NORM_SPACE",
+        "MAX_SPACE",
+        "END_SPACE",
+        "SUPERSCRIPT_X",
+        "SUPERSCRIPT_Y",
+        "SUBSCRIPT_X",
+        "SUBSCRIPT_Y",
+        "UNDERLINE_POSITION",
+        "UNDERLINE_THICKNESS",
+        "STRIKEOUT_ASCENT",
+        "STRIKEOUT_DESCENT",
+        "ITALIC_ANGLE",
+        "X_HEIGHT",
+        "QUAD_WIDTH",
+        "WEIGHT",
+        "POINT_SIZE",
+        "RESOLUTION",
+        "COPYRIGHT",
+        "NOTICE",
+        "FONT_NAME",
+        "FAMILY_NAME",
+        "FULL_NAME",
+        "CAP_HEIGHT",
+        "WM_CLASS",
+        "WM_TRANSIENT_FOR",
+    ];
+
+    /// Returns the name of a predefined atom.
+    pub fn name(atom: u32) -> Option<&'static str> {
+        NAMES.get((atom as usize).checked_sub(1)?).copied()
+    }
+}
+
+pub struct InternAtom {
+    only_if_exists: bool,
+    name: String,
+}
+
+impl InternAtom {
+    /// With `only_if_exists`, the reply holds None rather than creating a new atom.
+    pub fn new(only_if_exists: bool, name: String) -> Self {
+        InternAtom {
+            only_if_exists,
+            name,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+        let name = self.name.as_bytes();
+        let padding = (4 - (name.len() % 4)) % 4;
+
+        ret.write_u8(16).unwrap();
+        ret.write_u8(self.only_if_exists as u8).unwrap();
+        ret.write_u16::<BigEndian>(request_length(8 + name.len() + padding))
+            .unwrap();
+        ret.write_u16::<BigEndian>(name.len() as u16).unwrap();
+        ret.write_u16::<BigEndian>(0).unwrap();
+        ret.write_all(name).unwrap();
+        for _ in 0..padding {
+            ret.write_u8(0).unwrap();
+        }
+
+        ret
+    }
+}
+
+pub struct InternAtomReply {
+    pub atom: u32,
+}
+
+impl InternAtomReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let atom = buf.read_u32::<BigEndian>()?;
+
+        Ok(InternAtomReply { atom })
+    }
+}
+
+/// Interns several atoms with a single round trip, returning them in the order of `names`.
+pub fn intern_atoms<S: Read + Write>(conn: &mut Connection<S>, names: &[&str]) -> Result<Vec<u32>> {
+    let sequences = names
+        .iter()
+        .map(|&name| conn.send_with_reply(&InternAtom::new(false, name.into()).as_bytes()))
+        .collect::<Result<Vec<_>>>()?;
+
+    sequences
+        .into_iter()
+        .map(|sequence| Ok(InternAtomReply::from_bytes(&conn.wait_for_reply(sequence)?)?.atom))
+        .collect()
+}
+
+pub struct GetAtomName {
+    atom: u32,
+}
+
+impl GetAtomName {
+    pub fn new(atom: u32) -> Self {
+        GetAtomName { atom }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(17).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(2).unwrap();
+        ret.write_u32::<BigEndian>(self.atom).unwrap();
+
+        ret
+    }
+}
+
+pub struct GetAtomNameReply {
+    pub name: String,
+}
+
+impl GetAtomNameReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let len = buf.read_u16::<BigEndian>()? as usize;
+        let name = data.get(32..32 + len).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "atom name reply is too short",
+            )
+        })?;
+
+        Ok(GetAtomNameReply {
+            name: String::from_utf8_lossy(name).into_owned(),
+        })
+    }
+}
+
+/// Looks up the names of several atoms with a single round trip, without asking the server
+/// about predefined ones.
+pub fn atom_names<S: Read + Write>(conn: &mut Connection<S>, atoms: &[u32]) -> Result<Vec<String>> {
+    let sequences = atoms
+        .iter()
+        .map(|&atom| match atom::name(atom) {
+            Some(_) => Ok(None),
+            None => conn
+                .send_with_reply(&GetAtomName::new(atom).as_bytes())
+                .map(Some),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    atoms
+        .iter()
+        .zip(sequences)
+        .map(|(&atom, sequence)| match sequence {
+            Some(sequence) => {
+                Ok(GetAtomNameReply::from_bytes(&conn.wait_for_reply(sequence)?)?.name)
+            }
+            None => Ok(atom::name(atom).unwrap().to_string()),
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PropMode {
+    Replace = 0,
+    Prepend = 1,
+    Append = 2,
+}
+
+pub struct ChangeProperty<'a> {
+    mode: PropMode,
+    window: u32,
+    property: u32,
+    property_type: u32,
+    format: u8,
+    data: &'a [u8],
+}
+
+impl<'a> ChangeProperty<'a> {
+    /// `format` is 8, 16 or 32, and 16- and 32-bit items in `data` must already be big-endian,
+    /// as in `GetPropertyReply::value`.
+    pub fn new(
+        mode: PropMode,
+        window: u32,
+        property: u32,
+        property_type: u32,
+        format: u8,
+        data: &'a [u8],
+    ) -> Self {
+        ChangeProperty {
+            mode,
+            window,
+            property,
+            property_type,
+            format,
+            data,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(18).unwrap();
+        ret.write_u8(self.mode as u8).unwrap();
+        ret.write_u16::<BigEndian>(0).unwrap();
+        ret.write_u32::<BigEndian>(self.window).unwrap();
+        ret.write_u32::<BigEndian>(self.property).unwrap();
+        ret.write_u32::<BigEndian>(self.property_type).unwrap();
+        ret.write_u8(self.format).unwrap();
+        ret.write_all(&[0; 3]).unwrap();
+        ret.write_u32::<BigEndian>((self.data.len() * 8 / self.format as usize) as u32)
+            .unwrap();
+        ret.write_all(self.data).unwrap();
+        let padding = (4 - (ret.len() % 4)) % 4;
+        for _ in 0..padding {
+            ret.write_u8(0).unwrap();
+        }
+
+        let length = request_length(ret.len());
+        ret[2..4].copy_from_slice(&length.to_be_bytes());
+        ret
+    }
+}
+
+pub struct DeleteProperty {
+    window: u32,
+    property: u32,
+}
+
+impl DeleteProperty {
+    pub fn new(window: u32, property: u32) -> Self {
+        DeleteProperty { window, property }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(19).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(3).unwrap();
+        ret.write_u32::<BigEndian>(self.window).unwrap();
+        ret.write_u32::<BigEndian>(self.property).unwrap();
+
+        ret
+    }
+}
+
+pub struct GetProperty {
+    delete: bool,
+    window: u32,
+    property: u32,
+    property_type: u32,
+    long_offset: u32,
+    long_length: u32,
+}
+
+impl GetProperty {
+    /// `property_type` may be 0 (AnyPropertyType). The offset and length are in units of four
+    /// bytes.
+    pub fn new(
+        delete: bool,
+        window: u32,
+        property: u32,
+        property_type: u32,
+        long_offset: u32,
+        long_length: u32,
+    ) -> Self {
+        GetProperty {
+            delete,
+            window,
+            property,
+            property_type,
+            long_offset,
+            long_length,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(20).unwrap();
+        ret.write_u8(self.delete as u8).unwrap();
+        ret.write_u16::<BigEndian>(6).unwrap();
+        ret.write_u32::<BigEndian>(self.window).unwrap();
+        ret.write_u32::<BigEndian>(self.property).unwrap();
+        ret.write_u32::<BigEndian>(self.property_type).unwrap();
+        ret.write_u32::<BigEndian>(self.long_offset).unwrap();
+        ret.write_u32::<BigEndian>(self.long_length).unwrap();
+
+        ret
+    }
+}
+
+#[derive(Debug)]
+pub struct GetPropertyReply {
+    /// 8, 16 or 32; or 0 if the property does not exist.
+    pub format: u8,
+    pub property_type: u32,
+    pub bytes_after: u32,
+    /// The raw value, with 16- and 32-bit items still in the server's (big-endian) byte order.
+    pub value: Vec<u8>,
+}
+
+impl GetPropertyReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[1..]);
+
+        let format = buf.read_u8()?;
+        buf.set_position(7);
+        let property_type = buf.read_u32::<BigEndian>()?;
+        let bytes_after = buf.read_u32::<BigEndian>()?;
+        let value_len = buf.read_u32::<BigEndian>()? as usize;
+        let value = data[32..][..value_len * format as usize / 8].to_vec();
+
+        Ok(GetPropertyReply {
+            format,
+            property_type,
+            bytes_after,
+            value,
+        })
+    }
+}
+
+impl GetPropertyReply {
+    /// The value as 32-bit items, such as the atoms of an ATOM property.
+    pub fn u32_values(&self) -> Vec<u32> {
+        self.value
+            .chunks_exact(4)
+            .map(|item| u32::from_be_bytes([item[0], item[1], item[2], item[3]]))
+            .collect()
+    }
+}
+
+/// Reads a whole property however long it is, with as many `GetProperty` requests as that takes.
+///
+/// With `delete`, the property is deleted once the end has been read.
+pub fn read_property<S: Read + Write>(
+    conn: &mut Connection<S>,
+    delete: bool,
+    window: u32,
+    property: u32,
+    property_type: u32,
+) -> Result<GetPropertyReply> {
+    // In units of four bytes.
+    const CHUNK: u32 = 0x10000;
+
+    let mut offset = 0;
+    let mut value = Vec::new();
+    loop {
+        let request = GetProperty::new(delete, window, property, property_type, offset, CHUNK);
+        let reply = GetPropertyReply::from_bytes(&conn.request(&request.as_bytes())?)?;
+        // A property of another type is described but not returned.
+        if reply.value.is_empty() && offset == 0 {
+            return Ok(reply);
+        }
+        value.extend_from_slice(&reply.value);
+        if reply.bytes_after == 0 {
+            return Ok(GetPropertyReply { value, ..reply });
+        }
+        offset += CHUNK;
+    }
+}
+
+pub struct ListProperties {
+    window: u32,
+}
+
+impl ListProperties {
+    pub fn new(window: u32) -> Self {
+        ListProperties { window }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(21).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(2).unwrap();
+        ret.write_u32::<BigEndian>(self.window).unwrap();
+
+        ret
+    }
+}
+
+pub struct ListPropertiesReply {
+    pub atoms: Vec<u32>,
+}
+
+impl ListPropertiesReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let count = buf.read_u16::<BigEndian>()?;
+        buf.set_position(24);
+        let mut atoms = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            atoms.push(buf.read_u32::<BigEndian>()?);
+        }
+
+        Ok(ListPropertiesReply { atoms })
+    }
+}
+
+/// The ICCCM WM_HINTS property. Fields the client did not set are `None`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WmHints {
+    pub input: Option<bool>,
+    /// 0 for WithdrawnState, 1 for NormalState, 3 for IconicState.
+    pub initial_state: Option<u32>,
+    pub icon_pixmap: Option<u32>,
+    pub icon_window: Option<u32>,
+    pub icon_position: Option<(i32, i32)>,
+    pub icon_mask: Option<u32>,
+    pub window_group: Option<u32>,
+    pub urgent: bool,
+}
+
+impl WmHints {
+    /// Decodes a WM_HINTS property, returning `None` if it is not one.
+    pub fn from_property(property: &GetPropertyReply) -> Option<Self> {
+        let values = property.u32_values();
+        if property.format != 32 || values.len() < 8 {
+            return None;
+        }
+        let flags = values[0];
+        let field = |bit: u32, value: u32| (flags & bit != 0).then_some(value);
+
+        Some(WmHints {
+            input: field(1 << 0, values[1]).map(|input| input != 0),
+            initial_state: field(1 << 1, values[2]),
+            icon_pixmap: field(1 << 2, values[3]),
+            icon_window: field(1 << 3, values[4]),
+            icon_position: (flags & 1 << 4 != 0).then_some((values[5] as i32, values[6] as i32)),
+            icon_mask: field(1 << 5, values[7]),
+            window_group: values.get(8).and_then(|&group| field(1 << 6, group)),
+            urgent: flags & 1 << 8 != 0,
+        })
+    }
+}
+
+/// The ICCCM WM_SIZE_HINTS structure, as found in the WM_NORMAL_HINTS property. Fields the
+/// client did not set are `None`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WmSizeHints {
+    /// The position, and whether the user rather than the program chose it.
+    pub position: Option<(i32, i32, bool)>,
+    /// The size, and whether the user rather than the program chose it.
+    pub size: Option<(i32, i32, bool)>,
+    pub min_size: Option<(i32, i32)>,
+    pub max_size: Option<(i32, i32)>,
+    pub resize_inc: Option<(i32, i32)>,
+    /// The minimum and maximum aspect ratios, as numerator and denominator.
+    pub aspect: Option<((i32, i32), (i32, i32))>,
+    pub base_size: Option<(i32, i32)>,
+    pub win_gravity: Option<u32>,
+}
+
+impl WmSizeHints {
+    /// Decodes a WM_SIZE_HINTS property, including the shorter form written by clients older
+    /// than ICCCM version 1, returning `None` if it is not one.
+    pub fn from_property(property: &GetPropertyReply) -> Option<Self> {
+        let values = property.u32_values();
+        if property.format != 32 || values.len() < 15 {
+            return None;
+        }
+        let flags = values[0];
+        let pair = |bit: u32, i: usize| {
+            (flags & bit != 0)
+                .then(|| Some((*values.get(i)? as i32, *values.get(i + 1)? as i32)))
+                .flatten()
+        };
+        let chosen = |user: u32, program: u32, i: usize| {
+            pair(user | program, i).map(|(a, b)| (a, b, flags & user != 0))
+        };
+
+        Some(WmSizeHints {
+            position: chosen(1 << 0, 1 << 2, 1),
+            size: chosen(1 << 1, 1 << 3, 3),
+            min_size: pair(1 << 4, 5),
+            max_size: pair(1 << 5, 7),
+            resize_inc: pair(1 << 6, 9),
+            aspect: pair(1 << 7, 11).zip(pair(1 << 7, 13)),
+            base_size: pair(1 << 8, 15),
+            win_gravity: (flags & 1 << 9 != 0)
+                .then(|| values.get(17).copied())
+                .flatten(),
+        })
+    }
+}
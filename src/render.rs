@@ -0,0 +1,288 @@
+//! The parts of the RENDER extension needed for ARGB cursors.
+//!
+//! Every request takes the major opcode from `QueryExtension`.
+
+use std::io::Result;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::request_length;
+
+fn render_header(major_opcode: u8, minor_opcode: u8, length: u16) -> Vec<u8> {
+    let mut ret = Vec::new();
+
+    ret.write_u8(major_opcode).unwrap();
+    ret.write_u8(minor_opcode).unwrap();
+    ret.write_u16::<BigEndian>(length).unwrap();
+
+    ret
+}
+
+pub struct RenderQueryVersion {
+    major_opcode: u8,
+    client_major_version: u32,
+    client_minor_version: u32,
+}
+
+impl RenderQueryVersion {
+    pub fn new(major_opcode: u8, client_major_version: u32, client_minor_version: u32) -> Self {
+        RenderQueryVersion {
+            major_opcode,
+            client_major_version,
+            client_minor_version,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = render_header(self.major_opcode, 0, 3);
+
+        ret.write_u32::<BigEndian>(self.client_major_version)
+            .unwrap();
+        ret.write_u32::<BigEndian>(self.client_minor_version)
+            .unwrap();
+
+        ret
+    }
+}
+
+pub struct RenderQueryVersionReply {
+    pub major_version: u32,
+    pub minor_version: u32,
+}
+
+impl RenderQueryVersionReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let major_version = buf.read_u32::<BigEndian>()?;
+        let minor_version = buf.read_u32::<BigEndian>()?;
+
+        Ok(RenderQueryVersionReply {
+            major_version,
+            minor_version,
+        })
+    }
+}
+
+pub struct RenderQueryPictFormats {
+    major_opcode: u8,
+}
+
+impl RenderQueryPictFormats {
+    pub fn new(major_opcode: u8) -> Self {
+        RenderQueryPictFormats { major_opcode }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        render_header(self.major_opcode, 1, 1)
+    }
+}
+
+/// The channel layout of a direct picture format.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DirectFormat {
+    pub red_shift: u16,
+    pub red_mask: u16,
+    pub green_shift: u16,
+    pub green_mask: u16,
+    pub blue_shift: u16,
+    pub blue_mask: u16,
+    pub alpha_shift: u16,
+    pub alpha_mask: u16,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PictFormInfo {
+    pub id: u32,
+    /// 0 for Indexed, 1 for Direct.
+    pub kind: u8,
+    pub depth: u8,
+    pub direct: DirectFormat,
+    pub colormap: u32,
+}
+
+impl PictFormInfo {
+    /// Whether this is the standard 32-bit format with 8-bit premultiplied alpha, red, green
+    /// and blue channels from most to least significant, as used by Xcursor images.
+    pub fn is_argb32(&self) -> bool {
+        self.kind == 1
+            && self.depth == 32
+            && self.direct
+                == DirectFormat {
+                    red_shift: 16,
+                    red_mask: 0xff,
+                    green_shift: 8,
+                    green_mask: 0xff,
+                    blue_shift: 0,
+                    blue_mask: 0xff,
+                    alpha_shift: 24,
+                    alpha_mask: 0xff,
+                }
+    }
+}
+
+/// The picture formats from a `RenderQueryPictFormats` reply; the per-screen and subpixel
+/// information that follows them is not parsed.
+pub struct RenderQueryPictFormatsReply {
+    pub formats: Vec<PictFormInfo>,
+}
+
+impl RenderQueryPictFormatsReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let formats_count = buf.read_u32::<BigEndian>()?;
+        buf.set_position(24);
+        let mut formats = Vec::new();
+        for _ in 0..formats_count {
+            let id = buf.read_u32::<BigEndian>()?;
+            let kind = buf.read_u8()?;
+            let depth = buf.read_u8()?;
+            buf.read_u16::<BigEndian>()?;
+            let direct = DirectFormat {
+                red_shift: buf.read_u16::<BigEndian>()?,
+                red_mask: buf.read_u16::<BigEndian>()?,
+                green_shift: buf.read_u16::<BigEndian>()?,
+                green_mask: buf.read_u16::<BigEndian>()?,
+                blue_shift: buf.read_u16::<BigEndian>()?,
+                blue_mask: buf.read_u16::<BigEndian>()?,
+                alpha_shift: buf.read_u16::<BigEndian>()?,
+                alpha_mask: buf.read_u16::<BigEndian>()?,
+            };
+            let colormap = buf.read_u32::<BigEndian>()?;
+            formats.push(PictFormInfo {
+                id,
+                kind,
+                depth,
+                direct,
+                colormap,
+            });
+        }
+
+        Ok(RenderQueryPictFormatsReply { formats })
+    }
+}
+
+pub struct RenderCreatePicture {
+    major_opcode: u8,
+    pid: u32,
+    drawable: u32,
+    format: u32,
+}
+
+impl RenderCreatePicture {
+    /// Creates a picture with default attributes.
+    pub fn new(major_opcode: u8, pid: u32, drawable: u32, format: u32) -> Self {
+        RenderCreatePicture {
+            major_opcode,
+            pid,
+            drawable,
+            format,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = render_header(self.major_opcode, 4, 5);
+
+        ret.write_u32::<BigEndian>(self.pid).unwrap();
+        ret.write_u32::<BigEndian>(self.drawable).unwrap();
+        ret.write_u32::<BigEndian>(self.format).unwrap();
+        ret.write_u32::<BigEndian>(0).unwrap();
+
+        ret
+    }
+}
+
+pub struct RenderFreePicture {
+    major_opcode: u8,
+    picture: u32,
+}
+
+impl RenderFreePicture {
+    pub fn new(major_opcode: u8, picture: u32) -> Self {
+        RenderFreePicture {
+            major_opcode,
+            picture,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = render_header(self.major_opcode, 7, 2);
+
+        ret.write_u32::<BigEndian>(self.picture).unwrap();
+
+        ret
+    }
+}
+
+/// Creates a cursor from an ARGB picture (RENDER 0.5).
+pub struct RenderCreateCursor {
+    major_opcode: u8,
+    cid: u32,
+    source: u32,
+    x: u16,
+    y: u16,
+}
+
+impl RenderCreateCursor {
+    pub fn new(major_opcode: u8, cid: u32, source: u32, x: u16, y: u16) -> Self {
+        RenderCreateCursor {
+            major_opcode,
+            cid,
+            source,
+            x,
+            y,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = render_header(self.major_opcode, 27, 4);
+
+        ret.write_u32::<BigEndian>(self.cid).unwrap();
+        ret.write_u32::<BigEndian>(self.source).unwrap();
+        ret.write_u16::<BigEndian>(self.x).unwrap();
+        ret.write_u16::<BigEndian>(self.y).unwrap();
+
+        ret
+    }
+}
+
+/// One frame of an animated cursor: a cursor shown for `delay` milliseconds.
+#[derive(Clone, Copy, Debug)]
+pub struct AnimCursorElt {
+    pub cursor: u32,
+    pub delay: u32,
+}
+
+/// Creates a cursor that cycles through other cursors (RENDER 0.8).
+pub struct RenderCreateAnimCursor<'a> {
+    major_opcode: u8,
+    cid: u32,
+    frames: &'a [AnimCursorElt],
+}
+
+impl<'a> RenderCreateAnimCursor<'a> {
+    pub fn new(major_opcode: u8, cid: u32, frames: &'a [AnimCursorElt]) -> Self {
+        RenderCreateAnimCursor {
+            major_opcode,
+            cid,
+            frames,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = render_header(
+            self.major_opcode,
+            31,
+            request_length(8 + self.frames.len() * 8),
+        );
+
+        ret.write_u32::<BigEndian>(self.cid).unwrap();
+        for frame in self.frames {
+            ret.write_u32::<BigEndian>(frame.cursor).unwrap();
+            ret.write_u32::<BigEndian>(frame.delay).unwrap();
+        }
+
+        ret
+    }
+}
@@ -0,0 +1,781 @@
+//! Selections, and owning and converting them as the ICCCM describes for cut and paste.
+
+use std::io::{self, Read, Result, Write};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{
+    atom, event_mask, intern_atoms, read_property, ChangeProperty, ChangeWindowAttributes,
+    Connection, CreateWindow, Event, GetPropertyReply, PropMode, ReadTimeout, SendEvent,
+    ServerInit, WindowAttributes, XError, CURRENT_TIME,
+};
+
+/// How long either side of a transfer waits for the other before giving up.
+pub const SELECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct SetSelectionOwner {
+    owner: u32,
+    selection: u32,
+    time: u32,
+}
+
+impl SetSelectionOwner {
+    /// An `owner` of None gives up the selection.
+    pub fn new(owner: u32, selection: u32, time: u32) -> Self {
+        SetSelectionOwner {
+            owner,
+            selection,
+            time,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(22).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(4).unwrap();
+        ret.write_u32::<BigEndian>(self.owner).unwrap();
+        ret.write_u32::<BigEndian>(self.selection).unwrap();
+        ret.write_u32::<BigEndian>(self.time).unwrap();
+
+        ret
+    }
+}
+
+pub struct GetSelectionOwner {
+    selection: u32,
+}
+
+impl GetSelectionOwner {
+    pub fn new(selection: u32) -> Self {
+        GetSelectionOwner { selection }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(23).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(2).unwrap();
+        ret.write_u32::<BigEndian>(self.selection).unwrap();
+
+        ret
+    }
+}
+
+pub struct GetSelectionOwnerReply {
+    /// None if the selection has no owner.
+    pub owner: u32,
+}
+
+impl GetSelectionOwnerReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let owner = buf.read_u32::<BigEndian>()?;
+
+        Ok(GetSelectionOwnerReply { owner })
+    }
+}
+
+pub struct ConvertSelection {
+    requestor: u32,
+    selection: u32,
+    target: u32,
+    property: u32,
+    time: u32,
+}
+
+impl ConvertSelection {
+    pub fn new(requestor: u32, selection: u32, target: u32, property: u32, time: u32) -> Self {
+        ConvertSelection {
+            requestor,
+            selection,
+            target,
+            property,
+            time,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(24).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(6).unwrap();
+        ret.write_u32::<BigEndian>(self.requestor).unwrap();
+        ret.write_u32::<BigEndian>(self.selection).unwrap();
+        ret.write_u32::<BigEndian>(self.target).unwrap();
+        ret.write_u32::<BigEndian>(self.property).unwrap();
+        ret.write_u32::<BigEndian>(self.time).unwrap();
+
+        ret
+    }
+}
+
+/// The atoms used in selection transfers that the server does not predefine.
+#[derive(Clone, Copy, Debug)]
+pub struct SelectionAtoms {
+    pub clipboard: u32,
+    pub targets: u32,
+    pub multiple: u32,
+    pub timestamp: u32,
+    pub text: u32,
+    pub utf8_string: u32,
+    pub atom_pair: u32,
+    pub incr: u32,
+    /// The property on our own window that converted selections are stored in.
+    pub property: u32,
+}
+
+impl SelectionAtoms {
+    pub fn intern<S: Read + Write>(conn: &mut Connection<S>) -> Result<Self> {
+        let atoms = intern_atoms(
+            conn,
+            &[
+                "CLIPBOARD",
+                "TARGETS",
+                "MULTIPLE",
+                "TIMESTAMP",
+                "TEXT",
+                "UTF8_STRING",
+                "ATOM_PAIR",
+                "INCR",
+                "X11_CLIENT_SELECTION",
+            ],
+        )?;
+
+        Ok(SelectionAtoms {
+            clipboard: atoms[0],
+            targets: atoms[1],
+            multiple: atoms[2],
+            timestamp: atoms[3],
+            text: atoms[4],
+            utf8_string: atoms[5],
+            atom_pair: atoms[6],
+            incr: atoms[7],
+            property: atoms[8],
+        })
+    }
+}
+
+/// Creates an unmapped window on the root of `screen` to own selections and receive converted
+/// ones, selecting `PropertyNotify` events as `server_time` needs.
+pub fn selection_window<S: Read + Write>(conn: &mut Connection<S>, screen: usize) -> Result<u32> {
+    let root = conn
+        .server_init()
+        .roots
+        .get(screen)
+        .ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("no screen {}", screen))
+        })?
+        .root;
+    let window = conn.generate_id()?;
+
+    let create_window = CreateWindow::new(
+        0, window, root, 0, 0, 1, 1, 0, 2, // InputOnly
+        0, // CopyFromParent
+    )
+    .with_attributes(WindowAttributes {
+        event_mask: Some(event_mask::PROPERTY_CHANGE),
+        ..Default::default()
+    });
+    conn.send(&create_window.as_bytes())?;
+
+    Ok(window)
+}
+
+/// Gets the current server time by appending nothing to `property` on `window` and waiting for
+/// the resulting `PropertyNotify`. The window must select `PropertyChange` events.
+pub fn server_time<S: Read + Write>(
+    conn: &mut Connection<S>,
+    window: u32,
+    property: u32,
+) -> Result<u32> {
+    let append = ChangeProperty::new(PropMode::Append, window, property, atom::STRING, 8, &[]);
+    conn.send(&append.as_bytes())?;
+
+    let is_ours = |event: &Event| match *event {
+        Event::PropertyNotify {
+            window: w, atom, ..
+        } => w == window && atom == property,
+        _ => false,
+    };
+    match conn.wait_for_event(is_ours)? {
+        Event::PropertyNotify { time, .. } => Ok(time),
+        _ => unreachable!(),
+    }
+}
+
+/// Whether server time `a` is earlier than `b`, allowing for the clock wrapping around.
+fn is_before(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+fn u32_bytes(values: &[u32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_be_bytes())
+        .collect()
+}
+
+/// Sends the `SelectionNotify` that answers a `SelectionRequest`, with a `property` of None
+/// if the conversion failed.
+fn notify<S: Read + Write>(
+    conn: &mut Connection<S>,
+    time: u32,
+    requestor: u32,
+    selection: u32,
+    target: u32,
+    property: u32,
+) -> Result<()> {
+    let mut event = Vec::with_capacity(32);
+    event.write_u8(31).unwrap();
+    event.write_u8(0).unwrap();
+    event.write_u16::<BigEndian>(0).unwrap();
+    for value in [time, requestor, selection, target, property] {
+        event.write_u32::<BigEndian>(value).unwrap();
+    }
+    event.resize(32, 0);
+
+    let mut bytes = [0; 32];
+    bytes.copy_from_slice(&event);
+    conn.send(&SendEvent::new(false, requestor, 0, bytes).as_bytes())?;
+    Ok(())
+}
+
+/// The largest value the owner stores in one piece; anything longer is sent with the INCR
+/// protocol, in chunks of this size.
+fn incr_chunk_size(server_init: &ServerInit) -> usize {
+    // Leave room for the ChangeProperty header.
+    std::cmp::max(server_init.maximum_request_length as usize * 4, 1024) - 24
+}
+
+/// Data offered for one target, in format 8.
+struct Conversion {
+    target: u32,
+    property_type: u32,
+    data: Rc<[u8]>,
+}
+
+/// An INCR transfer in progress: each time the requestor deletes the property, the owner
+/// stores the next chunk, and finally an empty value.
+struct IncrTransfer {
+    requestor: u32,
+    property: u32,
+    property_type: u32,
+    data: Rc<[u8]>,
+    /// How much of `data` has been stored so far.
+    offset: usize,
+    last_activity: Instant,
+}
+
+/// Owns selections such as PRIMARY and CLIPBOARD and answers requests to convert them.
+///
+/// Besides the targets given by `set_text` and `set_data`, every owner supports TARGETS,
+/// MULTIPLE and TIMESTAMP. Values too large for one request are sent incrementally.
+///
+/// Events must be passed to `handle_event` for as long as any selection is owned or
+/// `is_transferring` is true, and `expire_transfers` called when none arrive for a while.
+pub struct SelectionOwner {
+    window: u32,
+    atoms: SelectionAtoms,
+    conversions: Vec<Conversion>,
+    /// Each selection owned, with the time it was acquired.
+    owned: Vec<(u32, u32)>,
+    transfers: Vec<IncrTransfer>,
+    timeout: Duration,
+}
+
+impl SelectionOwner {
+    /// `window` is usually one made by `selection_window`, and must select `PropertyChange`
+    /// events.
+    pub fn new(window: u32, atoms: SelectionAtoms) -> Self {
+        SelectionOwner {
+            window,
+            atoms,
+            conversions: Vec::new(),
+            owned: Vec::new(),
+            transfers: Vec::new(),
+            timeout: SELECTION_TIMEOUT,
+        }
+    }
+
+    pub fn window(&self) -> u32 {
+        self.window
+    }
+
+    /// Sets how long an incremental transfer may wait for the requestor before
+    /// `expire_transfers` drops it, `SELECTION_TIMEOUT` by default.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Offers `text` as UTF8_STRING, TEXT and STRING (Latin-1, with `?` for other characters),
+    /// replacing anything offered before.
+    pub fn set_text(&mut self, text: &str) {
+        let latin1 = text
+            .chars()
+            .map(|c| if (c as u32) < 0x100 { c as u8 } else { b'?' })
+            .collect::<Vec<_>>();
+        let utf8_string = self.atoms.utf8_string;
+
+        self.conversions = vec![
+            Conversion {
+                target: utf8_string,
+                property_type: utf8_string,
+                data: text.as_bytes().into(),
+            },
+            Conversion {
+                target: self.atoms.text,
+                property_type: utf8_string,
+                data: text.as_bytes().into(),
+            },
+            Conversion {
+                target: atom::STRING,
+                property_type: atom::STRING,
+                data: latin1.into(),
+            },
+        ];
+    }
+
+    /// Offers `data` as `target`, such as the atom `image/png`, replacing what was offered
+    /// for it before.
+    pub fn set_data(&mut self, target: u32, data: Vec<u8>) {
+        self.conversions
+            .retain(|conversion| conversion.target != target);
+        self.conversions.push(Conversion {
+            target,
+            property_type: target,
+            data: data.into(),
+        });
+    }
+
+    /// Takes ownership of `selection`, returning false if another client took it at the same
+    /// time.
+    pub fn acquire<S: Read + Write>(
+        &mut self,
+        conn: &mut Connection<S>,
+        selection: u32,
+    ) -> Result<bool> {
+        let time = server_time(conn, self.window, self.atoms.property)?;
+        conn.send(&SetSelectionOwner::new(self.window, selection, time).as_bytes())?;
+        let reply = conn.request(&GetSelectionOwner::new(selection).as_bytes())?;
+        if GetSelectionOwnerReply::from_bytes(&reply)?.owner != self.window {
+            return Ok(false);
+        }
+
+        self.owned.retain(|&(owned, _)| owned != selection);
+        self.owned.push((selection, time));
+        Ok(true)
+    }
+
+    /// Gives up `selection` if we own it.
+    pub fn release<S: Read + Write>(
+        &mut self,
+        conn: &mut Connection<S>,
+        selection: u32,
+    ) -> Result<()> {
+        if let Some(i) = self.owned.iter().position(|&(owned, _)| owned == selection) {
+            let (_, time) = self.owned.remove(i);
+            conn.send(&SetSelectionOwner::new(atom::NONE, selection, time).as_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn owns(&self, selection: u32) -> bool {
+        self.owned.iter().any(|&(owned, _)| owned == selection)
+    }
+
+    /// Whether any selection is still owned; false once every one has been cleared by another
+    /// client taking it.
+    pub fn is_owner(&self) -> bool {
+        !self.owned.is_empty()
+    }
+
+    /// Whether incremental transfers are still in progress. They carry on after the selection
+    /// is lost, since the data was promised before then.
+    pub fn is_transferring(&self) -> bool {
+        !self.transfers.is_empty()
+    }
+
+    /// Abandons incremental transfers whose requestor has not taken a chunk within the timeout
+    /// set by `set_timeout`.
+    pub fn expire_transfers<S: Read + Write>(&mut self, conn: &mut Connection<S>) -> Result<()> {
+        while let Some(i) = self
+            .transfers
+            .iter()
+            .position(|transfer| transfer.last_activity.elapsed() >= self.timeout)
+        {
+            self.finish_transfer(conn, i)?;
+        }
+        Ok(())
+    }
+
+    /// Answers a `SelectionRequest`, notes a `SelectionClear` for our window, or continues an
+    /// incremental transfer on `PropertyNotify`, returning whether `event` was one of them.
+    pub fn handle_event<S: Read + Write>(
+        &mut self,
+        conn: &mut Connection<S>,
+        event: &Event,
+    ) -> Result<bool> {
+        self.expire_transfers(conn)?;
+
+        match *event {
+            Event::SelectionRequest {
+                time,
+                owner,
+                requestor,
+                selection,
+                target,
+                property,
+                ..
+            } if owner == self.window => {
+                // Obsolete clients leave the property for the owner to choose.
+                let property = if property == atom::NONE {
+                    target
+                } else {
+                    property
+                };
+                let acquired = self
+                    .owned
+                    .iter()
+                    .find(|&&(owned, _)| owned == selection)
+                    .map(|&(_, acquired)| acquired);
+
+                let converted = match acquired {
+                    Some(acquired) if time == CURRENT_TIME || !is_before(time, acquired) => {
+                        if target == self.atoms.multiple {
+                            self.convert_multiple(conn, requestor, property, acquired)?
+                        } else {
+                            self.convert(conn, requestor, target, property, acquired)?
+                        }
+                    }
+                    _ => false,
+                };
+                let property = if converted { property } else { atom::NONE };
+                notify(conn, time, requestor, selection, target, property)?;
+                Ok(true)
+            }
+            Event::SelectionClear {
+                owner, selection, ..
+            } if owner == self.window => {
+                self.owned.retain(|&(owned, _)| owned != selection);
+                Ok(true)
+            }
+            // The requestor deleted the property, asking for the next chunk.
+            Event::PropertyNotify {
+                window,
+                atom,
+                state: 1,
+                ..
+            } => match self
+                .transfers
+                .iter()
+                .position(|transfer| transfer.requestor == window && transfer.property == atom)
+            {
+                Some(i) => {
+                    self.continue_transfer(conn, i)?;
+                    Ok(true)
+                }
+                None => Ok(false),
+            },
+            _ => Ok(false),
+        }
+    }
+
+    /// Selects or deselects `PropertyNotify` events on a requestor's window; our own window
+    /// always selects them.
+    fn select_property_events<S: Read + Write>(
+        &self,
+        conn: &mut Connection<S>,
+        window: u32,
+        select: bool,
+    ) -> Result<()> {
+        if window == self.window {
+            return Ok(());
+        }
+        let attributes = WindowAttributes {
+            event_mask: Some(if select {
+                event_mask::PROPERTY_CHANGE
+            } else {
+                0
+            }),
+            ..Default::default()
+        };
+        conn.send(&ChangeWindowAttributes::new(window, attributes).as_bytes())?;
+        Ok(())
+    }
+
+    /// Starts an incremental transfer by storing an INCR property holding the size.
+    fn start_transfer<S: Read + Write>(
+        &mut self,
+        conn: &mut Connection<S>,
+        transfer: IncrTransfer,
+    ) -> Result<()> {
+        self.select_property_events(conn, transfer.requestor, true)?;
+        let size = (std::cmp::min(transfer.data.len(), u32::MAX as usize) as u32).to_be_bytes();
+        let change = ChangeProperty::new(
+            PropMode::Replace,
+            transfer.requestor,
+            transfer.property,
+            self.atoms.incr,
+            32,
+            &size,
+        );
+        conn.send(&change.as_bytes())?;
+
+        self.transfers.retain(|other| {
+            other.requestor != transfer.requestor || other.property != transfer.property
+        });
+        self.transfers.push(transfer);
+        Ok(())
+    }
+
+    /// Stores the next chunk of transfer `i`, or the empty value that ends it.
+    fn continue_transfer<S: Read + Write>(
+        &mut self,
+        conn: &mut Connection<S>,
+        i: usize,
+    ) -> Result<()> {
+        let chunk_size = incr_chunk_size(conn.server_init());
+        let transfer = &mut self.transfers[i];
+        let end = std::cmp::min(transfer.offset + chunk_size, transfer.data.len());
+        let change = ChangeProperty::new(
+            PropMode::Replace,
+            transfer.requestor,
+            transfer.property,
+            transfer.property_type,
+            8,
+            &transfer.data[transfer.offset..end],
+        );
+        conn.send(&change.as_bytes())?;
+
+        if transfer.offset == transfer.data.len() {
+            self.finish_transfer(conn, i)
+        } else {
+            transfer.offset = end;
+            transfer.last_activity = Instant::now();
+            Ok(())
+        }
+    }
+
+    fn finish_transfer<S: Read + Write>(
+        &mut self,
+        conn: &mut Connection<S>,
+        i: usize,
+    ) -> Result<()> {
+        let transfer = self.transfers.remove(i);
+        let requestor = transfer.requestor;
+        if self
+            .transfers
+            .iter()
+            .all(|other| other.requestor != requestor)
+        {
+            self.select_property_events(conn, requestor, false)?;
+        }
+        Ok(())
+    }
+
+    /// Stores the selection as `target` in `property` on `requestor`, returning false if the
+    /// target is not supported.
+    fn convert<S: Read + Write>(
+        &mut self,
+        conn: &mut Connection<S>,
+        requestor: u32,
+        target: u32,
+        property: u32,
+        acquired: u32,
+    ) -> Result<bool> {
+        let atoms = &self.atoms;
+        let generated;
+        let (property_type, format, data) = if target == atoms.targets {
+            let mut targets = vec![atoms.targets, atoms.multiple, atoms.timestamp];
+            targets.extend(self.conversions.iter().map(|conversion| conversion.target));
+            generated = u32_bytes(&targets);
+            (atom::ATOM, 32, &generated[..])
+        } else if target == atoms.timestamp {
+            generated = u32_bytes(&[acquired]);
+            (atom::INTEGER, 32, &generated[..])
+        } else {
+            let Some(conversion) = self
+                .conversions
+                .iter()
+                .find(|conversion| conversion.target == target)
+            else {
+                return Ok(false);
+            };
+            if conversion.data.len() > incr_chunk_size(conn.server_init()) {
+                let transfer = IncrTransfer {
+                    requestor,
+                    property,
+                    property_type: conversion.property_type,
+                    data: conversion.data.clone(),
+                    offset: 0,
+                    last_activity: Instant::now(),
+                };
+                self.start_transfer(conn, transfer)?;
+                return Ok(true);
+            }
+            (conversion.property_type, 8, &conversion.data[..])
+        };
+
+        let change = ChangeProperty::new(
+            PropMode::Replace,
+            requestor,
+            property,
+            property_type,
+            format,
+            data,
+        );
+        conn.send(&change.as_bytes())?;
+        Ok(true)
+    }
+
+    /// Converts each target of a MULTIPLE request, whose property lists pairs of targets and
+    /// properties. The property of each pair that fails is replaced with None.
+    fn convert_multiple<S: Read + Write>(
+        &mut self,
+        conn: &mut Connection<S>,
+        requestor: u32,
+        property: u32,
+        acquired: u32,
+    ) -> Result<bool> {
+        // The requestor may have gone away, or named a property that is not an atom.
+        let pairs = match read_property(conn, false, requestor, property, self.atoms.atom_pair) {
+            Ok(pairs) => pairs,
+            Err(e) if XError::from_io_error(&e).is_some() => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        if pairs.property_type != self.atoms.atom_pair || pairs.format != 32 {
+            return Ok(false);
+        }
+
+        let mut pairs = pairs.u32_values();
+        for pair in pairs.chunks_exact_mut(2) {
+            let converted = pair[0] != self.atoms.multiple
+                && self.convert(conn, requestor, pair[0], pair[1], acquired)?;
+            if !converted {
+                pair[1] = atom::NONE;
+            }
+        }
+
+        let pairs = u32_bytes(&pairs);
+        let change = ChangeProperty::new(
+            PropMode::Replace,
+            requestor,
+            property,
+            self.atoms.atom_pair,
+            32,
+            &pairs,
+        );
+        conn.send(&change.as_bytes())?;
+        Ok(true)
+    }
+}
+
+/// Asks the owner of `selection` to convert it to `target`, and reads the result from
+/// `atoms.property` on `window`, deleting it afterwards.
+///
+/// `window` must select `PropertyChange` events, as one made by `selection_window` does, for
+/// values the owner sends incrementally. Returns `None` if the selection has no owner or the
+/// owner refused the conversion, and a `TimedOut` error if the owner stops responding for
+/// `SELECTION_TIMEOUT`. `time` should be the time of the event that triggered the paste.
+pub fn convert_selection<S: Read + Write + ReadTimeout>(
+    conn: &mut Connection<S>,
+    window: u32,
+    atoms: &SelectionAtoms,
+    selection: u32,
+    target: u32,
+    time: u32,
+) -> Result<Option<GetPropertyReply>> {
+    let timed_out = || io::Error::new(io::ErrorKind::TimedOut, "selection owner did not respond");
+
+    let convert = ConvertSelection::new(window, selection, target, atoms.property, time);
+    conn.send(&convert.as_bytes())?;
+
+    let is_ours = |event: &Event| match *event {
+        Event::SelectionNotify {
+            requestor,
+            selection: s,
+            ..
+        } => requestor == window && s == selection,
+        _ => false,
+    };
+    let property = match conn.wait_for_event_timeout(SELECTION_TIMEOUT, is_ours)? {
+        Some(Event::SelectionNotify { property, .. }) => property,
+        _ => return Err(timed_out()),
+    };
+    if property == atom::NONE {
+        return Ok(None);
+    }
+
+    let reply = read_property(conn, true, window, property, atom::NONE)?;
+    if reply.property_type != atoms.incr {
+        return Ok(Some(reply));
+    }
+
+    // Deleting the INCR property asked the owner for the first chunk.
+    let mut value = Vec::new();
+    loop {
+        let is_new_value = |event: &Event| match *event {
+            Event::PropertyNotify {
+                window: w,
+                atom,
+                state: 0,
+                ..
+            } => w == window && atom == property,
+            _ => false,
+        };
+        if conn
+            .wait_for_event_timeout(SELECTION_TIMEOUT, is_new_value)?
+            .is_none()
+        {
+            return Err(timed_out());
+        }
+
+        let chunk = read_property(conn, true, window, property, atom::NONE)?;
+        // A leftover notification for a value that has already been read and deleted.
+        if chunk.format == 0 {
+            continue;
+        }
+        if chunk.value.is_empty() {
+            return Ok(Some(GetPropertyReply { value, ..chunk }));
+        }
+        value.extend_from_slice(&chunk.value);
+    }
+}
+
+/// Lists the targets the owner of `selection` can convert it to.
+pub fn selection_targets<S: Read + Write + ReadTimeout>(
+    conn: &mut Connection<S>,
+    window: u32,
+    atoms: &SelectionAtoms,
+    selection: u32,
+) -> Result<Vec<u32>> {
+    let reply = convert_selection(conn, window, atoms, selection, atoms.targets, CURRENT_TIME)?;
+    Ok(match reply {
+        Some(reply) if reply.format == 32 => reply.u32_values(),
+        _ => Vec::new(),
+    })
+}
+
+/// Gets the text of `selection`, as UTF8_STRING or else as Latin-1 STRING.
+pub fn selection_text<S: Read + Write + ReadTimeout>(
+    conn: &mut Connection<S>,
+    window: u32,
+    atoms: &SelectionAtoms,
+    selection: u32,
+) -> Result<Option<String>> {
+    let utf8_string = atoms.utf8_string;
+    if let Some(reply) =
+        convert_selection(conn, window, atoms, selection, utf8_string, CURRENT_TIME)?
+    {
+        return Ok(Some(String::from_utf8_lossy(&reply.value).into_owned()));
+    }
+    let reply = convert_selection(conn, window, atoms, selection, atom::STRING, CURRENT_TIME)?;
+    Ok(reply.map(|reply| reply.value.iter().map(|&byte| byte as char).collect()))
+}
@@ -0,0 +1,161 @@
+//! Splitting of oversized drawing requests into several requests the server will accept.
+
+use std::io::Result;
+
+use crate::request_length;
+
+fn read_i16(data: &[u8]) -> i16 {
+    i16::from_be_bytes([data[0], data[1]])
+}
+
+fn set_length(request: &mut [u8]) {
+    let length = request_length(request.len());
+    request[2..4].copy_from_slice(&length.to_be_bytes());
+}
+
+/// Splits a `PolyPoint`, `PolyLine`, `PolySegment`, `PolyRectangle`, `PolyArc`,
+/// `PolyFillRectangle` or `PolyFillArc` request into requests of at most `max_bytes` each.
+///
+/// Returns `None` for any other request.
+pub(crate) fn split_poly(request: &[u8], max_bytes: usize) -> Option<Vec<Vec<u8>>> {
+    // A PolyLine split repeats the vertex where the pieces join.
+    let (element, overlap) = match request[0] {
+        64 => (4, 0),
+        65 => (4, 1),
+        66 | 67 | 70 => (8, 0),
+        68 | 71 => (12, 0),
+        _ => return None,
+    };
+    let header = &request[..12];
+    let elements = request[12..].chunks_exact(element).collect::<Vec<_>>();
+    let per_request = max_bytes.checked_sub(12)? / element;
+    if per_request <= overlap {
+        return None;
+    }
+
+    // In CoordinateMode::Previous the first point of each piece must be made absolute.
+    let relative = request[0] <= 65 && request[1] == 1;
+    let mut absolute = (0i16, 0i16);
+
+    let mut ret = Vec::new();
+    let mut start = 0;
+    loop {
+        let end = std::cmp::min(start + per_request, elements.len());
+        let mut part = header.to_vec();
+        for (i, element) in elements[start..end].iter().enumerate() {
+            if relative && i == 0 && start > 0 {
+                part.extend_from_slice(&absolute.0.to_be_bytes());
+                part.extend_from_slice(&absolute.1.to_be_bytes());
+            } else {
+                part.extend_from_slice(element);
+            }
+        }
+        set_length(&mut part);
+        ret.push(part);
+
+        if end == elements.len() {
+            return Some(ret);
+        }
+
+        let next = end - overlap;
+        if relative {
+            for (i, element) in elements[start..=next].iter().enumerate() {
+                let (x, y) = (read_i16(element), read_i16(&element[2..]));
+                if i == 0 && start == 0 {
+                    absolute = (x, y);
+                } else if i > 0 {
+                    absolute = (absolute.0.wrapping_add(x), absolute.1.wrapping_add(y));
+                }
+            }
+        }
+        start = next;
+    }
+}
+
+/// Splits a `PolyText8` or `PolyText16` request into requests of at most `max_bytes` each,
+/// breaking only between text items.
+///
+/// Each following request must start where the previous one left off, so `measure` is called
+/// with the current fontable (the GC, or the font most recently shifted to) and the CHAR2B
+/// string of every text item that is not in the final request, and must return its width.
+///
+/// Returns `None` for any other request.
+pub(crate) fn split_poly_text<F>(
+    request: &[u8],
+    max_bytes: usize,
+    mut measure: F,
+) -> Result<Option<Vec<Vec<u8>>>>
+where
+    F: FnMut(u32, &[u16]) -> Result<i32>,
+{
+    let char_size = match request[0] {
+        74 => 1,
+        75 => 2,
+        _ => return Ok(None),
+    };
+    let gc = u32::from_be_bytes([request[8], request[9], request[10], request[11]]);
+    let mut x = read_i16(&request[12..]);
+
+    let mut items = Vec::new();
+    let mut rest = &request[16..];
+    while rest.len() >= 2 {
+        let size = if rest[0] == 255 {
+            5
+        } else {
+            2 + rest[0] as usize * char_size
+        };
+        if size > rest.len() {
+            break;
+        }
+        items.push(&rest[..size]);
+        rest = &rest[size..];
+    }
+
+    let mut fontable = gc;
+    let mut ret = Vec::new();
+    let mut part_items: Vec<&[u8]> = Vec::new();
+    let mut part_len = 16;
+    for item in items {
+        if part_len + item.len() + 3 > max_bytes && !part_items.is_empty() {
+            ret.push(poly_text_part(&request[..16], x, &part_items));
+
+            for item in part_items.drain(..) {
+                if item[0] == 255 {
+                    fontable = u32::from_be_bytes([item[1], item[2], item[3], item[4]]);
+                } else {
+                    let string = if char_size == 1 {
+                        item[2..].iter().map(|&c| c as u16).collect::<Vec<_>>()
+                    } else {
+                        item[2..]
+                            .chunks_exact(2)
+                            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                            .collect()
+                    };
+                    let width = measure(fontable, &string)?;
+                    x = x
+                        .wrapping_add(item[1] as i8 as i16)
+                        .wrapping_add(width as i16);
+                }
+            }
+            part_len = 16;
+        }
+        part_len += item.len();
+        part_items.push(item);
+    }
+    ret.push(poly_text_part(&request[..16], x, &part_items));
+
+    Ok(Some(ret))
+}
+
+fn poly_text_part(header: &[u8], x: i16, items: &[&[u8]]) -> Vec<u8> {
+    let mut part = header.to_vec();
+    part[12..14].copy_from_slice(&x.to_be_bytes());
+    for item in items {
+        part.extend_from_slice(item);
+    }
+    let padding = (4 - (part.len() % 4)) % 4;
+    part.extend_from_slice(&[0; 3][..padding]);
+    set_length(&mut part);
+
+    part
+}
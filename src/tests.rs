@@ -1,7 +1,1442 @@
 use super::*;
 
+use std::io::{Read, Result, Write};
+
 #[test]
 fn client_init() {
     let x: Vec<u8> = ClientInit::new().into();
     assert_eq!(x, b"B\x00\x00\x0B\x00\x00\x00\x00\x00\x00\x00\x00");
 }
+
+/// A stream that replays canned server bytes and records each write call separately.
+///
+/// Once the bytes run out, a read made with a timeout set fails with `WouldBlock` as a socket's
+/// would, rather than seeing the end of the stream.
+struct MockStream {
+    input: std::io::Cursor<Vec<u8>>,
+    writes: Vec<Vec<u8>>,
+    timeout: std::cell::Cell<Option<std::time::Duration>>,
+}
+
+impl MockStream {
+    fn new(server_bytes: Vec<u8>) -> Self {
+        let mut input = server_init_bytes();
+        input.extend(server_bytes);
+        MockStream::with_input(input)
+    }
+
+    fn with_input(input: Vec<u8>) -> Self {
+        MockStream {
+            input: std::io::Cursor::new(input),
+            writes: Vec::new(),
+            timeout: std::cell::Cell::new(None),
+        }
+    }
+
+    fn written(&self) -> Vec<u8> {
+        self.writes.concat()
+    }
+}
+
+impl Read for MockStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let exhausted = self.input.position() >= self.input.get_ref().len() as u64;
+        if exhausted && !buf.is_empty() && self.timeout.get().is_some() {
+            return Err(std::io::ErrorKind::WouldBlock.into());
+        }
+        self.input.read(buf)
+    }
+}
+
+impl Write for MockStream {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.writes.push(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn write_vectored(&mut self, bufs: &[std::io::IoSlice]) -> Result<usize> {
+        let data = bufs
+            .iter()
+            .flat_map(|b| b.iter().copied())
+            .collect::<Vec<u8>>();
+        self.write(&data)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ReadTimeout for MockStream {
+    fn set_read_timeout(&self, timeout: Option<std::time::Duration>) -> Result<()> {
+        self.timeout.set(timeout);
+        Ok(())
+    }
+}
+
+/// A minimal connection setup reply with no vendor, pixmap formats or screens.
+fn server_init_bytes() -> Vec<u8> {
+    let mut ret = vec![1, 0, 0, 11, 0, 0, 0, 8];
+    ret.extend_from_slice(&[0, 0, 0, 0]); // release-number
+    ret.extend_from_slice(&[0x04, 0, 0, 0]); // resource-id-base
+    ret.extend_from_slice(&[0, 0x1f, 0xff, 0xff]); // resource-id-mask
+    ret.extend_from_slice(&[0, 0, 0, 0]); // motion-buffer-size
+    ret.extend_from_slice(&[0, 0, 0xff, 0xff]); // vendor length, maximum-request-length
+    ret.extend_from_slice(&[0, 0, 0, 0, 32, 32, 8, 255]);
+    ret.extend_from_slice(&[0, 0, 0, 0]);
+    ret
+}
+
+fn connection(server_bytes: Vec<u8>) -> Connection<MockStream> {
+    Connection::new(MockStream::new(server_bytes)).unwrap()
+}
+
+#[test]
+fn requests_are_buffered_until_flush() {
+    let mut conn = connection(Vec::new());
+    let setup_writes = conn.get_ref().writes.clone().len();
+
+    assert_eq!(conn.send(&MapWindow::new(1).as_bytes()).unwrap(), 1);
+    assert_eq!(conn.send(&MapWindow::new(2).as_bytes()).unwrap(), 2);
+    assert_eq!(conn.get_ref().writes.clone().len(), setup_writes);
+
+    conn.flush().unwrap();
+    let writes = conn.get_ref().writes.clone();
+    assert_eq!(writes.len(), setup_writes + 1);
+    assert_eq!(
+        writes.last().unwrap(),
+        &[8, 0, 0, 2, 0, 0, 0, 1, 8, 0, 0, 2, 0, 0, 0, 2]
+    );
+}
+
+#[test]
+fn large_payloads_are_written_with_buffered_requests() {
+    let mut conn = connection(Vec::new());
+    conn.send(&MapWindow::new(1).as_bytes()).unwrap();
+    let data = vec![0xaa; 5001];
+    conn.send_with_data(&[72, 2, 0, 0], &data).unwrap();
+
+    let written = conn.get_ref().written();
+    let request = &written[written.len() - 5016..];
+    assert_eq!(&request[..8], &[8, 0, 0, 2, 0, 0, 0, 1]);
+    assert_eq!(&request[8..12], &[72, 2, 0x04, 0xe4]);
+    assert_eq!(&request[12..5013], &data[..]);
+    assert_eq!(&request[5013..], &[0, 0, 0]);
+}
+
+#[test]
+fn waiting_for_reply_flushes_and_queues_events() {
+    let mut server = Vec::new();
+    let mut expose = [0u8; 32];
+    expose[0] = 12;
+    server.extend_from_slice(&expose);
+    let mut reply = [0u8; 36];
+    reply[0] = 1;
+    reply[3] = 1;
+    reply[7] = 1;
+    reply[32..].copy_from_slice(b"abcd");
+    server.extend_from_slice(&reply);
+
+    let mut conn = connection(server);
+    let sequence = conn.send_with_reply(&[43, 0, 0, 1]).unwrap();
+    let reply = conn.wait_for_reply(sequence).unwrap();
+    assert_eq!(&reply[32..], b"abcd");
+    assert!(conn.get_ref().written().ends_with(&[43, 0, 0, 1]));
+
+    match conn.next_event().unwrap() {
+        Event::Expose { .. } => {}
+        e => panic!("unexpected event {:?}", e),
+    }
+}
+
+fn reply_bytes(sequence: u16, body: &[u8]) -> Vec<u8> {
+    let mut ret = vec![1, 0];
+    ret.extend_from_slice(&sequence.to_be_bytes());
+    ret.extend_from_slice(&[0; 4]);
+    ret.extend_from_slice(body);
+    ret.resize(32, 0);
+    ret
+}
+
+/// The reply to a `GetProperty` for a value of `property_type` in `format`-bit units.
+fn property_reply(sequence: u16, format: u8, property_type: u32, value: &[u8]) -> Vec<u8> {
+    let mut body = property_type.to_be_bytes().to_vec();
+    body.extend_from_slice(&[0; 4]);
+    let len = value.len() as u32 * 8 / format as u32;
+    body.extend_from_slice(&len.to_be_bytes());
+    let padded = value.len() + (4 - (value.len() % 4)) % 4;
+    let mut ret = reply_bytes(sequence, &body);
+    ret[1] = format;
+    ret[7] = (padded / 4) as u8;
+    ret.extend_from_slice(value);
+    ret.resize(32 + padded, 0);
+    ret
+}
+
+/// The atoms the selection tests take the server to have interned.
+fn selection_atoms() -> SelectionAtoms {
+    SelectionAtoms {
+        clipboard: 50,
+        targets: 51,
+        multiple: 52,
+        timestamp: 53,
+        text: 54,
+        utf8_string: 55,
+        atom_pair: 56,
+        incr: 57,
+        property: 100,
+    }
+}
+
+/// A `SelectionNotify` telling requestor 0x300 that CLIPBOARD was converted to UTF8_STRING in
+/// property 100.
+fn selection_notify() -> Vec<u8> {
+    let mut ret = vec![31, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0];
+    ret.extend_from_slice(&[0, 0, 0, 50, 0, 0, 0, 55, 0, 0, 0, 100]);
+    ret.resize(32, 0);
+    ret
+}
+
+/// A `PropertyNotify` for property 100 on `window` at time 1000, with a `state` of 0 for a new
+/// value and 1 for a deletion.
+fn property_notify(window: u32, state: u8) -> Vec<u8> {
+    let mut ret = vec![28, 0, 0, 0];
+    ret.extend_from_slice(&window.to_be_bytes());
+    ret.extend_from_slice(&[0, 0, 0, 100, 0, 0, 3, 0xe8, state]);
+    ret.resize(32, 0);
+    ret
+}
+
+#[test]
+fn oversized_request_without_big_requests_is_rejected() {
+    let mut conn = connection(reply_bytes(1, &[0, 0, 0, 0]));
+    let data = vec![0; 0xffff * 4];
+    let err = conn.send_with_data(&[72, 2, 0, 0], &data).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn oversized_request_uses_big_requests() {
+    let mut server = reply_bytes(1, &[1, 130, 0, 0]);
+    server.extend(reply_bytes(2, &[0, 0x40, 0, 0]));
+    let mut conn = connection(server);
+
+    let data = vec![0xaa; 0xffff * 4];
+    assert_eq!(conn.send_with_data(&[72, 2, 0, 0], &data).unwrap(), 3);
+
+    let written = conn.get_ref().written();
+    let request = &written[written.len() - data.len() - 8..];
+    assert_eq!(&request[..8], &[72, 2, 0, 0, 0, 1, 0, 1]);
+    assert_eq!(
+        &written[written.len() - data.len() - 12..][..4],
+        &[130, 0, 0, 1]
+    );
+}
+
+#[test]
+fn fill_poly() {
+    let points = [Point { x: 1, y: -1 }, Point { x: 256, y: 2 }];
+    let bytes = FillPoly::new(7, 8, Shape::Convex, CoordinateMode::Previous, &points).as_bytes();
+    assert_eq!(
+        bytes,
+        [69, 0, 0, 6, 0, 0, 0, 7, 0, 0, 0, 8, 2, 1, 0, 0, 0, 1, 0xff, 0xff, 1, 0, 0, 2]
+    );
+}
+
+#[test]
+fn split_relative_poly_line_repeats_joint() {
+    let points = [
+        Point { x: 10, y: 10 },
+        Point { x: 1, y: 0 },
+        Point { x: 0, y: 2 },
+        Point { x: -3, y: 0 },
+    ];
+    let request = PolyLine::new(CoordinateMode::Previous, 1, 2, &points).as_bytes();
+    let parts = split::split_poly(&request, 12 + 3 * 4).unwrap();
+
+    assert_eq!(parts.len(), 2);
+    assert_eq!(&parts[0][..4], &[65, 1, 0, 6]);
+    assert_eq!(&parts[0][12..], &request[12..24]);
+    // The second line starts at the absolute position of the third point.
+    assert_eq!(&parts[1][..4], &[65, 1, 0, 5]);
+    assert_eq!(&parts[1][12..], &[0, 11, 0, 12, 0xff, 0xfd, 0, 0]);
+}
+
+#[test]
+fn split_poly_text_advances_origin() {
+    let mut request = vec![74, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 2, 0, 5, 0, 9];
+    request.extend_from_slice(&[3, 1, b'a', b'b', b'c']);
+    request.extend_from_slice(&[255, 0, 0, 0, 7]);
+    request.extend_from_slice(&[2, 0, b'd', b'e']);
+    request.extend_from_slice(&[1, 0, b'f', 0]);
+
+    let mut measured = Vec::new();
+    let parts = split::split_poly_text(&request, 28, |fontable, string| {
+        measured.push((fontable, string.to_vec()));
+        Ok(string.len() as i32 * 6)
+    })
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(measured, [(2, vec![97, 98, 99]), (7, vec![100, 101])]);
+    assert_eq!(parts.len(), 3);
+    assert_eq!(&parts[1][12..14], &[0, 24]);
+    assert_eq!(&parts[2][12..14], &[0, 36]);
+    assert_eq!(&parts[2][16..], &[1, 0, b'f', 0]);
+}
+
+#[test]
+fn generate_id() {
+    let mut conn = connection(Vec::new());
+    assert_eq!(conn.generate_id().unwrap(), 0x0400_0000);
+    assert_eq!(conn.generate_id().unwrap(), 0x0400_0001);
+
+    // A server that hands out no resource IDs at all.
+    let mut init = server_init_bytes();
+    init[16..20].copy_from_slice(&[0, 0, 0, 0]);
+    let mut conn = Connection::new(MockStream::with_input(init)).unwrap();
+    assert!(conn.generate_id().is_err());
+}
+
+#[test]
+fn copy_area_and_copy_plane() {
+    let copy = CopyArea::new(0x100, 0x200, 0x300, -1, 2, 3, 4, 50, 60);
+    assert_eq!(
+        copy.as_bytes(),
+        [
+            62, 0, 0, 7, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0xff, 0xff, 0, 2, 0, 3, 0, 4, 0, 50,
+            0, 60
+        ]
+    );
+
+    let copy = CopyPlane::new(0x100, 0x200, 0x300, 0, 0, 5, 6, 7, 8, 0x80);
+    let bytes = copy.as_bytes();
+    assert_eq!(bytes.len(), 32);
+    assert_eq!(bytes[..4], [63, 0, 0, 8]);
+    assert_eq!(bytes[20..], [0, 5, 0, 6, 0, 7, 0, 8, 0, 0, 0, 0x80]);
+}
+
+#[test]
+fn decode_exposure_events() {
+    let mut graphics = [0u8; 32];
+    graphics[..4].copy_from_slice(&[13 | 0x80, 0, 0, 9]);
+    graphics[4..8].copy_from_slice(&[0, 0x40, 0, 2]);
+    graphics[8..21].copy_from_slice(&[0, 1, 0, 2, 0, 30, 0, 40, 0, 0, 0, 3, 62]);
+    match Event::from_bytes(&graphics) {
+        Event::GraphicsExposure {
+            sequence: 9,
+            drawable: 0x400002,
+            x: 1,
+            y: 2,
+            width: 30,
+            height: 40,
+            minor_opcode: 0,
+            count: 3,
+            major_opcode: 62,
+        } => {}
+        event => panic!("unexpected {:?}", event),
+    }
+
+    let mut none = [0u8; 32];
+    none[..4].copy_from_slice(&[14, 0, 0, 10]);
+    none[4..11].copy_from_slice(&[0, 0x40, 0, 2, 0, 0, 63]);
+    match Event::from_bytes(&none) {
+        Event::NoExposure {
+            sequence: 10,
+            drawable: 0x400002,
+            minor_opcode: 0,
+            major_opcode: 63,
+        } => {}
+        event => panic!("unexpected {:?}", event),
+    }
+}
+
+#[test]
+fn validate_pixmap_depth() {
+    let mut init = ServerInit::from_stream(&mut std::io::Cursor::new(server_init_bytes())).unwrap();
+    for depth in [1, 24] {
+        init.pixmap_formats.push(PixmapFormat {
+            depth,
+            bits_per_pixel: if depth == 1 { 1 } else { 32 },
+            scanline_pad: 32,
+        });
+    }
+    let screen = Screen {
+        root: 0x100,
+        default_colormap: 0x20,
+        white_pixel: 0xffffff,
+        black_pixel: 0,
+        current_input_masks: 0,
+        width_pixels: 640,
+        height_pixels: 480,
+        width_millimeters: 169,
+        height_millimeters: 127,
+        min_installed_maps: 1,
+        max_installed_maps: 1,
+        root_visual: 0x21,
+        backing_stores: 0,
+        save_unders: false,
+        root_depth: 24,
+        allowed_depths: vec![Depth {
+            depth: 24,
+            visuals: Vec::new(),
+        }],
+    };
+
+    let pixmap = |depth| CreatePixmap::new(depth, 0x400001, 0x100, 16, 16);
+    assert!(pixmap(24).validate(&init, &screen).is_ok());
+    // Depth 1 is allowed although the screen does not list it.
+    assert!(pixmap(1).validate(&init, &screen).is_ok());
+    // No pixmap format for depth 8.
+    assert!(pixmap(8).validate(&init, &screen).is_err());
+    assert!(CreatePixmap::new(24, 0x400001, 0x100, 0, 16)
+        .validate(&init, &screen)
+        .is_err());
+
+    // A format without a screen depth to go with it.
+    init.pixmap_formats.push(PixmapFormat {
+        depth: 32,
+        bits_per_pixel: 32,
+        scanline_pad: 32,
+    });
+    assert!(pixmap(32).validate(&init, &screen).is_err());
+}
+
+fn layout(depth: u8, bits_per_pixel: u8, image_byte_order: u8) -> image::ImageLayout {
+    image::ImageLayout {
+        depth,
+        bits_per_pixel,
+        scanline_pad: 32,
+        image_byte_order,
+        bitmap_format_bit_order: image_byte_order,
+        bitmap_format_scanline_unit: 32,
+        bitmap_format_scanline_pad: 32,
+        red_mask: 0xf800,
+        green_mask: 0x07e0,
+        blue_mask: 0x001f,
+    }
+}
+
+#[test]
+fn rgba_to_16bpp_zpixmap() {
+    let rgba = [255, 0, 0, 255, 0, 255, 255, 255, 0, 0, 0, 255];
+    let image =
+        image::Image::from_rgba(&layout(16, 16, 0), ImageFormat::ZPixmap, &rgba, 3, 1).unwrap();
+
+    assert_eq!(image.bytes_per_line, 8);
+    assert_eq!(image.data, [0x00, 0xf8, 0xff, 0x07, 0, 0, 0, 0]);
+}
+
+#[test]
+fn rgba_to_msb_first_bitmap() {
+    let mut rgba = vec![0; 9 * 4];
+    rgba[3] = 255;
+    rgba[8 * 4 + 3] = 255;
+    let image =
+        image::Image::from_rgba(&layout(1, 1, 1), ImageFormat::Bitmap, &rgba, 9, 1).unwrap();
+
+    assert_eq!(image.data, [0x80, 0x80, 0, 0]);
+}
+
+#[test]
+fn image_round_trips_through_rgba() {
+    let rgba = [
+        255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 255, 0, 0, 0, 255,
+    ];
+    let mut layout = layout(24, 24, 1);
+    layout.red_mask = 0xff0000;
+    layout.green_mask = 0x00ff00;
+    layout.blue_mask = 0x0000ff;
+
+    for &format in &[ImageFormat::ZPixmap, ImageFormat::XYPixmap] {
+        let image = image::Image::from_rgba(&layout, format, &rgba, 5, 1).unwrap();
+        assert_eq!(image.to_rgba(&layout).unwrap(), &rgba[..]);
+    }
+
+    let image = image::Image::from_rgba(&layout, ImageFormat::ZPixmap, &rgba, 5, 1).unwrap();
+    assert_eq!(&image.data[..6], &[0xff, 0, 0, 0, 0xff, 0]);
+}
+
+#[test]
+fn rgb_pixel_for_true_color_visual() {
+    let visual = Visual {
+        id: 0x21,
+        class: VisualClass::TrueColor,
+        bits_per_rgb_value: 6,
+        colormap_entries: 64,
+        red_mask: 0xf800,
+        green_mask: 0x07e0,
+        blue_mask: 0x001f,
+    };
+    assert_eq!(visual.rgb_pixel(0xffff, 0x8000, 0), Some(0xfc00));
+
+    let pseudo = Visual {
+        class: VisualClass::PseudoColor,
+        ..visual
+    };
+    assert_eq!(pseudo.rgb_pixel(0xffff, 0x8000, 0), None);
+
+    let malformed = Visual {
+        blue_mask: 0,
+        ..visual
+    };
+    assert_eq!(malformed.rgb_pixel(0xffff, 0x8000, 0), None);
+}
+
+#[test]
+fn create_window_attributes() {
+    let bytes = CreateWindow::new(24, 1, 2, 0, 0, 10, 10, 0, 1, 0)
+        .with_attributes(WindowAttributes {
+            background_pixel: Some(0x123456),
+            event_mask: Some(event_mask::EXPOSURE),
+            ..Default::default()
+        })
+        .as_bytes();
+    assert_eq!(&bytes[..4], &[1, 24, 0, 10]);
+    assert_eq!(
+        &bytes[28..],
+        &[0, 0, 0x08, 0x02, 0, 0x12, 0x34, 0x56, 0, 0, 0x80, 0]
+    );
+    assert_eq!(event_mask::ALL_EVENTS, 0x00eb_ff7f);
+}
+
+#[test]
+fn parse_color_specs() {
+    let slate_gray = Rgb {
+        red: 112 * 257,
+        green: 128 * 257,
+        blue: 144 * 257,
+    };
+    assert_eq!(Rgb::parse("slate gray"), Some(slate_gray));
+    assert_eq!(Rgb::parse("SlateGray"), Some(slate_gray));
+    assert_eq!(
+        Rgb::parse("#3a7"),
+        Some(Rgb {
+            red: 0x3000,
+            green: 0xa000,
+            blue: 0x7000
+        })
+    );
+    assert_eq!(
+        Rgb::parse("rgb:f/80/1234"),
+        Some(Rgb {
+            red: 0xffff,
+            green: 0x8080,
+            blue: 0x1234
+        })
+    );
+    assert_eq!(
+        Rgb::parse("rgbi:1.0/0/0.5"),
+        Some(Rgb {
+            red: 0xffff,
+            green: 0,
+            blue: 0x8000
+        })
+    );
+    assert_eq!(Rgb::parse("no such color"), None);
+    assert_eq!(Rgb::parse("#12345"), None);
+}
+
+#[test]
+fn poly_text8_items() {
+    let long = [b'x'; 300];
+    let items = [
+        TextItem::Text {
+            delta: -2,
+            string: &long[..],
+        },
+        TextItem::Font(0x01020304),
+        TextItem::Text {
+            delta: 0,
+            string: b"ab",
+        },
+    ];
+    let bytes = PolyText8::new(1, 2, 3, 4, &items).as_bytes();
+
+    assert_eq!(&bytes[16..18], &[254, 0xfe]);
+    assert_eq!(&bytes[272..274], &[46, 0]);
+    assert_eq!(&bytes[320..329], &[255, 1, 2, 3, 4, 2, 0, b'a', b'b']);
+    assert_eq!(bytes.len(), 332);
+    assert_eq!(&bytes[..4], &[74, 0, 0, 83]);
+}
+
+/// The FONTINFO fields of a `QueryFont` or `ListFontsWithInfo` reply, from min-bounds on.
+fn font_info_bytes(count: u32, properties: &[(u32, u32)]) -> Vec<u8> {
+    let mut ret = Vec::new();
+    ret.extend_from_slice(&[0, 0, 0, 5, 0, 5, 0, 8, 0, 2, 0, 0, 0, 0, 0, 0]); // min-bounds
+    ret.extend_from_slice(&[0, 1, 0, 7, 0, 8, 0, 11, 0, 3, 0, 0, 0, 0, 0, 0]); // max-bounds
+    ret.extend_from_slice(&[0, 0x20, 0, 0x22, 0, 0x20]); // chars 0x20 to 0x22, default 0x20
+    ret.extend_from_slice(&(properties.len() as u16).to_be_bytes());
+    ret.extend_from_slice(&[0, 0, 0, 1]); // LeftToRight, byte1 0 to 0, all chars exist
+    ret.extend_from_slice(&[0, 11, 0, 3]);
+    ret.extend_from_slice(&count.to_be_bytes());
+    for &(name, value) in properties {
+        ret.extend_from_slice(&name.to_be_bytes());
+        ret.extend_from_slice(&value.to_be_bytes());
+    }
+    ret
+}
+
+#[test]
+fn parse_font_info_replies() {
+    let mut reply = vec![1, 0, 0, 1, 0, 0, 0, 0];
+    reply.extend(font_info_bytes(3, &[(atom::POINT_SIZE, 120)]));
+    assert_eq!(reply.len(), 68);
+    reply.extend_from_slice(&[0, 0, 0, 5, 0, 6, 0, 8, 0, 2, 0, 0]);
+    reply.extend_from_slice(&[0; 12]); // 0x21 does not exist
+    reply.extend_from_slice(&[0, 1, 0, 7, 0, 8, 0, 11, 0, 3, 0, 0]);
+    let font = QueryFontReply::from_bytes(&reply).unwrap();
+
+    assert_eq!(font.info.min_bounds.character_width, 5);
+    assert_eq!(font.info.max_bounds.right_side_bearing, 7);
+    assert_eq!(
+        (font.info.min_char_or_byte2, font.info.max_char_or_byte2),
+        (0x20, 0x22)
+    );
+    assert_eq!((font.info.font_ascent, font.info.font_descent), (11, 3));
+    assert!(font.info.all_chars_exist);
+    assert_eq!(font.info.property(atom::POINT_SIZE), Some(120));
+    assert_eq!(font.char_infos.len(), 3);
+    assert_eq!(font.char_info(0x22).unwrap().character_width, 8);
+    assert_eq!(font.char_info(0x23), None);
+    // 0x21 has no metrics, so the default character stands in for it.
+    assert_eq!(font.char_info(0x21), None);
+    assert_eq!(font.text_width(&[0x20, 0x21, 0x22]), 6 + 6 + 8);
+
+    let name = b"fixed";
+    let mut info = vec![1, name.len() as u8, 0, 2, 0, 0, 0, 0];
+    info.extend(font_info_bytes(4, &[]));
+    info.extend_from_slice(name);
+    let info = ListFontsWithInfoReply::from_bytes(&info).unwrap().unwrap();
+    assert_eq!(info.name, "fixed");
+    assert_eq!(info.replies_hint, 4);
+    assert_eq!(info.info.max_bounds.character_width, 8);
+    assert!(info.info.properties.is_empty());
+
+    let last = reply_bytes(3, &[]);
+    assert!(ListFontsWithInfoReply::from_bytes(&last).unwrap().is_none());
+}
+
+#[test]
+fn set_font_path() {
+    let path = SetFontPath::new(vec!["built-ins".into(), "/usr/share/fonts/misc".into()]);
+    let bytes = path.unwrap().as_bytes();
+    assert_eq!(bytes[..8], [51, 0, 0, 10, 0, 2, 0, 0]);
+    assert_eq!(bytes[8], 9);
+    assert_eq!(&bytes[9..18], b"built-ins");
+    assert_eq!(bytes[18], 21);
+    assert_eq!(bytes.len(), 40);
+
+    assert!(SetFontPath::new(vec!["x".repeat(256)]).is_err());
+}
+
+#[test]
+fn xlfd_names() {
+    let name = "-misc-fixed-medium-r-normal--13-120-75-75-c-70-iso10646-1";
+    let xlfd = Xlfd::parse(name).unwrap();
+    assert_eq!(xlfd.family, "fixed");
+    assert_eq!(xlfd.pixel_size(), Some(13));
+    assert!(xlfd.is_unicode());
+    assert_eq!(xlfd.to_string(), name);
+    assert_eq!(Xlfd::parse("fixed"), None);
+
+    assert!(font_name_matches("-MISC-*-r-*--1?-*-iso10646-1", name));
+    assert!(!font_name_matches("-misc-*-bold-*", name));
+
+    let names = [
+        "-misc-fixed-medium-r-normal--10-100-75-75-c-60-iso8859-1".to_string(),
+        "-misc-fixed-medium-r-normal--13-120-75-75-c-70-iso8859-1".to_string(),
+        "fixed".to_string(),
+    ];
+    assert_eq!(best_font_match(&names, 12), Some(names[1].clone()));
+    let scalable = "-bitstream-charter-medium-r-normal--0-0-0-0-p-0-iso8859-1".to_string();
+    assert_eq!(
+        best_font_match(&[names[0].clone(), scalable], 12).as_deref(),
+        Some("-bitstream-charter-medium-r-normal--12-*-*-*-p-*-iso8859-1")
+    );
+
+    assert_eq!(char2b_from_str("aé€😀"), [0x61, 0xe9, 0x20ac, 0xfffd]);
+}
+
+#[test]
+fn create_font_cursor() {
+    let mut conn = connection(Vec::new());
+    let cursor = super::create_font_cursor(&mut conn, cursor_font::XTERM).unwrap();
+    conn.flush().unwrap();
+
+    assert_eq!(cursor, 0x0400_0001);
+    let written = conn.get_ref().written();
+    let glyph_cursor = &written[written.len() - 40..][..32];
+    assert_eq!(&glyph_cursor[..4], &[94, 0, 0, 8]);
+    assert_eq!(&glyph_cursor[16..20], &[0, 152, 0, 153]);
+    assert_eq!(&glyph_cursor[26..], &[0xff; 6]);
+    assert_eq!(&written[written.len() - 8..], &[46, 0, 0, 2, 4, 0, 0, 0]);
+}
+
+#[test]
+fn parse_and_select_xcursor_images() {
+    fn image_chunk(size: u32, delay: u32) -> Vec<u8> {
+        let mut chunk = Vec::new();
+        for value in [36, 0xfffd_0002, size, 1, 2, 1, 1, 0, delay] {
+            chunk.extend_from_slice(&u32::to_le_bytes(value));
+        }
+        chunk.extend_from_slice(&0xff00_00ffu32.to_le_bytes());
+        chunk.extend_from_slice(&0x8000_0000u32.to_le_bytes());
+        chunk
+    }
+
+    let chunks = [image_chunk(24, 50), image_chunk(32, 0), image_chunk(24, 70)];
+    let mut file = Vec::new();
+    for value in [0x7275_6358, 16, 0x1_0000, 3] {
+        file.extend_from_slice(&u32::to_le_bytes(value));
+    }
+    let mut position = 16 + 12 * chunks.len() as u32;
+    for chunk in &chunks {
+        let size = u32::from_le_bytes([chunk[8], chunk[9], chunk[10], chunk[11]]);
+        for value in [0xfffd_0002, size, position] {
+            file.extend_from_slice(&u32::to_le_bytes(value));
+        }
+        position += chunk.len() as u32;
+    }
+    for chunk in &chunks {
+        file.extend_from_slice(chunk);
+    }
+
+    let images = parse_xcursor(&file).unwrap();
+    assert_eq!(images.len(), 3);
+    assert_eq!(
+        (images[0].width, images[0].height, images[0].xhot),
+        (2, 1, 1)
+    );
+    assert_eq!(images[0].pixels, [0xff00_00ff, 0x8000_0000]);
+
+    let frames = select_nominal_size(images, 26);
+    assert_eq!(
+        frames.iter().map(|frame| frame.delay).collect::<Vec<_>>(),
+        [50, 70]
+    );
+    assert!(parse_xcursor(b"GIF89a").is_err());
+
+    // A 0x7fff-square image header with no pixels after it is rejected before allocating.
+    let mut huge = Vec::new();
+    for value in [0x7275_6358, 16, 0x1_0000, 1, 0xfffd_0002, 24, 28] {
+        huge.extend_from_slice(&u32::to_le_bytes(value));
+    }
+    for value in [36, 0xfffd_0002, 24, 1, 0x7fff, 0x7fff, 0, 0, 0] {
+        huge.extend_from_slice(&u32::to_le_bytes(value));
+    }
+    let error = parse_xcursor(&huge).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn keymap_translation() {
+    let keyboard = GetKeyboardMappingReply {
+        keysyms_per_keycode: 4,
+        keysyms: vec![
+            0x61, 0, 0, 0, // a
+            0xff95, 0xffb7, 0, 0, // KP_Home, KP_7
+            0xffe5, 0, 0, 0, // Caps_Lock
+            0xff7f, 0, 0, 0, // Num_Lock
+            0xff7e, 0, 0, 0, // Mode_switch
+            0x62, 0x42, 0x6c1, 0x6e1, // b, B, Cyrillic a, A
+            0x31, 0x21, 0, 0, // 1, !
+        ],
+    };
+    let mut keycodes = vec![0; 8];
+    keycodes[1] = 12;
+    keycodes[4] = 13;
+    keycodes[7] = 14;
+    let modifiers = GetModifierMappingReply {
+        keycodes_per_modifier: 1,
+        keycodes,
+    };
+    let keymap = Keymap::new(10, keyboard, modifiers);
+    let (shift, lock, num_lock, mode_switch) = (0x1, 0x2, 0x10, 0x80);
+
+    assert_eq!(keymap.lock_mode(), LockMode::CapsLock);
+    assert_eq!(keymap.keysym(10, 0), 0x61);
+    assert_eq!(keymap.keysym(10, shift), 0x41);
+    assert_eq!(keymap.keysym(10, lock), 0x41);
+    assert_eq!(keymap.keysym(10, shift | lock), 0x41);
+    assert_eq!(keymap.keysym(16, lock), 0x31);
+    assert_eq!(keymap.keysym(16, shift | lock), 0x21);
+    assert_eq!(keymap.keysym(11, 0), 0xff95);
+    assert_eq!(keymap.keysym(11, num_lock), 0xffb7);
+    assert_eq!(keymap.keysym(11, num_lock | shift), 0xff95);
+    assert_eq!(keymap.keysym(15, mode_switch), 0x6c1);
+    assert_eq!(keymap.keysym(15, mode_switch | shift), 0x6e1);
+    assert_eq!(keymap.keysym(10, mode_switch | shift), 0x41);
+    assert_eq!(keymap.keysym(99, 0), 0);
+}
+
+#[test]
+fn keysym_names_and_unicode() {
+    assert_eq!(keysym::from_name("Return"), Some(keysym::XK_Return));
+    assert_eq!(keysym::from_name("XF86AudioMute"), Some(0x1008_ff12));
+    assert_eq!(keysym::from_name("U20AC"), Some(0x0100_20ac));
+    assert_eq!(keysym::from_name("U00e9"), Some(0xe9));
+    assert_eq!(keysym::from_name("NoSuchKey"), None);
+    assert_eq!(keysym::name(0xff0d).as_deref(), Some("Return"));
+    assert_eq!(keysym::name(0x0100_1234).as_deref(), Some("U1234"));
+
+    assert_eq!(keysym::to_char(keysym::XK_a), Some('a'));
+    assert_eq!(keysym::to_char(keysym::XK_eacute), Some('é'));
+    assert_eq!(keysym::to_char(keysym::XK_Cyrillic_de), Some('д'));
+    assert_eq!(keysym::to_char(keysym::XK_Greek_OMEGA), Some('Ω'));
+    assert_eq!(keysym::to_char(keysym::XK_EuroSign), Some('€'));
+    assert_eq!(keysym::to_char(0x0100_263a), Some('☺'));
+    assert_eq!(keysym::to_char(keysym::XK_Return), Some('\r'));
+    assert_eq!(keysym::to_char(keysym::XK_KP_7), Some('7'));
+    assert_eq!(keysym::to_char(keysym::XK_Shift_L), None);
+
+    assert_eq!(keysym::from_char('q'), keysym::XK_q);
+    assert_eq!(keysym::from_char('д'), keysym::XK_Cyrillic_de);
+    assert_eq!(keysym::from_char('☺'), 0x0100_263a);
+}
+
+#[test]
+fn compose_sequences() {
+    let mut table = ComposeTable::new();
+    table
+        .add_str(
+            r#"
+# comment
+<dead_acute> <e>            : "é"   eacute # LATIN SMALL LETTER E WITH ACUTE
+<Multi_key> <o> <c>         : "©"   copyright
+<Multi_key> <minus> <greater> : "\342\206\222" # RIGHTWARDS ARROW
+<Multi_key> <q> <q>         : "\"q\x21"
+<Multi_key> <a> <e>         : aring
+<Multi_key> <a> <e>         : "æ"   ae
+<Multi_key> <bogus_keysym> <x> : "x"
+"#,
+        )
+        .unwrap();
+
+    let mut state = table.state();
+    assert_eq!(state.feed(keysym::XK_a), ComposeStatus::Nothing);
+    assert_eq!(state.feed(keysym::XK_dead_acute), ComposeStatus::Composing);
+    assert_eq!(
+        state.feed(keysym::XK_e),
+        ComposeStatus::Composed {
+            string: "é".to_string(),
+            keysym: keysym::XK_eacute
+        }
+    );
+
+    let mut compose = |keysyms: &[u32]| {
+        let mut last = ComposeStatus::Nothing;
+        for &keysym in keysyms {
+            last = state.feed(keysym);
+        }
+        match last {
+            ComposeStatus::Composed { string, .. } => Some(string),
+            _ => None,
+        }
+    };
+    let multi = keysym::XK_Multi_key;
+    assert_eq!(
+        compose(&[multi, keysym::XK_minus, keysym::XK_greater]).as_deref(),
+        Some("→")
+    );
+    assert_eq!(
+        compose(&[multi, keysym::XK_q, keysym::XK_q]).as_deref(),
+        Some("\"q!")
+    );
+    assert_eq!(
+        compose(&[multi, keysym::XK_a, keysym::XK_e]).as_deref(),
+        Some("æ")
+    );
+    // Shift does not interrupt a sequence.
+    assert_eq!(
+        compose(&[multi, keysym::XK_Shift_L, keysym::XK_o, keysym::XK_c]).as_deref(),
+        Some("©")
+    );
+
+    let mut state = table.state();
+    state.feed(multi);
+    state.feed(keysym::XK_o);
+    assert!(state.is_composing());
+    assert_eq!(state.feed(keysym::XK_z), ComposeStatus::Cancelled);
+    assert!(!state.is_composing());
+}
+
+#[test]
+fn compose_files_and_includes() {
+    let dir = std::env::temp_dir().join(format!("x11_client_compose_{}", std::process::id()));
+    let locale_dir = dir.join("locale");
+    std::fs::create_dir_all(locale_dir.join("en_US.UTF-8")).unwrap();
+    let write = |path: &std::path::Path, text: &str| std::fs::write(path, text).unwrap();
+    write(
+        &locale_dir.join("compose.dir"),
+        "# comment\nen_US.UTF-8/Compose:\t\ten_US.UTF-8\n",
+    );
+    write(
+        &locale_dir.join("locale.alias"),
+        "en_US.utf8:\ten_US.UTF-8\n",
+    );
+    write(
+        &locale_dir.join("en_US.UTF-8/Compose"),
+        "include \"%S/common\"\n<Multi_key> <o> <c> : \"©\" copyright\n",
+    );
+    write(
+        &locale_dir.join("common"),
+        "<Multi_key> <minus> <greater> : \"→\"\n",
+    );
+    write(
+        &dir.join("XCompose"),
+        "include \"%L\"\n<Multi_key> <o> <c> : \"ⓒ\"\ninclude \"%H/extra\"\n",
+    );
+    write(&dir.join("extra"), "<Multi_key> <a> <e> : \"æ\" ae\n");
+    write(&dir.join("loop"), "include \"%H/loop\"\n");
+
+    std::env::set_var("XLOCALEDIR", &locale_dir);
+    std::env::set_var("LC_ALL", "en_US.utf8");
+    std::env::set_var("HOME", &dir);
+    std::env::set_var("XCOMPOSEFILE", dir.join("XCompose"));
+
+    assert_eq!(
+        locale_compose_file("en_US.utf8"),
+        Some(locale_dir.join("en_US.UTF-8/Compose"))
+    );
+    assert_eq!(locale_compose_file("xx_XX"), None);
+
+    let table = ComposeTable::load_default().unwrap();
+    let compose = |keysyms: &[u32]| {
+        let mut state = table.state();
+        let mut last = ComposeStatus::Nothing;
+        for &keysym in keysyms {
+            last = state.feed(keysym);
+        }
+        match last {
+            ComposeStatus::Composed { string, .. } => Some(string),
+            _ => None,
+        }
+    };
+    let multi = keysym::XK_Multi_key;
+    // From the system directory, through the locale's file.
+    assert_eq!(
+        compose(&[multi, keysym::XK_minus, keysym::XK_greater]).as_deref(),
+        Some("→")
+    );
+    // The user's file overrides the locale's after including it.
+    assert_eq!(
+        compose(&[multi, keysym::XK_o, keysym::XK_c]).as_deref(),
+        Some("ⓒ")
+    );
+    // From the home directory.
+    assert_eq!(
+        compose(&[multi, keysym::XK_a, keysym::XK_e]).as_deref(),
+        Some("æ")
+    );
+
+    let error = ComposeTable::new()
+        .add_file(&dir.join("loop"))
+        .err()
+        .unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn grab_hotkey_covers_lock_modifiers() {
+    let keyboard = GetKeyboardMappingReply {
+        keysyms_per_keycode: 1,
+        keysyms: vec![keysym::XK_Num_Lock],
+    };
+    let mut keycodes = vec![0; 8];
+    keycodes[4] = 77;
+    let modifiers = GetModifierMappingReply {
+        keycodes_per_modifier: 1,
+        keycodes,
+    };
+    let keymap = Keymap::new(77, keyboard, modifiers);
+
+    let mut conn = connection(Vec::new());
+    grab_hotkey(&mut conn, &keymap, 0x123, 0x4 | 0x8, 38).unwrap();
+    conn.flush().unwrap();
+
+    let written = conn.get_ref().written();
+    let requests = written[written.len() - 64..].chunks(16).collect::<Vec<_>>();
+    assert_eq!(
+        requests[0],
+        [33, 0, 0, 4, 0, 0, 1, 0x23, 0, 0x0c, 38, 1, 1, 0, 0, 0]
+    );
+    let modifiers = requests
+        .iter()
+        .map(|request| u16::from_be_bytes([request[8], request[9]]))
+        .collect::<Vec<_>>();
+    assert_eq!(modifiers, [0x0c, 0x0e, 0x1c, 0x1e]);
+}
+
+#[test]
+fn query_pointer_and_motion_history() {
+    let mut reply = reply_bytes(
+        1,
+        &[
+            0, 0, 1, 0, 0, 0, 2, 0, 0, 10, 0xff, 0xfe, 0, 3, 0, 4, 0, 0x11,
+        ],
+    );
+    reply[1] = 1;
+    let mut conn = connection(reply);
+
+    let pointer = conn.request(&QueryPointer::new(0x200).as_bytes()).unwrap();
+    let pointer = QueryPointerReply::from_bytes(&pointer).unwrap();
+    assert!(pointer.same_screen);
+    assert_eq!((pointer.root, pointer.child), (0x100, 0x200));
+    assert_eq!((pointer.root_x, pointer.root_y), (10, -2));
+    assert_eq!((pointer.win_x, pointer.win_y, pointer.mask), (3, 4, 0x11));
+
+    // The server keeps no motion history, so nothing is sent.
+    let writes = conn.get_ref().writes.len();
+    assert_eq!(get_motion_events(&mut conn, 0x200, 0, 0).unwrap(), []);
+    conn.flush().unwrap();
+    assert_eq!(conn.get_ref().writes.len(), writes);
+}
+
+#[test]
+fn selection_owner_and_requestor() {
+    let atoms = selection_atoms();
+
+    let mut server_bytes = vec![12, 0, 0, 0, 0, 0, 2, 0];
+    server_bytes.resize(32, 0);
+    server_bytes.extend(property_notify(0x200, 0));
+    server_bytes.extend(reply_bytes(3, &[0, 0, 2, 0]));
+    let mut conn = connection(server_bytes);
+
+    let mut owner = SelectionOwner::new(0x200, atoms);
+    owner.set_text("h\u{e9}llo \u{2603}");
+    assert!(owner.acquire(&mut conn, atoms.clipboard).unwrap());
+    assert!(owner.owns(atoms.clipboard) && !owner.owns(atom::PRIMARY));
+    // The Expose that arrived while waiting for the timestamp is still queued.
+    assert!(matches!(
+        conn.next_event().unwrap(),
+        Event::Expose { window: 0x200, .. }
+    ));
+
+    let mut answer = |conn: &mut Connection<MockStream>, time, target| {
+        let request = Event::SelectionRequest {
+            sequence: 0,
+            time,
+            owner: 0x200,
+            requestor: 0x300,
+            selection: atoms.clipboard,
+            target,
+            property: 60,
+        };
+        let start = conn.get_ref().written().len();
+        assert!(owner.handle_event(conn, &request).unwrap());
+        conn.flush().unwrap();
+        conn.get_ref().written()[start..].to_vec()
+    };
+
+    let written = answer(&mut conn, 1001, atoms.targets);
+    assert_eq!(written[..4], [18, 0, 0, 12]);
+    assert_eq!(written[12..24], [0, 0, 0, 4, 32, 0, 0, 0, 0, 0, 0, 6]);
+    let targets = written[24..48]
+        .chunks(4)
+        .map(|atom| u32::from_be_bytes([atom[0], atom[1], atom[2], atom[3]]))
+        .collect::<Vec<_>>();
+    assert_eq!(targets, [51, 52, 53, 55, 54, 31]);
+    let send_event = &written[48..];
+    assert_eq!(send_event[..12], [25, 0, 0, 11, 0, 0, 3, 0, 0, 0, 0, 0]);
+    assert_eq!(
+        send_event[12..36],
+        [31, 0, 0, 0, 0, 0, 3, 0xe9, 0, 0, 3, 0, 0, 0, 0, 50, 0, 0, 0, 51, 0, 0, 0, 60]
+    );
+
+    let written = answer(&mut conn, 1002, atom::STRING);
+    assert_eq!(
+        written[16..28],
+        [8, 0, 0, 0, 0, 0, 0, 7, b'h', 0xe9, b'l', b'l']
+    );
+    assert_eq!(written[28..31], [b'o', b' ', b'?']);
+
+    // Requests from before the selection was acquired are refused.
+    let written = answer(&mut conn, 999, atoms.utf8_string);
+    assert_eq!(written[0], 25);
+    assert_eq!(written[32..36], [0, 0, 0, 0]);
+
+    let clear = Event::SelectionClear {
+        sequence: 0,
+        time: 1003,
+        owner: 0x200,
+        selection: atoms.clipboard,
+    };
+    assert!(owner.handle_event(&mut conn, &clear).unwrap());
+    assert!(!owner.is_owner());
+
+    // The owner's SelectionNotify arrives through SendEvent.
+    let mut server_bytes = selection_notify();
+    server_bytes[0] |= 0x80;
+    server_bytes.extend(property_reply(2, 8, 55, b"hello"));
+    let mut conn = connection(server_bytes);
+
+    let text = selection_text(&mut conn, 0x300, &atoms, atoms.clipboard).unwrap();
+    assert_eq!(text.as_deref(), Some("hello"));
+    let written = conn.get_ref().written();
+    let get_property = &written[written.len() - 24..];
+    assert_eq!(get_property[..4], [20, 1, 0, 6]);
+    assert_eq!(get_property[8..12], [0, 0, 0, 100]);
+}
+
+#[test]
+fn multiple_conversion_for_a_vanished_requestor() {
+    let atoms = selection_atoms();
+    let mut server_bytes = property_notify(0x200, 0);
+    server_bytes.extend(reply_bytes(3, &[0, 0, 2, 0]));
+    // BadWindow for the GetProperty of the pairs to convert.
+    let mut error = vec![0, 3, 0, 4, 0, 0, 3, 0, 0, 0, 20];
+    error.resize(32, 0);
+    server_bytes.extend(error);
+    let mut conn = connection(server_bytes);
+
+    let mut owner = SelectionOwner::new(0x200, atoms);
+    owner.set_text("hello");
+    assert!(owner.acquire(&mut conn, atoms.clipboard).unwrap());
+    conn.flush().unwrap();
+    let start = conn.get_ref().written().len();
+    let request = Event::SelectionRequest {
+        sequence: 0,
+        time: 1001,
+        owner: 0x200,
+        requestor: 0x300,
+        selection: atoms.clipboard,
+        target: atoms.multiple,
+        property: 60,
+    };
+    assert!(owner.handle_event(&mut conn, &request).unwrap());
+    assert!(owner.is_owner());
+
+    // The conversion is refused.
+    conn.flush().unwrap();
+    let written = conn.get_ref().written()[start..].to_vec();
+    let requests = split_requests(&written);
+    assert_eq!(requests[0][..4], [20, 0, 0, 6]);
+    assert_eq!(requests[1][0], 25);
+    assert_eq!(requests[1][32..36], [0, 0, 0, 0]);
+}
+
+/// Splits written bytes into requests using their length fields.
+fn split_requests(mut bytes: &[u8]) -> Vec<&[u8]> {
+    let mut ret = Vec::new();
+    while !bytes.is_empty() {
+        let length = u16::from_be_bytes([bytes[2], bytes[3]]) as usize * 4;
+        ret.push(&bytes[..length]);
+        bytes = &bytes[length..];
+    }
+    ret
+}
+
+#[test]
+fn incremental_selection_transfers() {
+    let atoms = selection_atoms();
+    let mut server_bytes = property_notify(0x200, 0);
+    server_bytes.extend(reply_bytes(3, &[0, 0, 2, 0]));
+    let mut conn = connection(server_bytes);
+    let mut owner = SelectionOwner::new(0x200, atoms);
+    owner.set_data(70, vec![7; 300_000]);
+    assert!(owner.acquire(&mut conn, atoms.clipboard).unwrap());
+
+    let mut handle = |conn: &mut Connection<MockStream>, event: &Event| {
+        conn.flush().unwrap();
+        let start = conn.get_ref().written().len();
+        assert!(owner.handle_event(conn, event).unwrap());
+        conn.flush().unwrap();
+        let written = conn.get_ref().written()[start..].to_vec();
+        (written, owner.is_transferring())
+    };
+
+    let request = Event::SelectionRequest {
+        sequence: 0,
+        time: 1001,
+        owner: 0x200,
+        requestor: 0x300,
+        selection: atoms.clipboard,
+        target: 70,
+        property: 100,
+    };
+    let (written, transferring) = handle(&mut conn, &request);
+    assert!(transferring);
+    let requests = split_requests(&written);
+    assert_eq!(
+        requests[0],
+        [2, 0, 0, 4, 0, 0, 3, 0, 0, 0, 8, 0, 0, 0x40, 0, 0]
+    );
+    assert_eq!(requests[1][..4], [18, 0, 0, 7]);
+    assert_eq!(
+        requests[1][12..],
+        [0, 0, 0, 57, 32, 0, 0, 0, 0, 0, 0, 1, 0, 4, 0x93, 0xe0]
+    );
+    assert_eq!(requests[2][0], 25);
+
+    // Each deletion of the property by the requestor brings the next chunk.
+    let deleted = Event::from_bytes(&{
+        let mut bytes = [0; 32];
+        bytes.copy_from_slice(&property_notify(0x300, 1));
+        bytes
+    });
+    let mut lengths = Vec::new();
+    loop {
+        let (written, transferring) = handle(&mut conn, &deleted);
+        let requests = split_requests(&written);
+        assert_eq!(requests[0][12..17], [0, 0, 0, 70, 8]);
+        lengths.push(u32::from_be_bytes([
+            requests[0][20],
+            requests[0][21],
+            requests[0][22],
+            requests[0][23],
+        ]));
+        if !transferring {
+            // Done with the requestor's window.
+            assert_eq!(requests[1][..4], [2, 0, 0, 4]);
+            assert_eq!(requests[1][12..], [0, 0, 0, 0]);
+            break;
+        }
+    }
+    assert_eq!(lengths, [262_116, 37_884, 0]);
+
+    let mut server_bytes = selection_notify();
+    server_bytes.extend(property_reply(2, 32, 57, &[0, 0, 0, 11]));
+    server_bytes.extend(property_notify(0x300, 0));
+    server_bytes.extend(property_reply(3, 8, 55, b"hello "));
+    server_bytes.extend(property_notify(0x300, 0));
+    server_bytes.extend(property_reply(4, 8, 55, b"world"));
+    server_bytes.extend(property_notify(0x300, 0));
+    server_bytes.extend(property_reply(5, 8, 55, b""));
+    let mut conn = connection(server_bytes);
+
+    let text = selection_text(&mut conn, 0x300, &atoms, atoms.clipboard).unwrap();
+    assert_eq!(text.as_deref(), Some("hello world"));
+}
+
+#[test]
+fn selection_timeouts() {
+    let atoms = selection_atoms();
+
+    // Nobody answers the conversion.
+    let mut conn = connection(Vec::new());
+    let error = convert_selection(&mut conn, 0x300, &atoms, atoms.clipboard, 55, 0).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+    assert!(conn
+        .next_event_timeout(std::time::Duration::from_secs(1))
+        .unwrap()
+        .is_none());
+
+    // The owner starts an incremental transfer and never sends the first chunk.
+    let mut server_bytes = selection_notify();
+    server_bytes.extend(property_reply(2, 32, 57, &[0, 1, 0, 0]));
+    let mut conn = connection(server_bytes);
+    let error = convert_selection(&mut conn, 0x300, &atoms, atoms.clipboard, 55, 0).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+
+    // A requestor that stops deleting the property has its transfer dropped.
+    let mut server_bytes = property_notify(0x200, 0);
+    server_bytes.extend(reply_bytes(3, &[0, 0, 2, 0]));
+    let mut conn = connection(server_bytes);
+    let mut owner = SelectionOwner::new(0x200, atoms);
+    owner.set_data(70, vec![7; 300_000]);
+    owner.set_timeout(std::time::Duration::ZERO);
+    assert!(owner.acquire(&mut conn, atoms.clipboard).unwrap());
+    let request = Event::SelectionRequest {
+        sequence: 0,
+        time: 1001,
+        owner: 0x200,
+        requestor: 0x300,
+        selection: atoms.clipboard,
+        target: 70,
+        property: 100,
+    };
+    assert!(owner.handle_event(&mut conn, &request).unwrap());
+    assert!(owner.is_transferring());
+
+    conn.flush().unwrap();
+    let start = conn.get_ref().written().len();
+    owner.expire_transfers(&mut conn).unwrap();
+    assert!(!owner.is_transferring());
+    conn.flush().unwrap();
+    // No longer interested in the requestor's window.
+    assert_eq!(
+        conn.get_ref().written()[start..],
+        [2, 0, 0, 4, 0, 0, 3, 0, 0, 0, 8, 0, 0, 0, 0, 0]
+    );
+}
+
+#[test]
+fn list_properties_and_decode_wm_hints() {
+    let mut reply = reply_bytes(1, &[0, 2]);
+    reply[7] = 2;
+    reply.extend_from_slice(&[0, 0, 0, 39, 0, 0, 0, 40]);
+    let mut conn = connection(reply);
+    let list = conn
+        .request(&ListProperties::new(0x200).as_bytes())
+        .unwrap();
+    let list = ListPropertiesReply::from_bytes(&list).unwrap();
+    assert_eq!(list.atoms, [atom::WM_NAME, atom::WM_NORMAL_HINTS]);
+    assert_eq!(atom::name(atom::WM_NORMAL_HINTS), Some("WM_NORMAL_HINTS"));
+    assert_eq!(atom::name(69), None);
+
+    let property = |property_type, values: &[u32]| GetPropertyReply {
+        format: 32,
+        property_type,
+        bytes_after: 0,
+        value: values
+            .iter()
+            .flat_map(|value| value.to_be_bytes())
+            .collect(),
+    };
+
+    let hints = property(atom::WM_HINTS, &[0x143, 1, 1, 0, 0, 0, 0, 0, 0x400001]);
+    assert_eq!(
+        WmHints::from_property(&hints),
+        Some(WmHints {
+            input: Some(true),
+            initial_state: Some(1),
+            window_group: Some(0x400001),
+            urgent: true,
+            ..Default::default()
+        })
+    );
+
+    // Program-specified size, minimum size, resize increments, base size and gravity.
+    let mut values = [0; 18];
+    values[0] = 0x8 | 0x10 | 0x40 | 0x100 | 0x200;
+    values[3..11].copy_from_slice(&[484, 316, 10, 17, 0, 0, 6, 13]);
+    values[15..18].copy_from_slice(&[4, 4, 1]);
+    let size_hints = WmSizeHints::from_property(&property(atom::WM_SIZE_HINTS, &values));
+    assert_eq!(
+        size_hints,
+        Some(WmSizeHints {
+            size: Some((484, 316, false)),
+            min_size: Some((10, 17)),
+            resize_inc: Some((6, 13)),
+            base_size: Some((4, 4)),
+            win_gravity: Some(1),
+            ..Default::default()
+        })
+    );
+    assert_eq!(
+        WmSizeHints::from_property(&property(atom::WM_SIZE_HINTS, &[0; 4])),
+        None
+    );
+}
+
+#[test]
+fn list_extensions_and_translate_coordinates() {
+    let mut extensions = reply_bytes(1, &[]);
+    extensions[1] = 2;
+    extensions[7] = 5;
+    extensions.extend_from_slice(b"\x0cBIG-REQUESTS\x06RENDER");
+    let mut translate = reply_bytes(2, &[0, 0, 0, 0, 0xff, 0xff, 0, 20]);
+    translate[1] = 1;
+    extensions.extend_from_slice(&translate);
+    let mut conn = connection(extensions);
+
+    let list = conn.request(&ListExtensions::new().as_bytes()).unwrap();
+    let list = ListExtensionsReply::from_bytes(&list).unwrap();
+    assert_eq!(list.names, ["BIG-REQUESTS", "RENDER"]);
+
+    let request = TranslateCoordinates::new(0x400001, 0x100, -1, -1);
+    let reply = conn.request(&request.as_bytes()).unwrap();
+    let reply = TranslateCoordinatesReply::from_bytes(&reply).unwrap();
+    assert!(reply.same_screen);
+    assert_eq!(reply.child, 0);
+    assert_eq!((reply.dst_x, reply.dst_y), (-1, 20));
+
+    let written = conn.get_ref().written();
+    assert_eq!(&written[written.len() - 16..][..4], [40, 0, 0, 4]);
+    assert_eq!(&written[written.len() - 4..], [0xff, 0xff, 0xff, 0xff]);
+}
+
+#[test]
+fn destroyed_windows_are_skipped() {
+    let error = |code, sequence: u16, major| {
+        let mut ret = vec![0, code];
+        ret.extend_from_slice(&sequence.to_be_bytes());
+        ret.extend_from_slice(&[0, 0, 3, 0, 0, 0, major]);
+        ret.resize(32, 0);
+        ret
+    };
+    let mut server_bytes = error(XError::BAD_WINDOW, 1, 15);
+    server_bytes.extend(error(5, 2, 20)); // BadAtom
+    let mut conn = connection(server_bytes);
+
+    let tree = conn.request(&QueryTree::new(0x300).as_bytes());
+    assert!(unless_destroyed(tree).unwrap().is_none());
+    let property = GetProperty::new(false, 0x300, 0, 0, 0, 1024);
+    let error = unless_destroyed(conn.request(&property.as_bytes())).unwrap_err();
+    assert_eq!(XError::from_io_error(&error).unwrap().code, 5);
+}
+
+#[test]
+fn decode_input_and_structure_events() {
+    let mut button = [0u8; 32];
+    button[..4].copy_from_slice(&[4, 3, 0, 7]);
+    button[12..16].copy_from_slice(&[0, 0x40, 0, 1]);
+    button[24..28].copy_from_slice(&[0, 10, 0xff, 0xfe]);
+    button[28..31].copy_from_slice(&[0, 0x4, 1]);
+    match Event::from_bytes(&button) {
+        Event::ButtonPress {
+            detail: 3,
+            sequence: 7,
+            event: 0x400001,
+            event_x: 10,
+            event_y: -2,
+            state: 0x4,
+            same_screen: true,
+            ..
+        } => {}
+        event => panic!("unexpected {:?}", event),
+    }
+
+    let mut enter = [0u8; 32];
+    enter[..2].copy_from_slice(&[7, 3]);
+    enter[30..32].copy_from_slice(&[1, 0x3]);
+    match Event::from_bytes(&enter) {
+        Event::EnterNotify {
+            detail: 3,
+            mode: 1,
+            same_screen: true,
+            focus: true,
+            ..
+        } => {}
+        event => panic!("unexpected {:?}", event),
+    }
+
+    let mut configure = [0u8; 32];
+    configure[0] = 22;
+    configure[8..12].copy_from_slice(&[0, 0x40, 0, 1]);
+    configure[16..26].copy_from_slice(&[0xff, 0xf6, 0, 20, 1, 0x90, 0, 200, 0, 2]);
+    configure[26] = 1;
+    match Event::from_bytes(&configure) {
+        Event::ConfigureNotify {
+            window: 0x400001,
+            above_sibling: 0,
+            x: -10,
+            y: 20,
+            width: 400,
+            height: 200,
+            border_width: 2,
+            override_redirect: true,
+            ..
+        } => {}
+        event => panic!("unexpected {:?}", event),
+    }
+
+    // As sent with SendEvent.
+    let mut message = [0u8; 32];
+    message[..2].copy_from_slice(&[33 | 0x80, 32]);
+    message[4..12].copy_from_slice(&[0, 0x40, 0, 1, 0, 0, 1, 0x2c]);
+    message[12..16].copy_from_slice(&[0, 0, 1, 0x2d]);
+    match Event::from_bytes(&message) {
+        Event::ClientMessage {
+            format: 32,
+            window: 0x400001,
+            message_type: 0x12c,
+            data,
+            ..
+        } => assert_eq!(data[..4], [0, 0, 1, 0x2d]),
+        event => panic!("unexpected {:?}", event),
+    }
+}
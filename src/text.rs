@@ -0,0 +1,288 @@
+use std::io::{Error, ErrorKind, Result};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::request_length;
+
+pub struct QueryTextExtents<'a> {
+    fontable: u32,
+    string: &'a [u16],
+}
+
+impl<'a> QueryTextExtents<'a> {
+    /// `fontable` may be a font or a GC, in which case the GC's current font is used.
+    pub fn new(fontable: u32, string: &'a [u16]) -> Self {
+        QueryTextExtents { fontable, string }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+        let odd_length = self.string.len() % 2 == 1;
+
+        ret.write_u8(48).unwrap();
+        ret.write_u8(odd_length as u8).unwrap();
+        ret.write_u16::<BigEndian>(request_length(8 + self.string.len().div_ceil(2) * 4))
+            .unwrap();
+        ret.write_u32::<BigEndian>(self.fontable).unwrap();
+        for &c in self.string {
+            ret.write_u16::<BigEndian>(c).unwrap();
+        }
+        if odd_length {
+            ret.write_u16::<BigEndian>(0).unwrap();
+        }
+
+        ret
+    }
+}
+
+#[derive(Debug)]
+pub struct QueryTextExtentsReply {
+    pub draw_direction: u8,
+    pub font_ascent: i16,
+    pub font_descent: i16,
+    pub overall_ascent: i16,
+    pub overall_descent: i16,
+    pub overall_width: i32,
+    pub overall_left: i32,
+    pub overall_right: i32,
+}
+
+impl QueryTextExtentsReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[1..]);
+
+        let draw_direction = buf.read_u8()?;
+        buf.set_position(7);
+        let font_ascent = buf.read_i16::<BigEndian>()?;
+        let font_descent = buf.read_i16::<BigEndian>()?;
+        let overall_ascent = buf.read_i16::<BigEndian>()?;
+        let overall_descent = buf.read_i16::<BigEndian>()?;
+        let overall_width = buf.read_i32::<BigEndian>()?;
+        let overall_left = buf.read_i32::<BigEndian>()?;
+        let overall_right = buf.read_i32::<BigEndian>()?;
+
+        Ok(QueryTextExtentsReply {
+            draw_direction,
+            font_ascent,
+            font_descent,
+            overall_ascent,
+            overall_descent,
+            overall_width,
+            overall_left,
+            overall_right,
+        })
+    }
+}
+
+/// Converts a string to CHAR2B characters for drawing with an ISO10646-1 font.
+///
+/// Characters outside the Basic Multilingual Plane cannot be represented and are replaced with
+/// U+FFFD.
+pub fn char2b_from_str(string: &str) -> Vec<u16> {
+    string
+        .chars()
+        .map(|c| {
+            if (c as u32) <= 0xffff {
+                c as u16
+            } else {
+                0xfffd
+            }
+        })
+        .collect()
+}
+
+/// An item of a `PolyText8` or `PolyText16` request.
+#[derive(Clone, Copy, Debug)]
+pub enum TextItem<'a, T> {
+    /// Draws `string` after moving `delta` pixels along the baseline.
+    Text { delta: i8, string: &'a [T] },
+    /// Switches the GC's font for the rest of the request, and afterwards.
+    Font(u32),
+}
+
+fn write_text_items<T: Copy, F>(ret: &mut Vec<u8>, items: &[TextItem<T>], mut write_char: F)
+where
+    F: FnMut(&mut Vec<u8>, T),
+{
+    for item in items {
+        match *item {
+            TextItem::Text { delta, string } => {
+                // Each element holds at most 254 characters; the delta applies only to the first.
+                let mut chunks = string.chunks(254);
+                let first = chunks.next().unwrap_or(&[]);
+                for (i, chunk) in std::iter::once(first).chain(chunks).enumerate() {
+                    ret.write_u8(chunk.len() as u8).unwrap();
+                    ret.write_i8(if i == 0 { delta } else { 0 }).unwrap();
+                    for &c in chunk {
+                        write_char(ret, c);
+                    }
+                }
+            }
+            TextItem::Font(font) => {
+                ret.write_u8(255).unwrap();
+                ret.write_u32::<BigEndian>(font).unwrap();
+            }
+        }
+    }
+    let padding = (4 - (ret.len() % 4)) % 4;
+    for _ in 0..padding {
+        ret.write_u8(0).unwrap();
+    }
+
+    let length = request_length(ret.len());
+    ret[2..4].copy_from_slice(&length.to_be_bytes());
+}
+
+fn text_header(opcode: u8, data: u8, drawable: u32, gc: u32, x: i16, y: i16) -> Vec<u8> {
+    let mut ret = Vec::new();
+
+    ret.write_u8(opcode).unwrap();
+    ret.write_u8(data).unwrap();
+    ret.write_u16::<BigEndian>(0).unwrap();
+    ret.write_u32::<BigEndian>(drawable).unwrap();
+    ret.write_u32::<BigEndian>(gc).unwrap();
+    ret.write_i16::<BigEndian>(x).unwrap();
+    ret.write_i16::<BigEndian>(y).unwrap();
+
+    ret
+}
+
+pub struct PolyText8<'a> {
+    drawable: u32,
+    gc: u32,
+    x: i16,
+    y: i16,
+    items: &'a [TextItem<'a, u8>],
+}
+
+impl<'a> PolyText8<'a> {
+    pub fn new(drawable: u32, gc: u32, x: i16, y: i16, items: &'a [TextItem<'a, u8>]) -> Self {
+        PolyText8 {
+            drawable,
+            gc,
+            x,
+            y,
+            items,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = text_header(74, 0, self.drawable, self.gc, self.x, self.y);
+        write_text_items(&mut ret, self.items, |ret, c| ret.write_u8(c).unwrap());
+        ret
+    }
+}
+
+pub struct PolyText16<'a> {
+    drawable: u32,
+    gc: u32,
+    x: i16,
+    y: i16,
+    items: &'a [TextItem<'a, u16>],
+}
+
+impl<'a> PolyText16<'a> {
+    pub fn new(drawable: u32, gc: u32, x: i16, y: i16, items: &'a [TextItem<'a, u16>]) -> Self {
+        PolyText16 {
+            drawable,
+            gc,
+            x,
+            y,
+            items,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = text_header(75, 0, self.drawable, self.gc, self.x, self.y);
+        write_text_items(&mut ret, self.items, |ret, c| {
+            ret.write_u16::<BigEndian>(c).unwrap()
+        });
+        ret
+    }
+}
+
+fn check_image_text_length(len: usize) -> Result<()> {
+    if len > 255 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "image text is limited to 255 characters",
+        ));
+    }
+    Ok(())
+}
+
+/// Draws text over a background filled with the GC's background color.
+pub struct ImageText8<'a> {
+    drawable: u32,
+    gc: u32,
+    x: i16,
+    y: i16,
+    string: &'a [u8],
+}
+
+impl<'a> ImageText8<'a> {
+    /// Fails if `string` is longer than 255 characters.
+    pub fn new(drawable: u32, gc: u32, x: i16, y: i16, string: &'a [u8]) -> Result<Self> {
+        check_image_text_length(string.len())?;
+        Ok(ImageText8 {
+            drawable,
+            gc,
+            x,
+            y,
+            string,
+        })
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let n = self.string.len();
+        let mut ret = text_header(76, n as u8, self.drawable, self.gc, self.x, self.y);
+        ret.extend_from_slice(self.string);
+        let padding = (4 - (ret.len() % 4)) % 4;
+        for _ in 0..padding {
+            ret.write_u8(0).unwrap();
+        }
+
+        let length = request_length(ret.len());
+        ret[2..4].copy_from_slice(&length.to_be_bytes());
+        ret
+    }
+}
+
+/// Like `ImageText8`, with CHAR2B characters.
+pub struct ImageText16<'a> {
+    drawable: u32,
+    gc: u32,
+    x: i16,
+    y: i16,
+    string: &'a [u16],
+}
+
+impl<'a> ImageText16<'a> {
+    /// Fails if `string` is longer than 255 characters.
+    pub fn new(drawable: u32, gc: u32, x: i16, y: i16, string: &'a [u16]) -> Result<Self> {
+        check_image_text_length(string.len())?;
+        Ok(ImageText16 {
+            drawable,
+            gc,
+            x,
+            y,
+            string,
+        })
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let n = self.string.len();
+        let mut ret = text_header(77, n as u8, self.drawable, self.gc, self.x, self.y);
+        for &c in self.string {
+            ret.write_u16::<BigEndian>(c).unwrap();
+        }
+        let padding = (4 - (ret.len() % 4)) % 4;
+        for _ in 0..padding {
+            ret.write_u8(0).unwrap();
+        }
+
+        let length = request_length(ret.len());
+        ret[2..4].copy_from_slice(&length.to_be_bytes());
+        ret
+    }
+}
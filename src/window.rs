@@ -0,0 +1,304 @@
+use std::io::{Read, Result, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{atom, Connection, GetProperty, GetPropertyReply, XError};
+
+pub struct GetWindowAttributes {
+    window: u32,
+}
+
+impl GetWindowAttributes {
+    pub fn new(window: u32) -> Self {
+        GetWindowAttributes { window }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(3).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(2).unwrap();
+        ret.write_u32::<BigEndian>(self.window).unwrap();
+
+        ret
+    }
+}
+
+#[derive(Debug)]
+pub struct GetWindowAttributesReply {
+    pub backing_store: u8,
+    pub visual: u32,
+    pub class: u16,
+    pub bit_gravity: u8,
+    pub win_gravity: u8,
+    pub backing_planes: u32,
+    pub backing_pixel: u32,
+    pub save_under: bool,
+    pub map_is_installed: bool,
+    pub map_state: u8,
+    pub override_redirect: bool,
+    pub colormap: u32,
+    pub all_event_masks: u32,
+    pub your_event_mask: u32,
+    pub do_not_propagate_mask: u16,
+}
+
+impl GetWindowAttributesReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[1..]);
+
+        let backing_store = buf.read_u8()?;
+        buf.set_position(7);
+        let visual = buf.read_u32::<BigEndian>()?;
+        let class = buf.read_u16::<BigEndian>()?;
+        let bit_gravity = buf.read_u8()?;
+        let win_gravity = buf.read_u8()?;
+        let backing_planes = buf.read_u32::<BigEndian>()?;
+        let backing_pixel = buf.read_u32::<BigEndian>()?;
+        let save_under = buf.read_u8()? != 0;
+        let map_is_installed = buf.read_u8()? != 0;
+        let map_state = buf.read_u8()?;
+        let override_redirect = buf.read_u8()? != 0;
+        let colormap = buf.read_u32::<BigEndian>()?;
+        let all_event_masks = buf.read_u32::<BigEndian>()?;
+        let your_event_mask = buf.read_u32::<BigEndian>()?;
+        let do_not_propagate_mask = buf.read_u16::<BigEndian>()?;
+
+        Ok(GetWindowAttributesReply {
+            backing_store,
+            visual,
+            class,
+            bit_gravity,
+            win_gravity,
+            backing_planes,
+            backing_pixel,
+            save_under,
+            map_is_installed,
+            map_state,
+            override_redirect,
+            colormap,
+            all_event_masks,
+            your_event_mask,
+            do_not_propagate_mask,
+        })
+    }
+}
+
+pub struct GetGeometry {
+    drawable: u32,
+}
+
+impl GetGeometry {
+    pub fn new(drawable: u32) -> Self {
+        GetGeometry { drawable }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(14).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(2).unwrap();
+        ret.write_u32::<BigEndian>(self.drawable).unwrap();
+
+        ret
+    }
+}
+
+#[derive(Debug)]
+pub struct GetGeometryReply {
+    pub depth: u8,
+    pub root: u32,
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub border_width: u16,
+}
+
+impl GetGeometryReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[1..]);
+
+        let depth = buf.read_u8()?;
+        buf.set_position(7);
+        let root = buf.read_u32::<BigEndian>()?;
+        let x = buf.read_i16::<BigEndian>()?;
+        let y = buf.read_i16::<BigEndian>()?;
+        let width = buf.read_u16::<BigEndian>()?;
+        let height = buf.read_u16::<BigEndian>()?;
+        let border_width = buf.read_u16::<BigEndian>()?;
+
+        Ok(GetGeometryReply {
+            depth,
+            root,
+            x,
+            y,
+            width,
+            height,
+            border_width,
+        })
+    }
+}
+
+pub struct QueryTree {
+    window: u32,
+}
+
+impl QueryTree {
+    pub fn new(window: u32) -> Self {
+        QueryTree { window }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(15).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(2).unwrap();
+        ret.write_u32::<BigEndian>(self.window).unwrap();
+
+        ret
+    }
+}
+
+#[derive(Debug)]
+pub struct QueryTreeReply {
+    pub root: u32,
+    /// The parent window, or 0 (None) for a root window.
+    pub parent: u32,
+    /// Children in stacking order, bottom-most first.
+    pub children: Vec<u32>,
+}
+
+impl QueryTreeReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let root = buf.read_u32::<BigEndian>()?;
+        let parent = buf.read_u32::<BigEndian>()?;
+        let children_count = buf.read_u16::<BigEndian>()?;
+        buf.set_position(32);
+        let mut children = Vec::new();
+        for _ in 0..children_count {
+            children.push(buf.read_u32::<BigEndian>()?);
+        }
+
+        Ok(QueryTreeReply {
+            root,
+            parent,
+            children,
+        })
+    }
+}
+
+/// Translates a position relative to one window's origin into one relative to another's.
+pub struct TranslateCoordinates {
+    src_window: u32,
+    dst_window: u32,
+    src_x: i16,
+    src_y: i16,
+}
+
+impl TranslateCoordinates {
+    pub fn new(src_window: u32, dst_window: u32, src_x: i16, src_y: i16) -> Self {
+        TranslateCoordinates {
+            src_window,
+            dst_window,
+            src_x,
+            src_y,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+
+        ret.write_u8(40).unwrap();
+        ret.write_u8(0).unwrap();
+        ret.write_u16::<BigEndian>(4).unwrap();
+        ret.write_u32::<BigEndian>(self.src_window).unwrap();
+        ret.write_u32::<BigEndian>(self.dst_window).unwrap();
+        ret.write_i16::<BigEndian>(self.src_x).unwrap();
+        ret.write_i16::<BigEndian>(self.src_y).unwrap();
+
+        ret
+    }
+}
+
+#[derive(Debug)]
+pub struct TranslateCoordinatesReply {
+    /// False if the windows are on different screens, in which case the rest is zero.
+    pub same_screen: bool,
+    /// The child of the destination window containing the position, or 0 (None).
+    pub child: u32,
+    pub dst_x: i16,
+    pub dst_y: i16,
+}
+
+impl TranslateCoordinatesReply {
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let same_screen = data[1] != 0;
+        let mut buf = std::io::Cursor::new(&data[8..]);
+
+        let child = buf.read_u32::<BigEndian>()?;
+        let dst_x = buf.read_i16::<BigEndian>()?;
+        let dst_y = buf.read_i16::<BigEndian>()?;
+
+        Ok(TranslateCoordinatesReply {
+            same_screen,
+            child,
+            dst_x,
+            dst_y,
+        })
+    }
+}
+
+/// Turns the BadWindow error of a request about a window that has been destroyed into `None`,
+/// for callers that walk the window tree while other clients change it.
+pub fn unless_destroyed<T>(result: Result<T>) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) => match XError::from_io_error(&e) {
+            Some(error) if error.code == XError::BAD_WINDOW => Ok(None),
+            _ => Err(e),
+        },
+    }
+}
+
+/// Searches every screen's window tree for a window whose WM_NAME is `name`.
+pub fn find_window_by_name<S: Read + Write>(
+    conn: &mut Connection<S>,
+    name: &str,
+) -> Result<Option<u32>> {
+    let mut pending = conn
+        .server_init()
+        .roots
+        .iter()
+        .map(|screen| screen.root)
+        .collect::<Vec<_>>();
+
+    while let Some(window) = pending.pop() {
+        // Windows can be destroyed after their parent has listed them.
+        let wm_name = GetProperty::new(false, window, atom::WM_NAME, 0, 0, 1024);
+        let reply = conn
+            .request(&wm_name.as_bytes())
+            .and_then(|reply| GetPropertyReply::from_bytes(&reply));
+        let reply = match unless_destroyed(reply)? {
+            Some(reply) => reply,
+            None => continue,
+        };
+        if reply.format == 8 && reply.value == name.as_bytes() {
+            return Ok(Some(window));
+        }
+
+        let tree = conn
+            .request(&QueryTree::new(window).as_bytes())
+            .and_then(|tree| QueryTreeReply::from_bytes(&tree));
+        if let Some(tree) = unless_destroyed(tree)? {
+            pending.extend(tree.children);
+        }
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,338 @@
+//! Loading cursors from Xcursor themes, as found in `~/.icons` and `/usr/share/icons`.
+
+use std::io::{self, Read, Result, Write};
+use std::path::{Path, PathBuf};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::image::{Image, ImageLayout};
+use crate::{
+    create_font_cursor, cursor_font, AnimCursorElt, Connection, CreateGc, CreatePixmap, FreeCursor,
+    FreeGc, FreePixmap, QueryExtension, QueryExtensionReply, RenderCreateAnimCursor,
+    RenderCreateCursor, RenderCreatePicture, RenderFreePicture, RenderQueryPictFormats,
+    RenderQueryPictFormatsReply, RenderQueryVersion, RenderQueryVersionReply, Screen,
+};
+
+const IMAGE_TYPE: u32 = 0xfffd_0002;
+const DEFAULT_PATH: &str = "~/.local/share/icons:~/.icons:/usr/share/icons:/usr/share/pixmaps";
+
+/// One image of an Xcursor file. Pixels are premultiplied ARGB, row by row.
+#[derive(Clone, Debug)]
+pub struct CursorImage {
+    pub nominal_size: u32,
+    pub width: u16,
+    pub height: u16,
+    pub xhot: u16,
+    pub yhot: u16,
+    /// How long to show this frame of an animation, in milliseconds.
+    pub delay: u32,
+    pub pixels: Vec<u32>,
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Parses every image in an Xcursor file, in file order.
+pub fn parse_xcursor(data: &[u8]) -> Result<Vec<CursorImage>> {
+    let mut buf = io::Cursor::new(data);
+    if buf.read_u32::<LittleEndian>()? != 0x7275_6358 {
+        return Err(invalid_data("not an Xcursor file"));
+    }
+    let header_size = buf.read_u32::<LittleEndian>()?;
+    buf.read_u32::<LittleEndian>()?;
+    let toc_count = buf.read_u32::<LittleEndian>()?;
+    buf.set_position(header_size as u64);
+
+    let mut toc = Vec::new();
+    for _ in 0..toc_count {
+        let chunk_type = buf.read_u32::<LittleEndian>()?;
+        buf.read_u32::<LittleEndian>()?;
+        let position = buf.read_u32::<LittleEndian>()?;
+        if chunk_type == IMAGE_TYPE {
+            toc.push(position);
+        }
+    }
+
+    let mut ret = Vec::new();
+    for position in toc {
+        buf.set_position(position as u64);
+        let header_size = buf.read_u32::<LittleEndian>()?;
+        if buf.read_u32::<LittleEndian>()? != IMAGE_TYPE {
+            return Err(invalid_data(
+                "Xcursor table of contents points at the wrong chunk",
+            ));
+        }
+        let nominal_size = buf.read_u32::<LittleEndian>()?;
+        buf.read_u32::<LittleEndian>()?;
+        let width = buf.read_u32::<LittleEndian>()?;
+        let height = buf.read_u32::<LittleEndian>()?;
+        let xhot = buf.read_u32::<LittleEndian>()?;
+        let yhot = buf.read_u32::<LittleEndian>()?;
+        let delay = buf.read_u32::<LittleEndian>()?;
+        if width > 0x7fff || height > 0x7fff || xhot > width || yhot > height {
+            return Err(invalid_data("Xcursor image has invalid dimensions"));
+        }
+
+        let start = position as u64 + header_size as u64;
+        let available = (data.len() as u64).saturating_sub(start);
+        if width as u64 * height as u64 * 4 > available {
+            return Err(invalid_data("Xcursor image is truncated"));
+        }
+        buf.set_position(start);
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for _ in 0..width * height {
+            pixels.push(buf.read_u32::<LittleEndian>()?);
+        }
+        ret.push(CursorImage {
+            nominal_size,
+            width: width as u16,
+            height: height as u16,
+            xhot: xhot as u16,
+            yhot: yhot as u16,
+            delay,
+            pixels,
+        });
+    }
+
+    Ok(ret)
+}
+
+/// Keeps the images of the nominal size closest to `size`, which are the frames of the cursor.
+pub fn select_nominal_size(images: Vec<CursorImage>, size: u32) -> Vec<CursorImage> {
+    let Some(best) = images
+        .iter()
+        .map(|image| image.nominal_size)
+        .min_by_key(|nominal_size| nominal_size.abs_diff(size))
+    else {
+        return Vec::new();
+    };
+
+    images
+        .into_iter()
+        .filter(|image| image.nominal_size == best)
+        .collect()
+}
+
+/// The directories searched for themes: `XCURSOR_PATH`, or the same default as libXcursor.
+pub fn cursor_search_path() -> Vec<PathBuf> {
+    let path = std::env::var("XCURSOR_PATH").unwrap_or_else(|_| DEFAULT_PATH.to_string());
+    let home = std::env::var("HOME").ok();
+
+    path.split(':')
+        .filter(|dir| !dir.is_empty())
+        .filter_map(|dir| match dir.strip_prefix("~/") {
+            Some(rest) => home.as_ref().map(|home| Path::new(home).join(rest)),
+            None => Some(PathBuf::from(dir)),
+        })
+        .collect()
+}
+
+/// Reads the `Inherits` list from a theme's `index.theme`.
+fn inherited_themes(theme_dir: &Path) -> Vec<String> {
+    let Ok(index) = std::fs::read_to_string(theme_dir.join("index.theme")) else {
+        return Vec::new();
+    };
+
+    index
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            (key.trim() == "Inherits").then_some(value)
+        })
+        .flat_map(|value| value.split([',', ';']))
+        .map(|theme| theme.trim().to_string())
+        .filter(|theme| !theme.is_empty())
+        .collect()
+}
+
+fn find_in_theme(
+    search_path: &[PathBuf],
+    theme: &str,
+    name: &str,
+    visited: &mut Vec<String>,
+) -> Option<PathBuf> {
+    if visited.iter().any(|visited| visited == theme) {
+        return None;
+    }
+    visited.push(theme.to_string());
+
+    for dir in search_path {
+        let file = dir.join(theme).join("cursors").join(name);
+        if file.is_file() {
+            return Some(file);
+        }
+    }
+    for dir in search_path {
+        for parent in inherited_themes(&dir.join(theme)) {
+            if let Some(file) = find_in_theme(search_path, &parent, name, visited) {
+                return Some(file);
+            }
+        }
+    }
+
+    None
+}
+
+/// Finds the file for cursor `name` in `theme` or the themes it inherits from, falling back to
+/// the `default` theme.
+pub fn find_cursor_file(theme: &str, name: &str) -> Option<PathBuf> {
+    let search_path = cursor_search_path();
+    let mut visited = Vec::new();
+
+    find_in_theme(&search_path, theme, name, &mut visited)
+        .or_else(|| find_in_theme(&search_path, "default", name, &mut visited))
+}
+
+/// The nominal cursor size: `XCURSOR_SIZE`, or a 48th of the smaller screen dimension.
+pub fn cursor_size(screen: &Screen) -> u32 {
+    std::env::var("XCURSOR_SIZE")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .filter(|&size| size > 0)
+        .unwrap_or_else(|| {
+            std::cmp::max(
+                std::cmp::min(screen.width_pixels, screen.height_pixels) as u32 / 48,
+                1,
+            )
+        })
+}
+
+/// What the server's RENDER extension offers for cursors.
+struct RenderCursors {
+    major_opcode: u8,
+    argb32_format: u32,
+    animated: bool,
+}
+
+/// Checks for RENDER 0.5, which added ARGB cursors, and its ARGB32 picture format.
+fn render_cursors<S: Read + Write>(conn: &mut Connection<S>) -> Result<Option<RenderCursors>> {
+    let query = QueryExtension::new("RENDER".into());
+    let extension = QueryExtensionReply::from_bytes(&conn.request(&query.as_bytes())?)?;
+    if !extension.present {
+        return Ok(None);
+    }
+    let major_opcode = extension.major_opcode;
+
+    let version = RenderQueryVersion::new(major_opcode, 0, 11);
+    let version = RenderQueryVersionReply::from_bytes(&conn.request(&version.as_bytes())?)?;
+    let version = (version.major_version, version.minor_version);
+    if version < (0, 5) {
+        return Ok(None);
+    }
+
+    let formats = RenderQueryPictFormats::new(major_opcode);
+    let formats = RenderQueryPictFormatsReply::from_bytes(&conn.request(&formats.as_bytes())?)?;
+    let Some(format) = formats.formats.iter().find(|format| format.is_argb32()) else {
+        return Ok(None);
+    };
+
+    Ok(Some(RenderCursors {
+        major_opcode,
+        argb32_format: format.id,
+        animated: version >= (0, 8),
+    }))
+}
+
+/// Uploads one image into a depth 32 pixmap and creates a cursor from it.
+fn create_image_cursor<S: Read + Write>(
+    conn: &mut Connection<S>,
+    render: &RenderCursors,
+    root: u32,
+    image: &CursorImage,
+) -> Result<u32> {
+    let layout = ImageLayout::new(conn.server_init(), 32, None)?;
+    let data = Image::from_pixels(&layout, &image.pixels, image.width, image.height)?;
+
+    let pixmap = conn.generate_id()?;
+    conn.send(&CreatePixmap::new(32, pixmap, root, image.width, image.height).as_bytes())?;
+    let gc = conn.generate_id()?;
+    conn.send(&CreateGc::new(gc, pixmap, 0).as_bytes())?;
+    data.put(conn, pixmap, gc, 0, 0)?;
+    conn.send(&FreeGc::new(gc).as_bytes())?;
+
+    let picture = conn.generate_id()?;
+    let create_picture =
+        RenderCreatePicture::new(render.major_opcode, picture, pixmap, render.argb32_format);
+    conn.send(&create_picture.as_bytes())?;
+    let cursor = conn.generate_id()?;
+    let create_cursor =
+        RenderCreateCursor::new(render.major_opcode, cursor, picture, image.xhot, image.yhot);
+    conn.send(&create_cursor.as_bytes())?;
+    conn.send(&RenderFreePicture::new(render.major_opcode, picture).as_bytes())?;
+    conn.send(&FreePixmap::new(pixmap).as_bytes())?;
+
+    Ok(cursor)
+}
+
+fn load_theme_cursor<S: Read + Write>(
+    conn: &mut Connection<S>,
+    screen: usize,
+    name: &str,
+) -> Result<Option<u32>> {
+    let Some(render) = render_cursors(conn)? else {
+        return Ok(None);
+    };
+    let theme = std::env::var("XCURSOR_THEME").unwrap_or_else(|_| "default".to_string());
+    let Some(file) = find_cursor_file(&theme, name) else {
+        return Ok(None);
+    };
+
+    let (root, size) = {
+        let screen = conn.server_init().roots.get(screen).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("no screen {}", screen))
+        })?;
+        (screen.root, cursor_size(screen))
+    };
+    let mut frames = select_nominal_size(parse_xcursor(&std::fs::read(file)?)?, size);
+    if !render.animated {
+        frames.truncate(1);
+    }
+
+    match &frames[..] {
+        [] => Ok(None),
+        [image] => Ok(Some(create_image_cursor(conn, &render, root, image)?)),
+        _ => {
+            let mut elements = Vec::new();
+            for image in &frames {
+                let cursor = create_image_cursor(conn, &render, root, image)?;
+                elements.push(AnimCursorElt {
+                    cursor,
+                    delay: image.delay,
+                });
+            }
+            let cid = conn.generate_id()?;
+            conn.send(
+                &RenderCreateAnimCursor::new(render.major_opcode, cid, &elements).as_bytes(),
+            )?;
+            // The animated cursor keeps its own references to the frames.
+            for element in elements {
+                conn.send(&FreeCursor::new(element.cursor).as_bytes())?;
+            }
+            Ok(Some(cid))
+        }
+    }
+}
+
+/// Creates the cursor `name` (such as `left_ptr` or `xterm`) from the Xcursor theme named by
+/// `XCURSOR_THEME`, at the size given by `cursor_size`.
+///
+/// If the server lacks RENDER cursors or the theme has no such cursor, falls back to the shape of
+/// the same name in the core cursor font.
+pub fn load_cursor<S: Read + Write>(
+    conn: &mut Connection<S>,
+    screen: usize,
+    name: &str,
+) -> Result<u32> {
+    if let Some(cursor) = load_theme_cursor(conn, screen, name)? {
+        return Ok(cursor);
+    }
+
+    let shape = cursor_font::by_name(name).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no cursor named {:?}", name),
+        )
+    })?;
+    create_font_cursor(conn, shape)
+}
@@ -0,0 +1,185 @@
+//! X Logical Font Description names, as returned by `ListFonts`.
+
+use std::fmt;
+
+/// The fields of an XLFD name, such as
+/// `-misc-fixed-medium-r-normal--13-120-75-75-c-70-iso10646-1`.
+///
+/// Fields are kept as strings since they may be wildcards in a pattern.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Xlfd {
+    pub foundry: String,
+    pub family: String,
+    pub weight: String,
+    pub slant: String,
+    pub setwidth: String,
+    pub add_style: String,
+    pub pixel_size: String,
+    pub point_size: String,
+    pub resolution_x: String,
+    pub resolution_y: String,
+    pub spacing: String,
+    pub average_width: String,
+    pub registry: String,
+    pub encoding: String,
+}
+
+impl Xlfd {
+    /// Parses a name with exactly fourteen fields, or returns `None` for other names such as
+    /// aliases like `fixed`.
+    pub fn parse(name: &str) -> Option<Self> {
+        let fields = name.strip_prefix('-')?.split('-').collect::<Vec<_>>();
+        let [foundry, family, weight, slant, setwidth, add_style, pixel_size, point_size, resolution_x, resolution_y, spacing, average_width, registry, encoding] =
+            fields[..]
+        else {
+            return None;
+        };
+
+        Some(Xlfd {
+            foundry: foundry.to_string(),
+            family: family.to_string(),
+            weight: weight.to_string(),
+            slant: slant.to_string(),
+            setwidth: setwidth.to_string(),
+            add_style: add_style.to_string(),
+            pixel_size: pixel_size.to_string(),
+            point_size: point_size.to_string(),
+            resolution_x: resolution_x.to_string(),
+            resolution_y: resolution_y.to_string(),
+            spacing: spacing.to_string(),
+            average_width: average_width.to_string(),
+            registry: registry.to_string(),
+            encoding: encoding.to_string(),
+        })
+    }
+
+    /// Returns the pixel size, if it is a plain number rather than a wildcard or matrix.
+    pub fn pixel_size(&self) -> Option<u32> {
+        self.pixel_size.parse().ok()
+    }
+
+    /// A scalable font has zero pixel size, point size and average width, and can be opened at
+    /// any size with `scaled`.
+    pub fn is_scalable(&self) -> bool {
+        self.pixel_size == "0" && self.point_size == "0" && self.average_width == "0"
+    }
+
+    /// Returns a name for opening a scalable font at `pixel_size`, leaving the derived fields
+    /// for the server to fill in.
+    pub fn scaled(&self, pixel_size: u32) -> Self {
+        let wildcard_zero = |field: &str| {
+            if field == "0" {
+                "*".to_string()
+            } else {
+                field.to_string()
+            }
+        };
+
+        Xlfd {
+            pixel_size: pixel_size.to_string(),
+            point_size: "*".to_string(),
+            resolution_x: wildcard_zero(&self.resolution_x),
+            resolution_y: wildcard_zero(&self.resolution_y),
+            average_width: "*".to_string(),
+            ..self.clone()
+        }
+    }
+
+    /// Whether the font uses the ISO10646-1 encoding, so that strings can be drawn with
+    /// `char2b_from_str` and `ImageText16` or `PolyText16`.
+    pub fn is_unicode(&self) -> bool {
+        self.registry.eq_ignore_ascii_case("iso10646") && self.encoding == "1"
+    }
+}
+
+impl fmt::Display for Xlfd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "-{}-{}-{}-{}-{}-{}-{}-{}-{}-{}-{}-{}-{}-{}",
+            self.foundry,
+            self.family,
+            self.weight,
+            self.slant,
+            self.setwidth,
+            self.add_style,
+            self.pixel_size,
+            self.point_size,
+            self.resolution_x,
+            self.resolution_y,
+            self.spacing,
+            self.average_width,
+            self.registry,
+            self.encoding
+        )
+    }
+}
+
+/// Matches a font name against a pattern the way the server does for `ListFonts`: `*` matches
+/// any run of characters, `?` matches any single character, and case is ignored.
+pub fn font_name_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    let name = name.as_bytes();
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the most recent `*` if the rest fails to match.
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                p += 1;
+                backtrack = Some((p, n));
+            }
+            Some(&c) if c == b'?' || c.eq_ignore_ascii_case(&name[n]) => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    backtrack = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Picks the font from `names` (typically a `ListFonts` reply) closest to `pixel_size`.
+///
+/// A font of exactly that size is preferred, then a scalable font opened at that size, then
+/// the font of nearest size. Names that are not XLFD names are ignored. The returned name can be
+/// passed to `OpenFont`.
+pub fn best_font_match(names: &[String], pixel_size: u32) -> Option<String> {
+    let mut scalable = None;
+    let mut nearest: Option<(u32, &String)> = None;
+
+    for name in names {
+        let Some(xlfd) = Xlfd::parse(name) else {
+            continue;
+        };
+        if xlfd.is_scalable() {
+            scalable.get_or_insert(xlfd);
+            continue;
+        }
+        let Some(size) = xlfd.pixel_size() else {
+            continue;
+        };
+
+        let distance = size.abs_diff(pixel_size);
+        if distance == 0 {
+            return Some(name.clone());
+        }
+        if nearest.map_or(true, |(best, _)| distance < best) {
+            nearest = Some((distance, name));
+        }
+    }
+
+    match scalable {
+        Some(xlfd) => Some(xlfd.scaled(pixel_size).to_string()),
+        None => nearest.map(|(_, name)| name.clone()),
+    }
+}